@@ -1,7 +1,7 @@
 use crate::exports::jsongrep::jsongrep::jsongrep::{
     Guest, TimingResults, Timings,
 };
-use jsongrep::query::QueryDFA;
+use jsongrep::{Document, Format, query::QueryDFA};
 use wasip2::clocks::monotonic_clock;
 
 wit_bindgen::generate!(
@@ -16,7 +16,7 @@ wit_bindgen::generate!(
 struct JsonGrepper;
 
 /// Normalize input to a JSON string. YAML and other text formats are converted through
-/// `serde_json::Value` so that serde_json_borrow::Value can safely borrow from the resulting JSON
+/// [`Document`] so that serde_json_borrow::Value can safely borrow from the resulting JSON
 /// string.
 fn normalize_to_json(input: &str) -> Result<String, String> {
     // Try JSON first -> if valid, return as-is
@@ -24,11 +24,13 @@ fn normalize_to_json(input: &str) -> Result<String, String> {
         return Ok(input.to_string());
     }
 
-    // Try YAML -> must round-trip through serde_json::Value
-    match serde_yaml::from_str::<serde_json::Value>(input) {
-        Ok(value) => serde_json::to_string(&value).map_err(|e| e.to_string()),
+    // Try YAML -> must round-trip through the document's value
+    match Document::from_bytes(input.as_bytes().to_vec(), Format::Yaml) {
+        Ok(doc) => {
+            serde_json::to_string(doc.value()).map_err(|e| e.to_string())
+        }
         Err(yaml_err) => {
-            Err(format!("Failed to parse as JSON or YAML: {yaml_err}"))
+            Err(format!("Failed to parse as JSON or YAML: {yaml_err:#}"))
         }
     }
 }
@@ -0,0 +1,414 @@
+/*!
+# Structural Diff
+
+Comparing two documents value by value, reporting what was added, removed,
+or changed at each path, whatever the formats (and key order) the documents
+were written in.
+
+Objects are compared member by member, in any order. Arrays are compared
+element by element at the same index, so inserting an element near the
+start of an array reports the later elements as changed and the last one as
+added. Values of different types, such as `1` and `"1"`, are changed.
+
+A diff can be restricted to the values a query matches in either document
+with [`diff_matches`], and turned into an [RFC 6902] JSON Patch with
+[`to_patch`].
+
+# Examples
+
+```
+use jsongrep::{diff, edit::patch, query::QueryDFA};
+use serde_json::json;
+
+let old = json!({"image": "nginx:1.25", "ports": [80, 443], "debug": true});
+let new = json!({"ports": [80], "image": "nginx:1.27", "replicas": 2});
+
+let changes = diff::diff(&old, &new);
+assert_eq!(
+    diff::to_json(&changes),
+    json!([
+        {"op": "changed", "path": ["image"], "old": "nginx:1.25", "new": "nginx:1.27"},
+        {"op": "removed", "path": ["ports", 1], "value": 443},
+        {"op": "removed", "path": ["debug"], "value": true},
+        {"op": "added", "path": ["replicas"], "value": 2},
+    ])
+);
+
+let mut patched = old.clone();
+patch::apply_patch(&mut patched, &diff::to_patch(&changes)).unwrap();
+assert_eq!(patched, new);
+
+let dfa = QueryDFA::from_query_str("image").unwrap();
+assert_eq!(diff::diff_matches(&old, &new, &dfa).len(), 1);
+```
+
+[RFC 6902]: https://datatracker.ietf.org/doc/html/rfc6902
+*/
+use colored::Colorize;
+use serde_json::Value;
+use std::{
+    collections::HashSet,
+    io::{self, Write},
+};
+
+use crate::{
+    edit::{get, outermost_matches},
+    query::{PathType, QueryDFA},
+    utils::{PathFormat, format_path, write_colored_json},
+};
+
+/// A difference between two documents at one path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// Only the new document has a value at `path`.
+    Added {
+        /// The location of the value.
+        path: Vec<PathType>,
+        /// The new value.
+        value: Value,
+    },
+    /// Only the old document has a value at `path`.
+    Removed {
+        /// The location of the value.
+        path: Vec<PathType>,
+        /// The old value.
+        value: Value,
+    },
+    /// Both documents have a value at `path`, but not the same one, and
+    /// they are not both objects or both arrays.
+    Changed {
+        /// The location of the values.
+        path: Vec<PathType>,
+        /// The value in the old document.
+        old: Value,
+        /// The value in the new document.
+        new: Value,
+    },
+}
+
+impl Change {
+    /// The location of the change.
+    #[must_use]
+    pub fn path(&self) -> &[PathType] {
+        match self {
+            Self::Added { path, .. }
+            | Self::Removed { path, .. }
+            | Self::Changed { path, .. } => path,
+        }
+    }
+}
+
+/// The changes that turn `old` into `new`, in document order: the members
+/// of an object in the order of `old`, then the members only `new` has.
+#[must_use]
+pub fn diff(old: &Value, new: &Value) -> Vec<Change> {
+    let mut changes = Vec::new();
+    compare(&mut changes, Vec::new(), Some(old), Some(new));
+    changes
+}
+
+/// Like [`diff`], but comparing only the values `dfa` matches in either
+/// document, and what is inside them.
+///
+/// Matches in `old` come first, in document order, then the values added
+/// where only `new` has a match. A match is added whole even if `old` lacks
+/// its parent, so a JSON Patch of such a diff only applies to documents
+/// that have it.
+#[must_use]
+pub fn diff_matches(old: &Value, new: &Value, dfa: &QueryDFA) -> Vec<Change> {
+    let mut paths = outermost_matches(old, dfa);
+    let matched: HashSet<Vec<PathType>> = paths.iter().cloned().collect();
+    // Whether a path matches depends only on the path, so a match in `new`
+    // that `old` has, or is inside a value `old` has, was matched in `old`
+    // too.
+    for path in outermost_matches(new, dfa) {
+        if !(0..=path.len()).any(|len| matched.contains(&path[..len])) {
+            paths.push(path);
+        }
+    }
+
+    let mut changes = Vec::new();
+    for path in paths {
+        let (old, new) = (get(old, &path), get(new, &path));
+        compare(&mut changes, path, old, new);
+    }
+    changes
+}
+
+/// Appends the changes between `old` and `new`, the values (if any) at
+/// `path` in each document, to `changes`.
+fn compare(
+    changes: &mut Vec<Change>,
+    path: Vec<PathType>,
+    old: Option<&Value>,
+    new: Option<&Value>,
+) {
+    let mut stack = vec![(path, old, new)];
+    // Depth-first, children pushed in reverse, so changes come out in
+    // document order.
+    while let Some((path, old, new)) = stack.pop() {
+        let children = match (old, new) {
+            (Some(Value::Object(old)), Some(Value::Object(new))) => {
+                let removed_or_common = old.iter().map(|(key, value)| {
+                    let step = PathType::Field(key.as_str().into());
+                    (step, Some(value), new.get(key))
+                });
+                let added = new
+                    .iter()
+                    .filter(|(key, _)| !old.contains_key(*key))
+                    .map(|(key, value)| {
+                        (
+                            PathType::Field(key.as_str().into()),
+                            None,
+                            Some(value),
+                        )
+                    });
+                removed_or_common.chain(added).collect::<Vec<_>>()
+            }
+            (Some(Value::Array(old)), Some(Value::Array(new))) => (0..old
+                .len()
+                .max(new.len()))
+                .map(|idx| (PathType::Index(idx), old.get(idx), new.get(idx)))
+                .collect(),
+            (Some(old), Some(new)) => {
+                if old != new {
+                    changes.push(Change::Changed {
+                        path,
+                        old: old.clone(),
+                        new: new.clone(),
+                    });
+                }
+                continue;
+            }
+            (Some(value), None) => {
+                changes.push(Change::Removed { path, value: value.clone() });
+                continue;
+            }
+            (None, Some(value)) => {
+                changes.push(Change::Added { path, value: value.clone() });
+                continue;
+            }
+            (None, None) => continue,
+        };
+        for (step, old, new) in children.into_iter().rev() {
+            let mut child_path = path.clone();
+            child_path.push(step);
+            stack.push((child_path, old, new));
+        }
+    }
+}
+
+/// `changes` as a JSON array of objects, one per change, each with its
+/// kind (`op`), its `path` as an array of field names and indices, and its
+/// `value`, or its `old` and `new` values.
+#[must_use]
+pub fn to_json(changes: &[Change]) -> Value {
+    let path_json = |path: &[PathType]| -> Value {
+        path.iter()
+            .map(|step| match step {
+                PathType::Index(idx) => Value::from(*idx),
+                PathType::Field(name) => Value::from(&**name),
+            })
+            .collect()
+    };
+    changes
+        .iter()
+        .map(|change| match change {
+            Change::Added { path, value } => serde_json::json!({
+                "op": "added",
+                "path": path_json(path),
+                "value": value,
+            }),
+            Change::Removed { path, value } => serde_json::json!({
+                "op": "removed",
+                "path": path_json(path),
+                "value": value,
+            }),
+            Change::Changed { path, old, new } => serde_json::json!({
+                "op": "changed",
+                "path": path_json(path),
+                "old": old,
+                "new": new,
+            }),
+        })
+        .collect()
+}
+
+/// The JSON Patch that applies `changes`, as found by [`diff`] or
+/// [`diff_matches`], to the old document.
+///
+/// Removals come last, in reverse document order, so that removing an
+/// array element never shifts the path of another operation.
+#[must_use]
+pub fn to_patch(changes: &[Change]) -> Value {
+    let (removals, others): (Vec<_>, Vec<_>) = changes
+        .iter()
+        .partition(|change| matches!(change, Change::Removed { .. }));
+    others
+        .into_iter()
+        .chain(removals.into_iter().rev())
+        .map(|change| {
+            let path = format_path(change.path(), PathFormat::Pointer);
+            match change {
+                Change::Added { value, .. } => serde_json::json!({
+                    "op": "add", "path": path, "value": value,
+                }),
+                Change::Removed { .. } => serde_json::json!({
+                    "op": "remove", "path": path,
+                }),
+                Change::Changed { new, .. } => serde_json::json!({
+                    "op": "replace", "path": path, "value": new,
+                }),
+            }
+        })
+        .collect()
+}
+
+/// Writes `changes` for people, one line each.
+///
+/// Lines read `+ path = value` for added values, `- path = value` for
+/// removed ones, and `~ path = old -> new` for changed ones. Paths are in
+/// `jg` syntax and values compact JSON, colored like matches.
+///
+/// # Errors
+///
+/// Returns an error if writing fails.
+pub fn write_changes<W: Write>(
+    writer: &mut W,
+    changes: &[Change],
+) -> io::Result<()> {
+    for change in changes {
+        let marker = match change {
+            Change::Added { .. } => "+".green(),
+            Change::Removed { .. } => "-".red(),
+            Change::Changed { .. } => "~".yellow(),
+        };
+        write!(writer, "{} ", marker.bold())?;
+        // The whole document has the empty path.
+        if !change.path().is_empty() {
+            let path = format_path(change.path(), PathFormat::Jg);
+            write!(writer, "{} ", path.bold().magenta())?;
+        }
+        write!(writer, "= ")?;
+        match change {
+            Change::Added { value, .. } | Change::Removed { value, .. } => {
+                write_colored_json(writer, &value.into(), 0, false)?;
+            }
+            Change::Changed { old, new, .. } => {
+                write_colored_json(writer, &old.into(), 0, false)?;
+                write!(writer, " -> ")?;
+                write_colored_json(writer, &new.into(), 0, false)?;
+            }
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Unit testing.")]
+mod tests {
+    use super::*;
+    use crate::edit::patch::apply_patch;
+    use serde_json::json;
+
+    /// The paths of `changes` in `jg` syntax, each prefixed with the kind
+    /// of change.
+    fn summary(changes: &[Change]) -> Vec<String> {
+        changes
+            .iter()
+            .map(|change| {
+                let marker = match change {
+                    Change::Added { .. } => '+',
+                    Change::Removed { .. } => '-',
+                    Change::Changed { .. } => '~',
+                };
+                format!(
+                    "{marker}{}",
+                    format_path(change.path(), PathFormat::Jg)
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn diff_reports_changes_by_path() {
+        let old = json!({
+            "a": {"x": 1, "y": [1, 2, 3]},
+            "b": "same",
+            "c": {"k": 1},
+            "d": [],
+        });
+        let new = json!({
+            "d": [{}],
+            "c": "1",
+            "b": "same",
+            "a": {"y": [1, 5], "z": null, "x": 1},
+        });
+        assert_eq!(
+            summary(&diff(&old, &new)),
+            ["~a.y.[1]", "-a.y.[2]", "+a.z", "~c", "+d.[0]"]
+        );
+        assert_eq!(diff(&old, &old), []);
+        assert_eq!(
+            diff(&json!(1), &json!(1.0)),
+            [Change::Changed { path: vec![], old: json!(1), new: json!(1.0) }]
+        );
+    }
+
+    #[test]
+    fn diff_matches_compares_only_matched_values() {
+        let old = json!({"env": {"a": 1, "b": 2}, "logs": [1], "x": {"y": 1}});
+        let new = json!({"env": {"a": 3}, "logs": [1, 2], "z": {"y": 2}});
+        let changes = |query| {
+            let dfa = QueryDFA::from_query_str(query).unwrap();
+            summary(&diff_matches(&old, &new, &dfa))
+        };
+        assert_eq!(changes("env"), ["~env.a", "-env.b"]);
+        assert_eq!(changes("env.b | logs"), ["-env.b", "+logs.[1]"]);
+        assert_eq!(changes("*.y"), ["-x.y", "+z.y"]);
+        assert_eq!(changes("nope"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn patches_turn_the_old_document_into_the_new() {
+        for (old, new) in [
+            (json!({"a": [1, 2, 3, 4], "b": 1}), json!({"a": [0], "c": [2]})),
+            (json!([[1, 2, 3], 4]), json!([[1], 4, {"x": [1, 2]}])),
+            (json!({"a": 1}), json!("scalar")),
+        ] {
+            let mut patched = old.clone();
+            apply_patch(&mut patched, &to_patch(&diff(&old, &new))).unwrap();
+            assert_eq!(patched, new);
+        }
+
+        // Restricted diffs may remove elements of an array in document
+        // order, as separate matches.
+        let (old, new) = (json!({"a": [1, 2, 3, 4]}), json!({"a": [1]}));
+        let dfa = QueryDFA::from_query_str("a.[*]").unwrap();
+        let mut patched = old.clone();
+        let changes = diff_matches(&old, &new, &dfa);
+        assert_eq!(summary(&changes), ["-a.[1]", "-a.[2]", "-a.[3]"]);
+        apply_patch(&mut patched, &to_patch(&changes)).unwrap();
+        assert_eq!(patched, new);
+    }
+
+    #[test]
+    fn changes_are_written_one_per_line() {
+        colored::control::set_override(false);
+        let changes = diff(
+            &json!({"a": {"b": 1}, "c": [true]}),
+            &json!({"a": {"b": "1"}, "d e": {}}),
+        );
+        let mut out = Vec::new();
+        write_changes(&mut out, &changes).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "~ a.b = 1 -> \"1\"\n- c = [true]\n+ \"d e\" = {}\n"
+        );
+
+        let mut out = Vec::new();
+        write_changes(&mut out, &diff(&json!(1), &json!(2))).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "~ = 1 -> 2\n");
+    }
+}
@@ -0,0 +1,321 @@
+/*!
+# Documents
+
+Loading a document of any supported format into a [`Value`] that can be
+queried.
+
+A [`Value`] borrows from the text it was parsed from, so using one normally
+means keeping that text alive alongside it. A [`Document`] owns its input
+buffer (or, for formats that are not JSON, the converted value) and hands
+out a [`Value`] borrowed from itself, so it can be stored and passed around
+like any owned value.
+
+The format can be given explicitly, detected from a file extension, or
+sniffed from the content (see [`sniff_format`]). Formats whose cargo
+feature (`yaml`, `toml`, `cbor`, `msgpack`) is disabled fail to load with an
+error saying so.
+
+# Examples
+
+```
+# #[cfg(feature = "yaml")] {
+use jsongrep::{Document, Format, query::QueryDFA};
+
+let doc = Document::from_bytes(b"users:\n  - name: Alice\n".to_vec(), Format::Auto)
+    .unwrap();
+assert_eq!(doc.format(), Format::Yaml);
+
+let dfa = QueryDFA::from_query_str("users[*].name").unwrap();
+let results = dfa.find(doc.value());
+assert_eq!(results[0].value.to_string(), r#""Alice""#);
+# }
+```
+*/
+use anyhow::{Context as _, Result};
+use serde::de::IgnoredAny;
+use serde_json_borrow::Value;
+use std::path::Path;
+
+use crate::search::{
+    Format, format_from_extension, parse_jsonl, to_owned_json,
+};
+
+/// A parsed document that owns its data.
+///
+/// See the [module documentation](self) for details.
+#[derive(Debug)]
+pub struct Document {
+    /// Borrows from `_buffer`: the `'static` lifetime is a lie that is never
+    /// handed out (see [`Document::value`]). Declared first so that it is
+    /// dropped before the buffer.
+    value: Value<'static>,
+    _buffer: Buffer,
+    format: Format,
+}
+
+/// The data a [`Document`]'s value borrows from. Both variants keep that
+/// data on the heap, so it stays put when the document is moved.
+#[derive(Debug)]
+#[expect(dead_code, reason = "Only kept alive for the value to borrow from.")]
+enum Buffer {
+    /// The input text, for JSON and JSONL.
+    Text(String),
+    /// The converted value, for every other format.
+    Owned(Box<serde_json::Value>),
+}
+
+impl Document {
+    /// Parse `bytes` as `format`, sniffing the format from the content when
+    /// it is [`Format::Auto`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `bytes` is not valid in the format, or the
+    /// format's cargo feature is disabled.
+    pub fn from_bytes(bytes: Vec<u8>, format: Format) -> Result<Self> {
+        let format = match format {
+            Format::Auto => sniff_format(&bytes),
+            explicit => explicit,
+        };
+
+        match format {
+            Format::Auto | Format::Json | Format::Jsonl => {
+                let text = String::from_utf8(bytes).map_err(|_| {
+                    anyhow::anyhow!("{format} input is not valid UTF-8")
+                })?;
+                let value = if format == Format::Jsonl {
+                    parse_jsonl(&text)?
+                } else {
+                    serde_json::from_str(&text).with_context(|| {
+                        format!("Failed to parse as {format}")
+                    })?
+                };
+                // SAFETY: `value` borrows from the heap data of `text`, which
+                // is moved into `_buffer` below and never modified, moved
+                // out, or dropped before `value`.
+                let value = unsafe { extend_lifetime(value) };
+                Ok(Self { value, _buffer: Buffer::Text(text), format })
+            }
+            other => {
+                let owned = Box::new(to_owned_json(&bytes, other)?);
+                // SAFETY: as above; the box keeps the converted value at a
+                // fixed address.
+                let value = unsafe { extend_lifetime(Value::from(&*owned)) };
+                Ok(Self { value, _buffer: Buffer::Owned(owned), format })
+            }
+        }
+    }
+
+    /// Read and parse the file at `path` as `format`. With
+    /// [`Format::Auto`], the format is detected from the file extension, or
+    /// sniffed from the content when the extension is unknown.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, or as
+    /// [`Document::from_bytes`].
+    pub fn from_path(path: &Path, format: Format) -> Result<Self> {
+        let bytes = std::fs::read(path).with_context(|| {
+            format!("Failed to read file {}", path.display())
+        })?;
+        let format = match format {
+            Format::Auto => format_from_extension(path).unwrap_or(Format::Auto),
+            explicit => explicit,
+        };
+        Self::from_bytes(bytes, format)
+    }
+
+    /// The parsed document.
+    #[must_use]
+    pub const fn value(&self) -> &Value<'_> {
+        &self.value
+    }
+
+    /// The format the document was parsed as; never [`Format::Auto`].
+    #[must_use]
+    pub const fn format(&self) -> Format {
+        self.format
+    }
+}
+
+/// Erases the lifetime of a value borrowed from a [`Document`]'s buffer.
+///
+/// # Safety
+///
+/// The data `value` borrows from must outlive the returned value.
+const unsafe fn extend_lifetime(value: Value<'_>) -> Value<'static> {
+    // SAFETY: upheld by the caller.
+    unsafe { std::mem::transmute::<Value<'_>, Value<'static>>(value) }
+}
+
+/// Guess the format of `bytes` from their content.
+///
+/// This is a best-effort heuristic for inputs without a file name:
+///
+/// - Input that is not UTF-8 is binary: CBOR if it starts with a CBOR map
+///   or the CBOR self-describe tag, `MessagePack` otherwise.
+/// - Text that parses as one JSON value is JSON.
+/// - Text whose first line is a JSON object or array on its own is JSONL.
+/// - Text whose first line (ignoring comments) is a `[table]` header or a
+///   `key = value` pair is TOML.
+/// - Text starting with `{` or `[` is JSON (so that parse errors are
+///   reported as JSON errors); anything else is YAML.
+#[must_use]
+pub fn sniff_format(bytes: &[u8]) -> Format {
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    let Ok(text) = str::from_utf8(bytes) else {
+        // CBOR maps are major type 5 (0xa0..=0xbf); 0xd9 starts the
+        // self-describe tag 55799.
+        return match bytes.first() {
+            Some(0xa0..=0xbf | 0xd9) => Format::Cbor,
+            _ => Format::Msgpack,
+        };
+    };
+
+    if serde_json::from_str::<IgnoredAny>(text).is_ok() {
+        return Format::Json;
+    }
+
+    let first_line = text
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .unwrap_or_default();
+
+    if first_line.starts_with(['{', '['])
+        && serde_json::from_str::<IgnoredAny>(first_line).is_ok()
+    {
+        Format::Jsonl
+    } else if is_toml_line(first_line) {
+        Format::Toml
+    } else if first_line.starts_with(['{', '[']) {
+        Format::Json
+    } else {
+        Format::Yaml
+    }
+}
+
+/// Whether `line` looks like a TOML table header (`[a.b]`, `[[a]]`) or
+/// key/value pair (`a.b = 1`).
+fn is_toml_line(line: &str) -> bool {
+    let is_key = |key: &str| {
+        !key.is_empty()
+            && key.chars().all(|c| {
+                c.is_ascii_alphanumeric()
+                    || matches!(c, '_' | '-' | '.' | '"' | '\'' | ' ')
+            })
+    };
+
+    if let Some(header) = line.strip_prefix('[') {
+        let header = header.strip_prefix('[').unwrap_or(header);
+        return header
+            .split_once(']')
+            .is_some_and(|(key, _)| is_key(key.trim()));
+    }
+    line.split_once('=').is_some_and(|(key, _)| is_key(key.trim()))
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Unit testing.")]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sniff_format_recognizes_each_format() {
+        let cases: [(&[u8], Format); 8] = [
+            (br#"{"a": [1, 2]}"#, Format::Json),
+            (b"{\"a\": 1}\n{\"a\": 2}\n", Format::Jsonl),
+            (b"# config\n[server]\nport = 80\n", Format::Toml),
+            (b"name = \"jg\"\n", Format::Toml),
+            (b"a:\n  - 1\n", Format::Yaml),
+            (b"{ not json", Format::Json),
+            (b"\xa1\x61a\x01", Format::Cbor),
+            (b"\x81\xa1a\x01", Format::Msgpack),
+        ];
+        for (bytes, format) in cases {
+            assert_eq!(sniff_format(bytes), format, "{bytes:?}");
+        }
+    }
+
+    #[test]
+    fn from_bytes_parses_every_format() {
+        let json = r#"{"a":[1,"x"]}"#;
+        let owned: serde_json::Value = serde_json::from_str(json).unwrap();
+
+        let mut cbor = Vec::new();
+        ciborium::into_writer(&owned, &mut cbor).unwrap();
+        let msgpack = rmp_serde::to_vec(&owned).unwrap();
+
+        let mut cases = vec![(json.as_bytes().to_vec(), Format::Json)];
+        if cfg!(feature = "yaml") {
+            cases.push((b"a:\n  - 1\n  - x\n".to_vec(), Format::Yaml));
+        }
+        if cfg!(feature = "toml") {
+            cases.push((b"a = [1, \"x\"]\n".to_vec(), Format::Toml));
+        }
+        if cfg!(feature = "cbor") {
+            cases.push((cbor, Format::Cbor));
+        }
+        if cfg!(feature = "msgpack") {
+            cases.push((msgpack, Format::Msgpack));
+        }
+        for (bytes, format) in cases {
+            let doc = Document::from_bytes(bytes, Format::Auto).unwrap();
+            assert_eq!(doc.format(), format);
+            assert_eq!(doc.value().to_string(), json, "{format}");
+        }
+
+        let doc = Document::from_bytes(b"1\n\n[2]\n".to_vec(), Format::Jsonl)
+            .unwrap();
+        assert_eq!(doc.value().to_string(), "[1,[2]]");
+    }
+
+    #[test]
+    fn from_bytes_reports_parse_errors() {
+        let err =
+            Document::from_bytes(b"{".to_vec(), Format::Auto).unwrap_err();
+        assert_eq!(err.to_string(), "Failed to parse as JSON");
+
+        let err =
+            Document::from_bytes(b"\xff".to_vec(), Format::Json).unwrap_err();
+        assert_eq!(err.to_string(), "JSON input is not valid UTF-8");
+    }
+
+    #[test]
+    fn disabled_formats_are_errors() {
+        for (enabled, format, feature) in [
+            (cfg!(feature = "yaml"), Format::Yaml, "yaml"),
+            (cfg!(feature = "toml"), Format::Toml, "toml"),
+            (cfg!(feature = "cbor"), Format::Cbor, "cbor"),
+            (cfg!(feature = "msgpack"), Format::Msgpack, "msgpack"),
+        ] {
+            if enabled {
+                continue;
+            }
+            let err = Document::from_bytes(b"1".to_vec(), format).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!(
+                    "{format} support not enabled. Rebuild with --features \
+                     {feature}"
+                )
+            );
+        }
+    }
+
+    #[cfg(all(feature = "yaml", feature = "toml"))]
+    #[test]
+    fn from_path_detects_by_extension_then_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let yaml = dir.path().join("config.yml");
+        let unknown = dir.path().join("config");
+        std::fs::write(&yaml, "[1, 2]").unwrap();
+        std::fs::write(&unknown, "a = 1").unwrap();
+
+        let doc = Document::from_path(&yaml, Format::Auto).unwrap();
+        assert_eq!(doc.format(), Format::Yaml);
+        let doc = Document::from_path(&unknown, Format::Auto).unwrap();
+        assert_eq!(doc.format(), Format::Toml);
+        assert_eq!(doc.value().to_string(), r#"{"a":1}"#);
+    }
+}
@@ -0,0 +1,547 @@
+/*!
+# Editing
+
+Changing a document at the paths a query matches.
+
+Queries run over borrowed documents, so edits work on an owned
+[`serde_json::Value`] instead, in two steps: a query is first turned into a
+list of [`Operation`]s, one per matched path, which are then [applied](apply)
+to the document: [`plan_set`] replaces matches and [`plan_delete`] removes
+them. Keeping the plan separate from the document lets the same
+operations be shown, checked, or applied to another copy, for example as a
+JSON Patch with [`patch::to_patch`]. [`redact`] plans replacing matched
+values with placeholders or hashes. [`prune`] instead copies out only the
+matched parts of a document.
+
+# Examples
+
+```
+use jsongrep::{
+    edit::{self, Template},
+    query::QueryDFA,
+};
+
+let mut doc = serde_json::json!({
+    "services": {"web": {"image": "nginx:1.25"}, "db": {"image": "pg:16"}}
+});
+let dfa = QueryDFA::from_query_str("services.*.image").unwrap();
+let template = Template::parse(r#""{{value}}-patched""#).unwrap();
+
+let ops = edit::plan_set(&doc, &dfa, &template);
+assert_eq!(ops.len(), 2);
+edit::apply(&mut doc, &ops).unwrap();
+assert_eq!(doc["services"]["web"]["image"], "nginx:1.25-patched");
+```
+*/
+pub mod patch;
+pub mod redact;
+pub mod source;
+
+use anyhow::{Context as _, Result, bail};
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::{
+    query::{JSONPointer, PathType, QueryDFA},
+    utils::{PathFormat, format_path},
+};
+
+/// A change to a document at one path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operation {
+    /// Replace the value at `path` with `value`.
+    Replace {
+        /// The location to replace.
+        path: Vec<PathType>,
+        /// The new value.
+        value: Value,
+    },
+    /// Remove the object member or array element at `path`; later
+    /// elements of the array shift down by one.
+    Remove {
+        /// The location to remove.
+        path: Vec<PathType>,
+    },
+}
+
+impl Operation {
+    /// The location the operation changes.
+    #[must_use]
+    pub fn path(&self) -> &[PathType] {
+        match self {
+            Self::Replace { path, .. } | Self::Remove { path } => path,
+        }
+    }
+}
+
+/// A JSON value to set at matched paths, in which strings may refer to the
+/// match they replace:
+///
+/// - `{{value}}`: the matched value. A string that is exactly `"{{value}}"`
+///   becomes the value itself; inside other text, a matched string is
+///   inserted as is and any other value as JSON.
+/// - `{{key}}`: the last field name or index of the matched path.
+/// - `{{path}}`: the matched path, in query syntax.
+///
+/// A template without placeholders is just a constant value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    value: Value,
+}
+
+impl Template {
+    /// Parse a template from its JSON text.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `text` is not valid JSON.
+    pub fn parse(text: &str) -> Result<Self> {
+        let value = serde_json::from_str(text).with_context(|| {
+            format!(
+                "Invalid value {text:?}: expected JSON (quote strings, e.g. \
+                 '\"text\"')"
+            )
+        })?;
+        Ok(Self { value })
+    }
+
+    /// The value to set at `path`, where `current` is.
+    #[must_use]
+    pub fn render(&self, path: &[PathType], current: &Value) -> Value {
+        let mut rendered = self.value.clone();
+        let mut stack = vec![&mut rendered];
+        while let Some(value) = stack.pop() {
+            match value {
+                Value::String(text) => {
+                    if let Some(whole) = render_whole(text, path, current) {
+                        *value = whole;
+                    } else if text.contains("{{") {
+                        *text = render_text(text, path, current);
+                    }
+                }
+                Value::Array(items) => stack.extend(items.iter_mut()),
+                Value::Object(map) => stack.extend(map.values_mut()),
+                Value::Null | Value::Bool(_) | Value::Number(_) => {}
+            }
+        }
+        rendered
+    }
+}
+
+/// The typed value of a string that consists of a single placeholder.
+fn render_whole(
+    text: &str,
+    path: &[PathType],
+    current: &Value,
+) -> Option<Value> {
+    match text {
+        "{{value}}" => Some(current.clone()),
+        "{{key}}" => Some(match path.last() {
+            Some(PathType::Index(idx)) => Value::from(*idx),
+            Some(PathType::Field(name)) => Value::from(&**name),
+            None => Value::from(""),
+        }),
+        _ => None,
+    }
+}
+
+/// `text` with every placeholder replaced by its text. Placeholders are
+/// found in one left-to-right pass, so the inserted text is never expanded.
+fn render_text(text: &str, path: &[PathType], current: &Value) -> String {
+    let mut rendered = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(tail) = rest.strip_prefix("{{value}}") {
+            match current {
+                Value::String(s) => rendered.push_str(s),
+                other => rendered.push_str(&other.to_string()),
+            }
+            rest = tail;
+        } else if let Some(tail) = rest.strip_prefix("{{key}}") {
+            match path.last() {
+                Some(PathType::Index(idx)) => {
+                    rendered.push_str(&idx.to_string());
+                }
+                Some(PathType::Field(name)) => rendered.push_str(name),
+                None => {}
+            }
+            rest = tail;
+        } else if let Some(tail) = rest.strip_prefix("{{path}}") {
+            rendered.push_str(&format_path(path, PathFormat::Jg));
+            rest = tail;
+        } else {
+            rendered.push('{');
+            rest = &rest[1..];
+        }
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+/// The paths `dfa` matches in `doc`, in document order, leaving out paths
+/// inside another match: an edit at the outer match already covers them.
+#[must_use]
+pub fn outermost_matches(doc: &Value, dfa: &QueryDFA) -> Vec<Vec<PathType>> {
+    outermost(dfa.find_tree(doc))
+}
+
+/// The paths of `matches`, found in pre-order, that are not inside another.
+pub(super) fn outermost(
+    matches: Vec<JSONPointer<'_, Value>>,
+) -> Vec<Vec<PathType>> {
+    let mut paths: Vec<Vec<PathType>> = Vec::new();
+    for m in matches {
+        // Matches come in pre-order, so an enclosing match is always the
+        // last one kept.
+        if paths.last().is_some_and(|outer| m.path.starts_with(outer)) {
+            continue;
+        }
+        paths.push(m.path);
+    }
+    paths
+}
+
+/// Plan replacing every value `dfa` matches in `doc` with `template`,
+/// rendered for that match. Matches inside another match are replaced
+/// along with it.
+#[must_use]
+pub fn plan_set(
+    doc: &Value,
+    dfa: &QueryDFA,
+    template: &Template,
+) -> Vec<Operation> {
+    outermost_matches(doc, dfa)
+        .into_iter()
+        .filter_map(|path| {
+            let current = get(doc, &path)?;
+            let value = template.render(&path, current);
+            Some(Operation::Replace { path, value })
+        })
+        .collect()
+}
+
+/// Plan removing every value `dfa` matches in `doc`. Matches inside another
+/// match are removed along with it.
+///
+/// Removing an array element shifts the ones after it, so the operations
+/// are ordered deepest path first and, within an array, highest index
+/// first: applied in order, each path still points at the value it
+/// matched.
+#[must_use]
+pub fn plan_delete(doc: &Value, dfa: &QueryDFA) -> Vec<Operation> {
+    let mut paths = outermost_matches(doc, dfa);
+    // Reverse document order puts later siblings first; the stable sort
+    // keeps that order among paths of the same depth.
+    paths.reverse();
+    paths.sort_by_key(|path| std::cmp::Reverse(path.len()));
+    paths.into_iter().map(|path| Operation::Remove { path }).collect()
+}
+
+/// The parts of `doc` that `dfa` matches, at their original positions.
+///
+/// The result has the same shape as `doc` but holds only the matched values
+/// and the objects and arrays leading to them; it is `None` if nothing
+/// matched.
+///
+/// Object members keep their order; kept array elements keep their order
+/// but are renumbered from 0, so `a.[3]` alone becomes `a.[0]`.
+#[must_use]
+pub fn prune(doc: &Value, dfa: &QueryDFA) -> Option<Value> {
+    let paths = outermost_matches(doc, dfa);
+    if paths.first().is_some_and(Vec::is_empty) {
+        // The whole document matched.
+        return Some(doc.clone());
+    }
+    let mut pruned = empty_like(doc)?;
+    // For each array of `doc` (by address), the index of its element last
+    // copied into `pruned`, to tell whether the next path into the array
+    // needs a new element.
+    let mut last_kept: HashMap<*const Vec<Value>, usize> = HashMap::new();
+
+    for path in &paths {
+        let (last, parents) = path.split_last()?;
+        let (mut src, mut dst) = (doc, &mut pruned);
+        for part in parents {
+            (src, dst) = step(src, dst, part, &mut last_kept, empty_like)?;
+        }
+        step(src, dst, last, &mut last_kept, |value| Some(value.clone()))?;
+    }
+    (!paths.is_empty()).then_some(pruned)
+}
+
+/// Descends from `src` to its child at `part`, and from `dst`, its pruned
+/// copy, to the corresponding child, creating it with `init` if needed.
+fn step<'s, 'd>(
+    src: &'s Value,
+    dst: &'d mut Value,
+    part: &PathType,
+    last_kept: &mut HashMap<*const Vec<Value>, usize>,
+    init: impl FnOnce(&Value) -> Option<Value>,
+) -> Option<(&'s Value, &'d mut Value)> {
+    match (src, dst, part) {
+        (Value::Object(src), Value::Object(dst), PathType::Field(name)) => {
+            let child = src.get(&**name)?;
+            let copy = match dst.entry(&**name) {
+                serde_json::map::Entry::Occupied(entry) => entry.into_mut(),
+                serde_json::map::Entry::Vacant(entry) => {
+                    entry.insert(init(child)?)
+                }
+            };
+            Some((child, copy))
+        }
+        (Value::Array(src), Value::Array(dst), PathType::Index(idx)) => {
+            let child = src.get(*idx)?;
+            if last_kept.insert(std::ptr::from_ref(src), *idx) != Some(*idx) {
+                dst.push(init(child)?);
+            }
+            Some((child, dst.last_mut()?))
+        }
+        _ => None,
+    }
+}
+
+/// An empty object or array of the same kind as `value`, if it is one.
+fn empty_like(value: &Value) -> Option<Value> {
+    match value {
+        Value::Object(_) => Some(Value::Object(serde_json::Map::new())),
+        Value::Array(_) => Some(Value::Array(Vec::new())),
+        _ => None,
+    }
+}
+
+/// Apply `ops` to `doc`, in order.
+///
+/// # Errors
+///
+/// Returns an error if the path of an operation does not exist in `doc`,
+/// or an operation removes the whole document.
+pub fn apply(doc: &mut Value, ops: &[Operation]) -> Result<()> {
+    let missing = |path: &[PathType]| {
+        anyhow::anyhow!(
+            "Path {} does not exist",
+            format_path(path, PathFormat::Jg)
+        )
+    };
+    for op in ops {
+        match op {
+            Operation::Replace { path, value } => {
+                let target = get_mut(doc, path).ok_or_else(|| missing(path))?;
+                *target = value.clone();
+            }
+            Operation::Remove { path } => {
+                let Some((last, parent)) = path.split_last() else {
+                    bail!("Cannot remove the whole document");
+                };
+                let removed = match (get_mut(doc, parent), last) {
+                    (Some(Value::Object(map)), PathType::Field(name)) => {
+                        map.shift_remove(&**name)
+                    }
+                    (Some(Value::Array(items)), PathType::Index(idx))
+                        if *idx < items.len() =>
+                    {
+                        Some(items.remove(*idx))
+                    }
+                    _ => None,
+                };
+                removed.ok_or_else(|| missing(path))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The value at `path` in `doc`.
+#[must_use]
+pub fn get<'a>(doc: &'a Value, path: &[PathType]) -> Option<&'a Value> {
+    path.iter().try_fold(doc, |value, part| match (value, part) {
+        (Value::Object(map), PathType::Field(name)) => map.get(&**name),
+        (Value::Array(items), PathType::Index(idx)) => items.get(*idx),
+        _ => None,
+    })
+}
+
+/// The value at `path` in `doc`, mutably.
+pub fn get_mut<'a>(
+    doc: &'a mut Value,
+    path: &[PathType],
+) -> Option<&'a mut Value> {
+    path.iter().try_fold(doc, |value, part| match (value, part) {
+        (Value::Object(map), PathType::Field(name)) => map.get_mut(&**name),
+        (Value::Array(items), PathType::Index(idx)) => items.get_mut(*idx),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Unit testing.")]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn set(doc: &mut Value, query: &str, template: &str) -> usize {
+        let dfa = QueryDFA::from_query_str(query).unwrap();
+        let ops = plan_set(doc, &dfa, &Template::parse(template).unwrap());
+        apply(doc, &ops).unwrap();
+        ops.len()
+    }
+
+    #[test]
+    fn set_replaces_every_match() {
+        let mut doc = json!({"a": [{"x": 1}, {"x": 2}, {"y": 3}]});
+        assert_eq!(set(&mut doc, "a[*].x", "null"), 2);
+        assert_eq!(doc, json!({"a": [{"x": null}, {"x": null}, {"y": 3}]}));
+
+        assert_eq!(set(&mut doc, "nope", "1"), 0);
+        assert_eq!(set(&mut doc, "", r#"{"b": true}"#), 1);
+        assert_eq!(doc, json!({"b": true}));
+    }
+
+    #[test]
+    fn set_skips_matches_inside_other_matches() {
+        let mut doc = json!({"a": {"a": {"a": 1}}});
+        assert_eq!(set(&mut doc, "a*", r#""{{path}}""#), 1);
+        assert_eq!(doc, json!(""));
+
+        let mut doc = json!({"a": {"a": {"a": 1}}});
+        assert_eq!(set(&mut doc, "a.a*", r#""{{path}}""#), 1);
+        assert_eq!(doc, json!({"a": "a"}));
+    }
+
+    #[test]
+    fn templates_render_placeholders() {
+        let mut doc = json!({"img": {"web": "nginx", "n": [5]}});
+        set(&mut doc, "img.web", r#""{{value}}:1.27""#);
+        set(&mut doc, "img.n[0]", r#"{"was": "{{value}}", "at": "{{key}}"}"#);
+        assert_eq!(
+            doc,
+            json!({"img": {"web": "nginx:1.27", "n": [{"was": 5, "at": 0}]}})
+        );
+
+        set(&mut doc, "img.n[0].was", r#"["{{path}} was {{value}}"]"#);
+        assert_eq!(doc["img"]["n"][0]["was"], json!(["img.n.[0].was was 5"]));
+
+        // Inserted text is not expanded again.
+        let mut doc = json!({"a": "{{key}}", "b": "{{path}}"});
+        set(&mut doc, "a", r#""x-{{value}}""#);
+        set(&mut doc, "b", r#""{{{key}}}-{{value}}""#);
+        assert_eq!(doc, json!({"a": "x-{{key}}", "b": "{b}-{{path}}"}));
+    }
+
+    fn delete(doc: &mut Value, query: &str) -> usize {
+        let dfa = QueryDFA::from_query_str(query).unwrap();
+        let ops = plan_delete(doc, &dfa);
+        apply(doc, &ops).unwrap();
+        ops.len()
+    }
+
+    #[test]
+    fn delete_removes_every_match() {
+        let mut doc = json!({
+            "user": {"name": "a", "password": "x", "keys": [{"token": 1}]},
+            "token": 2
+        });
+        assert_eq!(delete(&mut doc, "(* | [*])*.(password | token)"), 3);
+        assert_eq!(doc, json!({"user": {"name": "a", "keys": [{}]}}));
+        assert_eq!(delete(&mut doc, "nope"), 0);
+    }
+
+    #[test]
+    fn delete_removes_several_elements_of_one_array() {
+        let mut doc = json!({"a": [0, 1, 2, 3, 4, [5, 6]], "b": [[0, 1], 2]});
+        assert_eq!(delete(&mut doc, "a.([1] | [3:5] | [5].[0])"), 4);
+        assert_eq!(delete(&mut doc, "b.([*] | [0].[*])"), 2);
+        assert_eq!(doc, json!({"a": [0, 2, [6]], "b": []}));
+    }
+
+    #[test]
+    fn delete_keeps_the_order_of_the_other_keys() {
+        let mut doc = json!({"a": 1, "b": 2, "c": 3, "d": 4});
+        delete(&mut doc, "a");
+        assert_eq!(doc.to_string(), r#"{"b":2,"c":3,"d":4}"#);
+    }
+
+    #[test]
+    fn delete_plans_deepest_and_highest_index_first() {
+        let doc = json!({"a": [[0, 1], 1, [2]], "b": 1});
+        let dfa = QueryDFA::from_query_str("b | a.([1] | [0].[*] | [2].[0])")
+            .unwrap();
+        let paths: Vec<_> = plan_delete(&doc, &dfa)
+            .iter()
+            .map(|op| format_path(op.path(), PathFormat::Jg))
+            .collect();
+        assert_eq!(
+            paths,
+            ["a.[2].[0]", "a.[0].[1]", "a.[0].[0]", "a.[1]", "b"]
+        );
+    }
+
+    #[test]
+    fn prune_keeps_matches_at_their_positions() {
+        let doc = json!({
+            "kind": "Deployment",
+            "spec": {
+                "replicas": 3,
+                "template": {"spec": {"containers": [
+                    {"name": "a", "image": "x"},
+                    {"name": "b"},
+                    {"name": "c", "image": "y", "ports": [1, 2, 3]}
+                ]}}
+            }
+        });
+        let dfa = QueryDFA::from_query_str(
+            "spec.(replicas | template.spec.containers[*].(image | ports[1:]))",
+        )
+        .unwrap();
+        assert_eq!(
+            prune(&doc, &dfa).unwrap(),
+            json!({
+                "spec": {
+                    "replicas": 3,
+                    "template": {"spec": {"containers": [
+                        {"image": "x"},
+                        {"image": "y", "ports": [2, 3]}
+                    ]}}
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn prune_without_matches_or_of_everything() {
+        let doc = json!([{"a": 1}, {"b": 2}]);
+        let prune_with =
+            |query| prune(&doc, &QueryDFA::from_query_str(query).unwrap());
+        assert_eq!(prune_with("nope"), None);
+        assert_eq!(prune_with("[*]*"), Some(doc.clone()));
+        assert_eq!(prune_with("[1].b"), Some(json!([{"b": 2}])));
+
+        // A scalar document can only match as a whole.
+        let dfa = QueryDFA::from_query_str("").unwrap();
+        assert_eq!(prune(&json!(5), &dfa), Some(json!(5)));
+        let dfa = QueryDFA::from_query_str("a").unwrap();
+        assert_eq!(prune(&json!(5), &dfa), None);
+    }
+
+    #[test]
+    fn template_parse_requires_json() {
+        let err = Template::parse("nginx").unwrap_err();
+        assert!(err.to_string().contains("expected JSON"), "{err}");
+    }
+
+    #[test]
+    fn apply_fails_on_missing_paths() {
+        let mut doc = json!({"a": 1});
+        let ops = [Operation::Replace {
+            path: vec![PathType::Field("b".into()), PathType::Index(0)],
+            value: json!(2),
+        }];
+        let err = apply(&mut doc, &ops).unwrap_err();
+        assert_eq!(err.to_string(), "Path b.[0] does not exist");
+
+        let ops = [Operation::Remove { path: vec![] }];
+        let err = apply(&mut doc, &ops).unwrap_err();
+        assert_eq!(err.to_string(), "Cannot remove the whole document");
+    }
+}
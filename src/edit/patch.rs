@@ -0,0 +1,346 @@
+/*!
+# JSON Patch
+
+[RFC 6902] JSON Patches: converting planned [`Operation`]s into a patch, and
+applying a patch to a document.
+
+A patch is a JSON array of operations, each addressing a location with an
+[RFC 6901] JSON Pointer:
+
+```json
+[
+  {"op": "replace", "path": "/services/web/image", "value": "nginx:1.27"},
+  {"op": "remove", "path": "/users/0/password"}
+]
+```
+
+[`apply_patch`] supports every operation of the RFC: `add`, `remove`,
+`replace`, `move`, `copy`, and `test`.
+
+# Examples
+
+```
+use jsongrep::{edit::{self, patch}, query::QueryDFA};
+
+let mut doc = serde_json::json!({"user": {"name": "a", "token": "t"}});
+let dfa = QueryDFA::from_query_str("user.token").unwrap();
+
+let ops = edit::plan_delete(&doc, &dfa);
+let json_patch = patch::to_patch(&ops);
+assert_eq!(
+    json_patch,
+    serde_json::json!([{"op": "remove", "path": "/user/token"}])
+);
+
+patch::apply_patch(&mut doc, &json_patch).unwrap();
+assert_eq!(doc, serde_json::json!({"user": {"name": "a"}}));
+```
+
+[RFC 6902]: https://datatracker.ietf.org/doc/html/rfc6902
+[RFC 6901]: https://datatracker.ietf.org/doc/html/rfc6901
+*/
+use anyhow::{Context as _, Result, anyhow, bail};
+use serde_json::Value;
+
+use super::Operation;
+use crate::utils::{PathFormat, format_path};
+
+/// The JSON Patch equivalent to applying `ops` in order.
+#[must_use]
+pub fn to_patch(ops: &[Operation]) -> Value {
+    ops.iter()
+        .map(|op| match op {
+            Operation::Replace { path, value } => serde_json::json!({
+                "op": "replace",
+                "path": format_path(path, PathFormat::Pointer),
+                "value": value,
+            }),
+            Operation::Remove { path } => serde_json::json!({
+                "op": "remove",
+                "path": format_path(path, PathFormat::Pointer),
+            }),
+        })
+        .collect()
+}
+
+/// Apply the JSON Patch `patch` to `doc`.
+///
+/// Patches are atomic: if any operation fails, `doc` is left unchanged.
+///
+/// # Errors
+///
+/// Returns an error, naming the failing operation by its index, if `patch`
+/// is not an array of well-formed operations, an operation addresses a
+/// location that does not exist, or a `test` operation fails.
+pub fn apply_patch(doc: &mut Value, patch: &Value) -> Result<()> {
+    let ops = patch.as_array().context("A JSON Patch must be an array")?;
+    let mut patched = doc.clone();
+    for (i, op) in ops.iter().enumerate() {
+        apply_one(&mut patched, op)
+            .with_context(|| format!("Patch operation {i} failed"))?;
+    }
+    *doc = patched;
+    Ok(())
+}
+
+/// Apply a single patch operation.
+fn apply_one(doc: &mut Value, op: &Value) -> Result<()> {
+    let member = |name: &str| {
+        op.get(name).ok_or_else(|| anyhow!("Missing {name:?} member"))
+    };
+    let pointer = |name: &str| -> Result<Vec<String>> {
+        let text = member(name)?
+            .as_str()
+            .ok_or_else(|| anyhow!("{name:?} must be a string"))?;
+        parse_pointer(text)
+    };
+
+    let path = pointer("path")?;
+    match member("op")?.as_str() {
+        Some("add") => add(doc, &path, member("value")?.clone()),
+        Some("remove") => remove(doc, &path).map(drop),
+        Some("replace") => {
+            *resolve_mut(doc, &path)? = member("value")?.clone();
+            Ok(())
+        }
+        Some("move") => {
+            let from = pointer("from")?;
+            if path.len() > from.len() && path.starts_with(&from) {
+                bail!("Cannot move a value into one of its children");
+            }
+            let value = remove(doc, &from)?;
+            add(doc, &path, value)
+        }
+        Some("copy") => {
+            let value = resolve_mut(doc, &pointer("from")?)?.clone();
+            add(doc, &path, value)
+        }
+        Some("test") => {
+            if *resolve_mut(doc, &path)? == *member("value")? {
+                Ok(())
+            } else {
+                bail!(
+                    "Test failed: {} has a different value",
+                    pointer_text(&path)
+                )
+            }
+        }
+        _ => bail!("Unknown operation {}", member("op")?),
+    }
+}
+
+/// The reference tokens of an RFC 6901 JSON Pointer.
+///
+/// # Errors
+///
+/// Returns an error if `pointer` is neither empty nor starts with `/`.
+fn parse_pointer(pointer: &str) -> Result<Vec<String>> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+    let Some(tokens) = pointer.strip_prefix('/') else {
+        bail!("Invalid JSON Pointer {pointer:?}: must start with '/'");
+    };
+    Ok(tokens
+        .split('/')
+        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+        .collect())
+}
+
+/// `tokens` as a JSON Pointer again, for messages.
+fn pointer_text(tokens: &[String]) -> String {
+    tokens.iter().fold(String::new(), |mut text, token| {
+        text.push('/');
+        text.push_str(&token.replace('~', "~0").replace('/', "~1"));
+        text
+    })
+}
+
+/// The array index a reference token names, if it is one.
+fn parse_index(token: &str) -> Option<usize> {
+    // RFC 6901: no leading zeros, digits only.
+    if token.is_empty()
+        || (token.len() > 1 && token.starts_with('0'))
+        || !token.bytes().all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+    token.parse().ok()
+}
+
+/// The value at `tokens` in `doc`.
+fn resolve_mut<'a>(
+    doc: &'a mut Value,
+    tokens: &[String],
+) -> Result<&'a mut Value> {
+    let mut value = doc;
+    for (depth, token) in tokens.iter().enumerate() {
+        let child = match value {
+            Value::Object(map) => map.get_mut(token),
+            Value::Array(items) => {
+                parse_index(token).and_then(|idx| items.get_mut(idx))
+            }
+            _ => None,
+        };
+        value = child.ok_or_else(|| {
+            anyhow!("Path {} does not exist", pointer_text(&tokens[..=depth]))
+        })?;
+    }
+    Ok(value)
+}
+
+/// Adds `value` at `tokens`: sets an object member, inserts into an array
+/// (`-` appends), or replaces the whole document.
+fn add(doc: &mut Value, tokens: &[String], value: Value) -> Result<()> {
+    let Some((last, parent)) = tokens.split_last() else {
+        *doc = value;
+        return Ok(());
+    };
+    match resolve_mut(doc, parent)? {
+        Value::Object(map) => {
+            map.insert(last.clone(), value);
+        }
+        Value::Array(items) => {
+            let idx = if last == "-" {
+                items.len()
+            } else {
+                parse_index(last).filter(|idx| *idx <= items.len()).ok_or_else(
+                    || {
+                        anyhow!(
+                            "Index {} is out of bounds",
+                            pointer_text(tokens)
+                        )
+                    },
+                )?
+            };
+            items.insert(idx, value);
+        }
+        _ => bail!("Path {} does not exist", pointer_text(parent)),
+    }
+    Ok(())
+}
+
+/// Removes and returns the value at `tokens`.
+fn remove(doc: &mut Value, tokens: &[String]) -> Result<Value> {
+    let Some((last, parent)) = tokens.split_last() else {
+        bail!("Cannot remove the whole document");
+    };
+    let removed = match resolve_mut(doc, parent)? {
+        Value::Object(map) => map.shift_remove(last),
+        Value::Array(items) => parse_index(last)
+            .filter(|idx| *idx < items.len())
+            .map(|idx| items.remove(idx)),
+        _ => None,
+    };
+    removed
+        .ok_or_else(|| anyhow!("Path {} does not exist", pointer_text(tokens)))
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Unit testing.")]
+mod tests {
+    use super::*;
+    use crate::{edit, query::QueryDFA};
+    use serde_json::json;
+
+    fn patched(doc: &Value, patch: &Value) -> Result<Value> {
+        let mut doc = doc.clone();
+        apply_patch(&mut doc, patch)?;
+        Ok(doc)
+    }
+
+    #[test]
+    fn planned_edits_round_trip_through_a_patch() {
+        let doc = json!({"a/b": [1, 2, 3], "c~": {"d": true}});
+        let set = QueryDFA::from_query_str(r#""c~".d"#).unwrap();
+        let delete = QueryDFA::from_query_str(r#""a/b".([0] | [2])"#).unwrap();
+
+        let mut edited = doc.clone();
+        let mut ops = edit::plan_set(
+            &edited,
+            &set,
+            &edit::Template::parse("false").unwrap(),
+        );
+        edit::apply(&mut edited, &ops).unwrap();
+        let deletes = edit::plan_delete(&edited, &delete);
+        edit::apply(&mut edited, &deletes).unwrap();
+        ops.extend(deletes);
+
+        let patch = to_patch(&ops);
+        assert_eq!(
+            patch,
+            json!([
+                {"op": "replace", "path": "/c~0/d", "value": false},
+                {"op": "remove", "path": "/a~1b/2"},
+                {"op": "remove", "path": "/a~1b/0"},
+            ])
+        );
+        assert_eq!(patched(&doc, &patch).unwrap(), edited);
+    }
+
+    #[test]
+    fn apply_patch_supports_every_operation() {
+        let doc = json!({"a": [1, 2], "b": {"c": "x"}});
+        let patch = json!([
+            {"op": "add", "path": "/a/1", "value": 9},
+            {"op": "add", "path": "/a/-", "value": 3},
+            {"op": "copy", "from": "/b/c", "path": "/d"},
+            {"op": "move", "from": "/b", "path": "/e"},
+            {"op": "test", "path": "/e/c", "value": "x"},
+            {"op": "replace", "path": "", "value": {"all": ["new"]}},
+            {"op": "add", "path": "/all/0", "value": "first"},
+        ]);
+        assert_eq!(
+            patched(&doc, &patch).unwrap(),
+            json!({"all": ["first", "new"]})
+        );
+
+        let patch = json!([
+            {"op": "add", "path": "/a/1", "value": 9},
+            {"op": "copy", "from": "/b/c", "path": "/d"},
+            {"op": "move", "from": "/b", "path": "/e"},
+        ]);
+        assert_eq!(
+            patched(&doc, &patch).unwrap(),
+            json!({"a": [1, 9, 2], "d": "x", "e": {"c": "x"}})
+        );
+    }
+
+    #[test]
+    fn failed_patches_leave_the_document_unchanged() {
+        let mut doc = json!({"a": [1]});
+        for (patch, message) in [
+            (json!({}), "A JSON Patch must be an array"),
+            (
+                json!([{"op": "remove", "path": "/a/1"}]),
+                "Patch operation 0 failed",
+            ),
+            (
+                json!([
+                    {"op": "remove", "path": "/a/0"},
+                    {"op": "test", "path": "/a", "value": [1]},
+                ]),
+                "Patch operation 1 failed",
+            ),
+            (json!([{"op": "add", "path": "a"}]), "Patch operation 0 failed"),
+            (
+                json!([{"op": "move", "from": "/a", "path": "/a/0"}]),
+                "Patch operation 0 failed",
+            ),
+        ] {
+            let err = apply_patch(&mut doc, &patch).unwrap_err();
+            assert_eq!(err.to_string(), message);
+            assert_eq!(doc, json!({"a": [1]}));
+        }
+
+        let err = apply_patch(
+            &mut doc,
+            &json!([{"op": "replace", "path": "/a/01", "value": 0}]),
+        )
+        .unwrap_err();
+        assert_eq!(
+            format!("{err:#}"),
+            "Patch operation 0 failed: Path /a/01 does not exist"
+        );
+    }
+}
@@ -0,0 +1,291 @@
+/*!
+# Redaction
+
+Planning the replacement of matched values with a placeholder or a keyed
+hash, keeping the document's structure and types.
+
+Only strings and numbers are redacted: a match that is an object or array
+has the strings and numbers inside it redacted, and booleans and nulls,
+which carry next to no information, are left alone. Strings stay strings
+and numbers stay numbers:
+
+| [`Redactor`]    | String                      | Number                    |
+|-----------------|-----------------------------|---------------------------|
+| `Placeholder`   | the placeholder             | `0`                       |
+| `Hash`          | `sha256:` and 16 hex digits | an integer below 2^48     |
+
+Hashes are HMAC-SHA256 under a secret key, so the same value always
+redacts to the same token (redacted records can still be correlated)
+while, without the key, a token cannot be checked against guesses.
+
+# Examples
+
+```
+use jsongrep::{
+    edit::{
+        self,
+        redact::{self, Redactor},
+    },
+    query::QueryDFA,
+};
+
+let mut doc = serde_json::json!({
+    "user": {"email": "a@example.com", "age": 42, "admin": true}
+});
+let dfa = QueryDFA::from_query_str("user").unwrap();
+
+let ops = redact::plan_redact(&doc, &dfa, &Redactor::default());
+edit::apply(&mut doc, &ops).unwrap();
+assert_eq!(
+    doc,
+    serde_json::json!({
+        "user": {"email": "[REDACTED]", "age": 0, "admin": true}
+    })
+);
+```
+*/
+use serde_json::Value;
+use sha2::{Digest as _, Sha256};
+use std::fmt::Write as _;
+
+use super::{Operation, outermost};
+use crate::query::{PathType, QueryDFA};
+
+/// The default placeholder for redacted strings.
+pub const DEFAULT_PLACEHOLDER: &str = "[REDACTED]";
+
+/// What redacted values are replaced with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Redactor {
+    /// Strings become this text, numbers become `0`.
+    Placeholder(String),
+    /// Strings become `sha256:` followed by the first 16 hex digits of the
+    /// HMAC-SHA256 of the value under this key; numbers become an integer
+    /// taken from the same digest.
+    Hash(Vec<u8>),
+}
+
+impl Default for Redactor {
+    fn default() -> Self {
+        Self::Placeholder(DEFAULT_PLACEHOLDER.to_string())
+    }
+}
+
+impl Redactor {
+    /// The redacted form of `value`, or `None` if `value` is not redacted
+    /// (it is a boolean, null, or a collection).
+    #[must_use]
+    pub fn redact(&self, value: &Value) -> Option<Value> {
+        match (self, value) {
+            (Self::Placeholder(text), Value::String(_)) => {
+                Some(Value::String(text.clone()))
+            }
+            (Self::Placeholder(_), Value::Number(_)) => Some(Value::from(0)),
+            (Self::Hash(key), Value::String(s)) => {
+                let digest = hmac_sha256(key, s.as_bytes());
+                let hex = format!("sha256:{}", hex(&digest[..8]));
+                Some(Value::String(hex))
+            }
+            (Self::Hash(key), Value::Number(n)) => {
+                let digest = hmac_sha256(key, n.to_string().as_bytes());
+                let mut bytes = [0; 8];
+                bytes[2..].copy_from_slice(&digest[..6]);
+                // 48 bits: exact in every JSON implementation, including
+                // those that read numbers as doubles.
+                Some(Value::from(u64::from_be_bytes(bytes)))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Plan redacting every string and number that `dfa` matches in `doc`, or
+/// that is inside a match.
+#[must_use]
+pub fn plan_redact(
+    doc: &Value,
+    dfa: &QueryDFA,
+    redactor: &Redactor,
+) -> Vec<Operation> {
+    plan(doc, outermost(dfa.find_tree(doc)), redactor)
+}
+
+/// Like [`plan_redact`], for `record`, element `index` of a top-level array
+/// read on its own, as with a JSONL stream redacted record by record. Paths
+/// are relative to the record.
+#[must_use]
+pub fn plan_redact_record(
+    record: &Value,
+    index: usize,
+    dfa: &QueryDFA,
+    redactor: &Redactor,
+) -> Vec<Operation> {
+    plan(record, outermost(dfa.find_tree_at_index(index, record)), redactor)
+}
+
+/// A replacement for each redacted value at or below `paths` in `doc`.
+fn plan(
+    doc: &Value,
+    paths: Vec<Vec<PathType>>,
+    redactor: &Redactor,
+) -> Vec<Operation> {
+    let mut ops = Vec::new();
+    let mut stack: Vec<(Vec<PathType>, &Value)> = paths
+        .into_iter()
+        .rev()
+        .filter_map(|path| Some((path.clone(), super::get(doc, &path)?)))
+        .collect();
+    // Depth-first, children pushed in reverse, so operations come out in
+    // document order.
+    while let Some((path, value)) = stack.pop() {
+        match value {
+            Value::Object(map) => {
+                for (key, child) in map.iter().rev() {
+                    let mut child_path = path.clone();
+                    child_path.push(PathType::Field(key.as_str().into()));
+                    stack.push((child_path, child));
+                }
+            }
+            Value::Array(items) => {
+                for (idx, child) in items.iter().enumerate().rev() {
+                    let mut child_path = path.clone();
+                    child_path.push(PathType::Index(idx));
+                    stack.push((child_path, child));
+                }
+            }
+            _ => {
+                if let Some(value) = redactor.redact(value) {
+                    ops.push(Operation::Replace { path, value });
+                }
+            }
+        }
+    }
+    ops
+}
+
+/// `bytes` as lowercase hex digits.
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+/// HMAC-SHA256 (RFC 2104) of `message` under `key`.
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    const BLOCK: usize = 64;
+    let mut block = [0; BLOCK];
+    if key.len() > BLOCK {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let inner = Sha256::new()
+        .chain_update(block.map(|b| b ^ 0x36))
+        .chain_update(message)
+        .finalize();
+    Sha256::new()
+        .chain_update(block.map(|b| b ^ 0x5c))
+        .chain_update(inner)
+        .finalize()
+        .into()
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Unit testing.")]
+mod tests {
+    use super::*;
+    use crate::edit;
+    use serde_json::json;
+
+    fn redacted(doc: &Value, query: &str, redactor: &Redactor) -> Value {
+        let dfa = QueryDFA::from_query_str(query).unwrap();
+        let mut doc = doc.clone();
+        let ops = plan_redact(&doc, &dfa, redactor);
+        edit::apply(&mut doc, &ops).unwrap();
+        doc
+    }
+
+    #[test]
+    fn hmac_matches_rfc_4231() {
+        assert_eq!(
+            hex(&hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        // Keys longer than a block are hashed first.
+        assert_eq!(
+            hex(&hmac_sha256(
+                &[0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First"
+            )),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+    }
+
+    #[test]
+    fn redaction_keeps_structure_and_types() {
+        let doc = json!({
+            "id": 1,
+            "card": {"number": "4111", "cvv": 123, "valid": true},
+            "tags": ["a", null],
+        });
+        assert_eq!(
+            redacted(&doc, "card | tags", &Redactor::default()),
+            json!({
+                "id": 1,
+                "card": {"number": "[REDACTED]", "cvv": 0, "valid": true},
+                "tags": ["[REDACTED]", null],
+            })
+        );
+        assert_eq!(
+            redacted(&doc, "id", &Redactor::Placeholder("***".into())),
+            json!({
+                "id": 0,
+                "card": {"number": "4111", "cvv": 123, "valid": true},
+                "tags": ["a", null],
+            })
+        );
+    }
+
+    #[test]
+    fn hashes_are_deterministic_per_key() {
+        let doc = json!([
+            {"email": "a@example.com", "n": 5},
+            {"email": "a@example.com", "n": 5},
+            {"email": "b@example.com", "n": 6},
+        ]);
+        let key = Redactor::Hash(b"secret".to_vec());
+        let out = redacted(&doc, "[*].(email | n)", &key);
+
+        assert_eq!(out[0], out[1]);
+        assert_ne!(out[0]["email"], out[2]["email"]);
+        assert_ne!(out[0]["n"], out[2]["n"]);
+        let token = out[0]["email"].as_str().unwrap();
+        assert!(token.starts_with("sha256:") && token.len() == 23, "{token}");
+        assert!(out[0]["n"].as_u64().unwrap() < 1 << 48);
+
+        let other =
+            redacted(&doc, "[*].email", &Redactor::Hash(b"k2".to_vec()));
+        assert_ne!(other[0]["email"], out[0]["email"]);
+    }
+
+    #[test]
+    fn records_are_redacted_at_their_index() {
+        let dfa = QueryDFA::from_query_str("[1].token | [*].password").unwrap();
+        let record = json!({"token": "t", "password": "p"});
+        let paths = |index| {
+            plan_redact_record(&record, index, &dfa, &Redactor::default())
+                .iter()
+                .map(|op| op.path().to_vec())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(paths(0), [vec![PathType::Field("password".into())]]);
+        assert_eq!(
+            paths(1),
+            [
+                vec![PathType::Field("token".into())],
+                vec![PathType::Field("password".into())],
+            ]
+        );
+    }
+}
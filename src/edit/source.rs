@@ -0,0 +1,1176 @@
+/*!
+# Format-Preserving Edits
+
+Applying planned [`Operation`]s to the source text of a TOML or YAML input
+rather than to its parsed [`Value`], so that comments, key order, quoting,
+and layout stay byte for byte the same everywhere the operations do not
+touch.
+
+TOML is edited through a [`toml_edit`] document. YAML is edited as text:
+block mappings and sequences are found by their indentation, and only the
+lines of an edited entry are rewritten. A string replacing a scalar keeps
+its quoting style (double, single, or plain) when it can be written that
+way. An edit inside a flow collection (`[...]` or `{...}`) rewrites that
+collection.
+
+As a safeguard, the edited text is parsed again and must equal the edited
+document, so an input this cannot edit faithfully (YAML anchors, several
+YAML documents, ...) is an error rather than a silently wrong file.
+
+# Examples
+
+```
+# #[cfg(feature = "yaml")] {
+use jsongrep::{
+    edit::{self, Template, source},
+    query::QueryDFA,
+};
+
+let text = "# Web server\nport: 80 # public\nhost: example.com\n";
+let doc = serde_json::json!({"port": 80, "host": "example.com"});
+let dfa = QueryDFA::from_query_str("port").unwrap();
+let ops = edit::plan_set(&doc, &dfa, &Template::parse("8080").unwrap());
+
+let edited = source::apply("yaml", text, &ops).unwrap();
+assert_eq!(edited, "# Web server\nport: 8080 # public\nhost: example.com\n");
+# }
+```
+*/
+use anyhow::{Context as _, Result, bail};
+use serde_json::Value;
+
+use super::Operation;
+use crate::format::FormatRegistry;
+
+/// Apply `ops`, planned against the document `text` parses to, to `text`,
+/// the source of a `format` input (a name as given to `--format`).
+///
+/// `toml` and `yaml` are supported; the parts of `text` the operations do
+/// not edit are returned unchanged.
+///
+/// # Errors
+///
+/// Returns an error if `format` is not supported, `text` does not parse,
+/// an operation does not apply, or the edit cannot be made without
+/// reformatting the rest of the input.
+pub fn apply(format: &str, text: &str, ops: &[Operation]) -> Result<String> {
+    if !matches!(format, "toml" | "yaml") {
+        bail!(
+            "Cannot preserve the formatting of {format} input: only TOML \
+             and YAML are supported"
+        );
+    }
+    let registry = FormatRegistry::new();
+    let parser = registry
+        .get(format)
+        .with_context(|| format!("Unknown format {format:?}"))?;
+    let parse = |text: &str| parser.parse(text.as_bytes());
+
+    let original = parse(text)?;
+    let mut expected = original.clone();
+    super::apply(&mut expected, ops)?;
+
+    let edited = edit_source(format, text, original, ops)?;
+
+    let reparsed = parse(&edited).with_context(|| {
+        format!("Editing produced invalid {format}; the input is unchanged")
+    })?;
+    if reparsed != expected {
+        bail!(
+            "Cannot edit this {format} input while preserving its \
+             formatting; the input is unchanged"
+        );
+    }
+    Ok(edited)
+}
+
+/// Applies `ops` to `text` with the editor for `format`.
+fn edit_source(
+    format: &str,
+    text: &str,
+    original: Value,
+    ops: &[Operation],
+) -> Result<String> {
+    match format {
+        #[cfg(feature = "toml")]
+        "toml" => apply_toml(text, ops),
+        #[cfg(feature = "yaml")]
+        "yaml" => yaml::apply(text, original, ops),
+        _ => {
+            let _ = (text, original, ops);
+            bail!("Support for {format} is not enabled in this build")
+        }
+    }
+}
+
+/// Applies `ops` to a [`toml_edit`] document parsed from `text`.
+#[cfg(feature = "toml")]
+fn apply_toml(text: &str, ops: &[Operation]) -> Result<String> {
+    use crate::query::PathType;
+    use toml_edit::{DocumentMut, Item};
+
+    /// The item at `path`.
+    fn item_at<'a>(
+        item: &'a mut Item,
+        path: &[PathType],
+    ) -> Result<&'a mut Item> {
+        path.iter().enumerate().try_fold(item, |item, (depth, part)| {
+            match part {
+                PathType::Field(name) => item
+                    .as_table_like_mut()
+                    .and_then(|table| table.get_mut(name)),
+                PathType::Index(idx) => item.get_mut(*idx),
+            }
+            .ok_or_else(|| missing(&path[..=depth]))
+        })
+    }
+
+    let mut doc: DocumentMut = text.parse().context("Failed to parse TOML")?;
+    for op in ops {
+        match op {
+            Operation::Replace { path, value } => {
+                if path.is_empty() && !value.is_object() {
+                    bail!("A TOML document must be a table");
+                }
+                let item = item_at(doc.as_item_mut(), path)?;
+                *item = toml_item(item, value)?;
+            }
+            Operation::Remove { path } => {
+                let Some((last, parent)) = path.split_last() else {
+                    bail!("Cannot remove the whole document");
+                };
+                let parent = item_at(doc.as_item_mut(), parent)?;
+                let removed = match last {
+                    PathType::Field(name) => parent
+                        .as_table_like_mut()
+                        .and_then(|table| table.remove(name))
+                        .is_some(),
+                    PathType::Index(idx) => remove_toml_element(parent, *idx),
+                };
+                if !removed {
+                    return Err(missing(path));
+                }
+            }
+        }
+    }
+    Ok(doc.to_string())
+}
+
+/// Removes element `idx` of an array or array of tables, returning whether
+/// there was one.
+#[cfg(feature = "toml")]
+fn remove_toml_element(item: &mut toml_edit::Item, idx: usize) -> bool {
+    if let Some(tables) = item.as_array_of_tables_mut()
+        && idx < tables.len()
+    {
+        tables.remove(idx);
+        true
+    } else if let Some(array) = item.as_array_mut()
+        && idx < array.len()
+    {
+        let removed = array.remove(idx);
+        // The new first element takes over the spacing after `[`.
+        if idx == 0
+            && let Some(first) = array.get_mut(0)
+        {
+            let prefix = removed.decor().prefix().cloned();
+            first.decor_mut().set_prefix(prefix.unwrap_or_default());
+        }
+        true
+    } else {
+        false
+    }
+}
+
+/// `value` as an item replacing `old`, in the same style: tables stay
+/// tables and arrays of tables stay arrays of tables, and a value keeps the
+/// whitespace and comments around it.
+#[cfg(feature = "toml")]
+fn toml_item(old: &toml_edit::Item, value: &Value) -> Result<toml_edit::Item> {
+    use toml_edit::{ArrayOfTables, Item};
+
+    let table = |value: &Value| -> Result<toml_edit::Table> {
+        match toml_value(value)? {
+            toml_edit::Value::InlineTable(table) => Ok(table.into_table()),
+            _ => bail!("Expected a table"),
+        }
+    };
+    Ok(match (old, value) {
+        (Item::Table(old), Value::Object(_)) => {
+            let mut new = table(value)?;
+            new.decor_mut().clone_from(old.decor());
+            if let Some(position) = old.position() {
+                new.set_position(position);
+            }
+            Item::Table(new)
+        }
+        (Item::ArrayOfTables(_), Value::Array(items))
+            if items.iter().all(Value::is_object) =>
+        {
+            let mut tables = ArrayOfTables::new();
+            for item in items {
+                tables.push(table(item)?);
+            }
+            Item::ArrayOfTables(tables)
+        }
+        (Item::Value(old), _) => {
+            let mut new = toml_value(value)?;
+            new.decor_mut().clone_from(old.decor());
+            Item::Value(new)
+        }
+        _ => Item::Value(toml_value(value)?),
+    })
+}
+
+/// `value` as a TOML value, with collections inline.
+#[cfg(feature = "toml")]
+fn toml_value(value: &Value) -> Result<toml_edit::Value> {
+    Ok(match value {
+        Value::Null => bail!("TOML has no null value"),
+        Value::Bool(b) => (*b).into(),
+        Value::Number(n) => {
+            if let Some(int) = n.as_i64() {
+                int.into()
+            } else if n.is_f64()
+                && let Some(float) = n.as_f64()
+            {
+                float.into()
+            } else {
+                bail!("{n} is out of range for a TOML integer")
+            }
+        }
+        Value::String(s) => s.as_str().into(),
+        Value::Array(items) => toml_edit::Value::Array(
+            items.iter().map(toml_value).collect::<Result<_>>()?,
+        ),
+        Value::Object(map) => {
+            let mut table = toml_edit::InlineTable::new();
+            for (key, value) in map {
+                table.insert(key, toml_value(value)?);
+            }
+            toml_edit::Value::InlineTable(table)
+        }
+    })
+}
+
+/// The error for an operation on a path that does not exist.
+#[cfg_attr(
+    not(any(feature = "toml", feature = "yaml")),
+    expect(dead_code, reason = "Only used by the format editors.")
+)]
+fn missing(path: &[crate::query::PathType]) -> anyhow::Error {
+    anyhow::anyhow!(
+        "Path {} does not exist",
+        crate::utils::format_path(path, crate::utils::PathFormat::Jg)
+    )
+}
+
+#[cfg(feature = "yaml")]
+mod yaml {
+    //! YAML, edited as text.
+    //!
+    //! Block collections are found line by line: the entries of a mapping or
+    //! sequence are the lines at its indentation, and each entry runs until
+    //! the next one. Each operation rewrites one byte range of the text,
+    //! which is then scanned afresh for the next operation.
+    use anyhow::{Context as _, Result, bail};
+    use serde_json::Value;
+    use std::ops::Range;
+
+    use super::missing;
+    use crate::{
+        edit::{self, Operation},
+        query::PathType,
+        utils::{PathFormat, format_path},
+    };
+
+    /// Applies `ops` to `text`, which parses to `doc`.
+    pub(super) fn apply(
+        text: &str,
+        mut doc: Value,
+        ops: &[Operation],
+    ) -> Result<String> {
+        let mut text = text.to_string();
+        for op in ops {
+            // Rewritten values are taken from the document as edited so far.
+            edit::apply(&mut doc, std::slice::from_ref(op))?;
+            let (range, replacement) = {
+                let source = Source::new(&text)?;
+                match op {
+                    Operation::Replace { path, .. } => {
+                        source.replace(path, &doc)?
+                    }
+                    Operation::Remove { path } => source.remove(path, &doc)?,
+                }
+            };
+            text.replace_range(range, &replacement);
+        }
+        Ok(text)
+    }
+
+    /// A rewrite of part of the text.
+    type Rewrite = (Range<usize>, String);
+
+    /// A line of the text.
+    struct Line {
+        /// Offset of the first byte.
+        start: usize,
+        /// Offset of the line break (or the end of the text).
+        end: usize,
+        /// Offset of the next line.
+        next: usize,
+        /// Number of leading spaces.
+        indent: usize,
+        /// Whether the line is empty, a comment, or a document marker.
+        blank: bool,
+    }
+
+    /// A value in the text: from `start`, on `line`, up to `end_line`.
+    #[derive(Clone, Copy)]
+    struct Node {
+        start: usize,
+        line: usize,
+        end_line: usize,
+    }
+
+    /// The syntax of a [`Node`].
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    enum Kind {
+        Mapping,
+        Sequence,
+        Flow,
+        Scalar,
+    }
+
+    /// A mapping entry (`key: value`) or sequence entry (`- value`).
+    struct Entry {
+        /// The key of a mapping entry.
+        key: Option<String>,
+        /// Offset of the key or `-`.
+        start: usize,
+        line: usize,
+        end_line: usize,
+        /// Offset just past the `:` or `-`.
+        marker_end: usize,
+        /// `None` for an empty (null) value.
+        value: Option<Node>,
+    }
+
+    /// Where the value at a path is.
+    enum Place {
+        /// The whole document, `None` if it is empty.
+        Root(Option<Node>),
+        /// An entry of a block collection.
+        Entry {
+            entry: Entry,
+            /// Number of entries in the collection.
+            siblings: usize,
+            /// Offset of the following entry.
+            next_start: Option<usize>,
+        },
+        /// Inside the flow collection `node`, at `depth` in the path.
+        InFlow { node: Node, depth: usize },
+    }
+
+    /// The text, split into lines.
+    struct Source<'a> {
+        text: &'a str,
+        lines: Vec<Line>,
+    }
+
+    impl<'a> Source<'a> {
+        /// Splits `text` into lines.
+        ///
+        /// # Errors
+        ///
+        /// Returns an error for a stream of several documents.
+        fn new(text: &'a str) -> Result<Self> {
+            let mut lines = Vec::new();
+            let mut start = 0;
+            let mut content = false;
+            while start < text.len() {
+                let next = text[start..]
+                    .find('\n')
+                    .map_or(text.len(), |i| start + i + 1);
+                let end = start
+                    + text[start..next].trim_end_matches(['\n', '\r']).len();
+                let body = text[start..end].trim_start_matches(' ');
+                let indent = end - start - body.len();
+                let mut blank = body.is_empty() || body.starts_with('#');
+                if indent == 0 && is_marker(body) {
+                    if content && body.starts_with("---") {
+                        bail!(
+                            "Cannot preserve the formatting of YAML with \
+                             several documents"
+                        );
+                    }
+                    if !body[3..].trim_start().is_empty()
+                        && !body[3..].trim_start().starts_with('#')
+                    {
+                        bail!(
+                            "Cannot preserve the formatting of YAML with \
+                             content on a document marker line"
+                        );
+                    }
+                    blank = true;
+                } else if indent == 0 && !content && body.starts_with('%') {
+                    blank = true;
+                }
+                content |= !blank;
+                lines.push(Line { start, end, next, indent, blank });
+                start = next;
+            }
+            Ok(Self { text, lines })
+        }
+
+        /// The whole document.
+        fn root(&self) -> Option<Node> {
+            let line = self.lines.iter().position(|line| !line.blank)?;
+            Some(Node {
+                start: self.lines[line].start + self.lines[line].indent,
+                line,
+                end_line: self.lines.len(),
+            })
+        }
+
+        /// The offset just past the content on `line` from `from`, before
+        /// any comment and trailing whitespace.
+        fn content_end(&self, line: usize, from: usize) -> usize {
+            let bytes = &self.text.as_bytes()[from..self.lines[line].end];
+            let mut quote = None;
+            let mut end = 0;
+            let mut i = 0;
+            while i < bytes.len() {
+                let byte = bytes[i];
+                let prev = i.checked_sub(1).map(|j| bytes[j]);
+                match quote {
+                    Some(b'"') if byte == b'\\' => i += 1,
+                    Some(b'\'')
+                        if byte == b'\''
+                            && bytes.get(i + 1) == Some(&b'\'') =>
+                    {
+                        i += 1;
+                    }
+                    Some(q) if byte == q => quote = None,
+                    None if byte == b'#'
+                        && prev.is_none_or(|b| b == b' ' || b == b'\t') =>
+                    {
+                        break;
+                    }
+                    None if (byte == b'"' || byte == b'\'')
+                        && prev.is_none_or(|b| {
+                            matches!(
+                                b,
+                                b' ' | b'\t' | b'[' | b'{' | b',' | b':'
+                            )
+                        }) =>
+                    {
+                        quote = Some(byte);
+                    }
+                    Some(_) | None => {}
+                }
+                i += 1;
+                if !matches!(byte, b' ' | b'\t') {
+                    end = i.min(bytes.len());
+                }
+            }
+            from + end
+        }
+
+        /// The comment at the end of `line`, if any.
+        fn comment(&self, line: usize) -> Option<&'a str> {
+            let info = &self.lines[line];
+            let end = self.content_end(line, info.start + info.indent);
+            let comment = self.text[end..info.end].trim();
+            comment.starts_with('#').then_some(comment)
+        }
+
+        /// The last non-blank line from `line` up to `end_line`.
+        fn last_line(&self, line: usize, end_line: usize) -> usize {
+            (line..end_line)
+                .rev()
+                .find(|&l| !self.lines[l].blank)
+                .unwrap_or(line)
+        }
+
+        /// The offset just past the content of `node`.
+        fn value_end(&self, node: Node) -> usize {
+            let last = self.last_line(node.line, node.end_line);
+            if last == node.line {
+                self.content_end(node.line, node.start)
+            } else {
+                let line = &self.lines[last];
+                self.content_end(last, line.start + line.indent)
+            }
+        }
+
+        /// The column of `offset` on `line`.
+        fn column(&self, line: usize, offset: usize) -> usize {
+            offset - self.lines[line].start
+        }
+
+        fn kind(&self, node: Node) -> Kind {
+            let content =
+                &self.text[node.start..self.content_end(node.line, node.start)];
+            if is_dash(content) {
+                Kind::Sequence
+            } else if content.starts_with(['[', '{']) {
+                Kind::Flow
+            } else if self.key(node.line, node.start).is_some() {
+                Kind::Mapping
+            } else {
+                Kind::Scalar
+            }
+        }
+
+        /// The key of a `key: value` entry at `from` on `line`, and the
+        /// offset just past its `:`.
+        fn key(&self, line: usize, from: usize) -> Option<(String, usize)> {
+            let content = &self.text[from..self.content_end(line, from)];
+            let (key, rest) = match content.as_bytes().first()? {
+                b'"' => {
+                    let close = closing_quote(content, b'"')?;
+                    let key = serde_json::from_str(&content[..=close]).ok()?;
+                    (key, &content[close + 1..])
+                }
+                b'\'' => {
+                    let close = closing_quote(content, b'\'')?;
+                    (
+                        content[1..close].replace("''", "'"),
+                        &content[close + 1..],
+                    )
+                }
+                b'?' | b'&' | b'*' | b'!' | b'[' | b'{' | b'|' | b'>' => {
+                    return None;
+                }
+                _ => {
+                    let colon = content.match_indices(':').find(|(i, _)| {
+                        content[i + 1..].is_empty()
+                            || content[i + 1..].starts_with([' ', '\t'])
+                    })?;
+                    (
+                        content[..colon.0].trim_end().to_string(),
+                        &content[colon.0..],
+                    )
+                }
+            };
+            let after =
+                rest.trim_start_matches([' ', '\t']).strip_prefix(':')?;
+            if !(after.is_empty() || after.starts_with([' ', '\t'])) {
+                return None;
+            }
+            Some((key, from + content.len() - after.len()))
+        }
+
+        /// The entries of the block collection `node`.
+        fn entries(&self, node: Node, kind: Kind) -> Result<Vec<Entry>> {
+            let column = self.column(node.line, node.start);
+            let mut starts = vec![(node.line, node.start)];
+            for l in node.line + 1..node.end_line {
+                let line = &self.lines[l];
+                let body = &self.text[line.start + line.indent..line.end];
+                // In a mapping, a `-` line at the same indentation is part
+                // of the previous entry's value.
+                if !line.blank
+                    && line.indent == column
+                    && is_dash(body) == (kind == Kind::Sequence)
+                {
+                    starts.push((l, line.start + line.indent));
+                }
+            }
+
+            let mut entries = Vec::with_capacity(starts.len());
+            for (i, &(line, start)) in starts.iter().enumerate() {
+                let end_line = starts.get(i + 1).map_or(node.end_line, |s| s.0);
+                let (key, marker_end) = if kind == Kind::Sequence {
+                    (None, start + 1)
+                } else {
+                    let (key, marker_end) =
+                        self.key(line, start).with_context(|| {
+                            format!(
+                                "Cannot parse line {} of the YAML",
+                                line + 1
+                            )
+                        })?;
+                    (Some(key), marker_end)
+                };
+                let value_start = marker_end
+                    + self.text[marker_end..self.lines[line].end].len()
+                    - self.text[marker_end..self.lines[line].end]
+                        .trim_start_matches([' ', '\t'])
+                        .len();
+                let value = if self.content_end(line, value_start) > value_start
+                {
+                    Some(Node { start: value_start, line, end_line })
+                } else {
+                    (line + 1..end_line).find(|&l| !self.lines[l].blank).map(
+                        |l| Node {
+                            start: self.lines[l].start + self.lines[l].indent,
+                            line: l,
+                            end_line,
+                        },
+                    )
+                };
+                entries.push(Entry {
+                    key,
+                    start,
+                    line,
+                    end_line,
+                    marker_end,
+                    value,
+                });
+            }
+            Ok(entries)
+        }
+
+        /// Finds the value at `path`.
+        fn find(&self, path: &[PathType]) -> Result<Place> {
+            let mut node = self.root();
+            let mut place = Place::Root(node);
+            for (depth, part) in path.iter().enumerate() {
+                let current = node.ok_or_else(|| missing(&path[..=depth]))?;
+                let kind = self.kind(current);
+                let mut entries = match kind {
+                    Kind::Flow => {
+                        return Ok(Place::InFlow { node: current, depth });
+                    }
+                    Kind::Scalar => bail!(
+                        "Cannot edit inside {} while preserving the YAML \
+                         formatting",
+                        format_path(&path[..depth], PathFormat::Jg)
+                    ),
+                    Kind::Mapping | Kind::Sequence => {
+                        self.entries(current, kind)?
+                    }
+                };
+                let idx = match (kind, part) {
+                    (Kind::Mapping, PathType::Field(name)) => entries
+                        .iter()
+                        .position(|entry| entry.key.as_deref() == Some(name)),
+                    (Kind::Sequence, PathType::Index(idx)) => {
+                        (*idx < entries.len()).then_some(*idx)
+                    }
+                    _ => None,
+                }
+                .ok_or_else(|| missing(&path[..=depth]))?;
+                let siblings = entries.len();
+                let next_start = entries.get(idx + 1).map(|entry| entry.start);
+                let entry = entries.swap_remove(idx);
+                node = entry.value;
+                place = Place::Entry { entry, siblings, next_start };
+            }
+            Ok(place)
+        }
+
+        /// Rewrites the value at `path` to its value in `doc`.
+        fn replace(&self, path: &[PathType], doc: &Value) -> Result<Rewrite> {
+            match self.find(path)? {
+                Place::InFlow { node, depth } => {
+                    self.rewrite_flow(node, &path[..depth], doc)
+                }
+                Place::Root(node) => {
+                    let value = edit::get(doc, path).context("No document")?;
+                    Ok(self.rewrite_root(node, value)?)
+                }
+                Place::Entry { entry, .. } => {
+                    let value =
+                        edit::get(doc, path).ok_or_else(|| missing(path))?;
+                    self.rewrite_entry(&entry, value)
+                }
+            }
+        }
+
+        /// Removes the entry at `path`, which `doc` no longer has.
+        fn remove(&self, path: &[PathType], doc: &Value) -> Result<Rewrite> {
+            let Some((_, parent)) = path.split_last() else {
+                bail!("Cannot remove the whole document");
+            };
+            match self.find(path)? {
+                Place::InFlow { node, depth } => {
+                    self.rewrite_flow(node, &path[..depth], doc)
+                }
+                Place::Root(_) => bail!("Cannot remove the whole document"),
+                // The collection is now empty: write it as `{}` or `[]`.
+                Place::Entry { siblings: 1, .. } => self.replace(parent, doc),
+                Place::Entry { entry, next_start, .. } => {
+                    let line = &self.lines[entry.line];
+                    if entry.start == line.start + line.indent {
+                        let last = self.last_line(entry.line, entry.end_line);
+                        Ok((line.start..self.lines[last].next, String::new()))
+                    } else {
+                        // The first entry after a `- `: the next entry moves
+                        // up into its place.
+                        let next = next_start.context("No following entry")?;
+                        Ok((entry.start..next, String::new()))
+                    }
+                }
+            }
+        }
+
+        /// Rewrites the flow collection `node`, at `path`, to its value in
+        /// `doc`.
+        fn rewrite_flow(
+            &self,
+            node: Node,
+            path: &[PathType],
+            doc: &Value,
+        ) -> Result<Rewrite> {
+            let value = edit::get(doc, path).ok_or_else(|| missing(path))?;
+            Ok((node.start..self.value_end(node), flow(value)?))
+        }
+
+        /// Rewrites the whole document to `value`.
+        fn rewrite_root(
+            &self,
+            node: Option<Node>,
+            value: &Value,
+        ) -> Result<Rewrite> {
+            let Some(node) = node else {
+                let end = self.text.len();
+                return Ok((end..end, format!("{}\n", block(value)?)));
+            };
+            let old = &self.text[node.start..self.value_end(node)];
+            let text = match restyled(value, old)? {
+                Some(text) => text,
+                None if self.kind(node) == Kind::Flow => flow(value)?,
+                None => block(value)?,
+            };
+            Ok((node.start..self.value_end(node), text))
+        }
+
+        /// Rewrites the value of `entry` to `value`.
+        fn rewrite_entry(
+            &self,
+            entry: &Entry,
+            value: &Value,
+        ) -> Result<Rewrite> {
+            let single_line = entry.value.filter(|old| {
+                old.line == entry.line
+                    && self.last_line(old.line, old.end_line) == old.line
+            });
+            if let Some(old) = single_line {
+                let token = &self.text[old.start..self.value_end(old)];
+                let text = match restyled(value, token)? {
+                    Some(text) => Some(text),
+                    None if self.kind(old) == Kind::Flow => Some(flow(value)?),
+                    None => None,
+                };
+                if let Some(text) = text {
+                    return Ok((old.start..self.value_end(old), text));
+                }
+            }
+
+            // Rewrite everything after the `:` or `-`, keeping a comment on
+            // the entry's line unless it belongs to a value spanning lines.
+            let comment = if single_line.is_some()
+                || entry.value.is_none_or(|old| old.line != entry.line)
+            {
+                self.comment(entry.line)
+            } else {
+                None
+            };
+            let last = self.last_line(entry.line, entry.end_line);
+            let range = entry.marker_end..self.lines[last].end;
+            let column = self.column(entry.line, entry.start);
+
+            let mut text = String::new();
+            if let Some(scalar) = inline(value)? {
+                text = format!(" {scalar}");
+                if let Some(comment) = comment {
+                    text = format!("{text} {comment}");
+                }
+            } else if entry.key.is_none() && comment.is_none() {
+                // A compact `- key: value` entry.
+                text.push(' ');
+                text.push_str(&indented(&block(value)?, column + 2, false));
+            } else {
+                let indent = match entry.value {
+                    Some(old) if old.line != entry.line => {
+                        self.column(old.line, old.start)
+                    }
+                    _ => column + 2,
+                };
+                if let Some(comment) = comment {
+                    text = format!(" {comment}");
+                }
+                text.push('\n');
+                text.push_str(&indented(&block(value)?, indent, true));
+            }
+            Ok((range, text))
+        }
+    }
+
+    /// Whether `body` is a document marker (`---` or `...`).
+    fn is_marker(body: &str) -> bool {
+        (body.starts_with("---") || body.starts_with("..."))
+            && (body.len() == 3 || body[3..].starts_with([' ', '\t']))
+    }
+
+    /// Whether `body` starts a sequence entry.
+    fn is_dash(body: &str) -> bool {
+        body == "-" || body.starts_with("- ") || body.starts_with("-\t")
+    }
+
+    /// The offset of the quote closing the string `text` starts with.
+    fn closing_quote(text: &str, quote: u8) -> Option<usize> {
+        let bytes = text.as_bytes();
+        let mut i = 1;
+        while i < bytes.len() {
+            match bytes[i] {
+                b'\\' if quote == b'"' => i += 1,
+                b'\'' if quote == b'\'' && bytes.get(i + 1) == Some(&b'\'') => {
+                    i += 1;
+                }
+                byte if byte == quote => return Some(i),
+                _ => {}
+            }
+            i += 1;
+        }
+        None
+    }
+
+    /// `value` as a YAML value on one line, unless it is a non-empty
+    /// collection.
+    fn inline(value: &Value) -> Result<Option<String>> {
+        Ok(match value {
+            Value::Array(items) if items.is_empty() => Some("[]".into()),
+            Value::Object(map) if map.is_empty() => Some("{}".into()),
+            Value::Array(_) | Value::Object(_) => None,
+            Value::String(s) => {
+                let yaml = serde_yaml::to_string(s)?;
+                let yaml = yaml.trim_end_matches('\n');
+                // Multi-line strings become block scalars; quote them.
+                Some(if yaml.contains('\n') {
+                    serde_json::to_string(s)?
+                } else {
+                    yaml.to_string()
+                })
+            }
+            _ => Some(value.to_string()),
+        })
+    }
+
+    /// `value` as [`inline`] writes it, except that a string is quoted like
+    /// `old`, the scalar it replaces, when it can be written that way.
+    fn restyled(value: &Value, old: &str) -> Result<Option<String>> {
+        if let Value::String(s) = value {
+            let text = match old.as_bytes().first() {
+                Some(b'"') => serde_json::to_string(s)?,
+                Some(b'\'') => format!("'{}'", s.replace('\'', "''")),
+                Some(b'!' | b'&' | b'*' | b'|' | b'>' | b'[' | b'{') | None => {
+                    return inline(value);
+                }
+                Some(_) => s.clone(),
+            };
+            let parsed = serde_yaml::from_str::<Value>(&text).ok();
+            if !text.contains('\n') && parsed.as_ref() == Some(value) {
+                return Ok(Some(text));
+            }
+        }
+        inline(value)
+    }
+
+    /// `value` in flow style, e.g. `{name: a, ports: [80, 443]}`.
+    fn flow(value: &Value) -> Result<String> {
+        /// A string, quoted if it has flow indicators.
+        fn scalar(s: &str) -> Result<String> {
+            let plain =
+                inline(&Value::String(s.to_string()))?.unwrap_or_default();
+            Ok(if plain.contains([',', '[', ']', '{', '}']) {
+                serde_json::to_string(s)?
+            } else {
+                plain
+            })
+        }
+
+        Ok(match value {
+            Value::Array(items) => format!(
+                "[{}]",
+                items.iter().map(flow).collect::<Result<Vec<_>>>()?.join(", ")
+            ),
+            Value::Object(map) => format!(
+                "{{{}}}",
+                map.iter()
+                    .map(|(key, value)| {
+                        Ok(format!("{}: {}", scalar(key)?, flow(value)?))
+                    })
+                    .collect::<Result<Vec<_>>>()?
+                    .join(", ")
+            ),
+            Value::String(s) => scalar(s)?,
+            _ => value.to_string(),
+        })
+    }
+
+    /// `value` in block style, without a trailing line break.
+    fn block(value: &Value) -> Result<String> {
+        Ok(serde_yaml::to_string(value)?.trim_end_matches('\n').to_string())
+    }
+
+    /// `text` with its lines indented by `indent` spaces, except the first
+    /// unless `first`.
+    fn indented(text: &str, indent: usize, first: bool) -> String {
+        let pad = " ".repeat(indent);
+        text.lines()
+            .enumerate()
+            .map(|(i, line)| {
+                if line.is_empty() || (i == 0 && !first) {
+                    line.to_string()
+                } else {
+                    format!("{pad}{line}")
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Unit testing.")]
+mod tests {
+    use super::*;
+    use crate::{
+        edit::{self, Template},
+        query::QueryDFA,
+    };
+
+    /// Sets `query` to `value` and then deletes `delete` in the `format`
+    /// source `text`.
+    fn edited(
+        format: &str,
+        text: &str,
+        set: &[(&str, &str)],
+        delete: &[&str],
+    ) -> Result<String> {
+        let parser = FormatRegistry::new();
+        let mut doc = parser.get(format).unwrap().parse(text.as_bytes())?;
+        let mut ops = Vec::new();
+        for (query, value) in set {
+            let dfa = QueryDFA::from_query_str(query).unwrap();
+            let planned =
+                edit::plan_set(&doc, &dfa, &Template::parse(value).unwrap());
+            edit::apply(&mut doc, &planned)?;
+            ops.extend(planned);
+        }
+        for query in delete {
+            let dfa = QueryDFA::from_query_str(query).unwrap();
+            let planned = edit::plan_delete(&doc, &dfa);
+            edit::apply(&mut doc, &planned)?;
+            ops.extend(planned);
+        }
+        apply(format, text, &ops)
+    }
+
+    #[cfg(feature = "toml")]
+    const CARGO_TOML: &str = r#"# The package.
+[package]
+name = "demo"   # keep this name
+version = "0.1.0"
+authors = [ "a", "b" ]
+
+[dependencies]
+serde = { version = "1", features = ["derive"] }  # serialization
+anyhow = "1"
+
+[[bin]]
+name = "one"
+
+[[bin]]
+name = "two"
+"#;
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml_edits_keep_comments_and_layout() {
+        assert_eq!(
+            edited(
+                "toml",
+                CARGO_TOML,
+                &[
+                    ("package.version", r#""0.2.0""#),
+                    ("dependencies.serde.version", r#""1.0.200""#),
+                ],
+                &["dependencies.anyhow", "bin.[0]", "package.authors.[0]"],
+            )
+            .unwrap(),
+            r#"# The package.
+[package]
+name = "demo"   # keep this name
+version = "0.2.0"
+authors = [ "b" ]
+
+[dependencies]
+serde = { version = "1.0.200", features = ["derive"] }  # serialization
+
+[[bin]]
+name = "two"
+"#
+        );
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml_tables_stay_tables() {
+        assert_eq!(
+            edited(
+                "toml",
+                CARGO_TOML,
+                &[("dependencies", r#"{"serde": "1"}"#)],
+                &[],
+            )
+            .unwrap(),
+            CARGO_TOML.replace(
+                "serde = { version = \"1\", features = [\"derive\"] }  # \
+                 serialization\nanyhow = \"1\"\n",
+                "serde = \"1\"\n"
+            )
+        );
+        let err = edited("toml", CARGO_TOML, &[("package.name", "null")], &[])
+            .unwrap_err();
+        assert_eq!(err.to_string(), "TOML has no null value");
+    }
+
+    #[cfg(feature = "yaml")]
+    const VALUES_YAML: &str = "\
+# Default values.
+replicaCount: 1   # scale me
+
+image:
+  repository: nginx
+  # The tag defaults to the chart's appVersion.
+  tag: \"1.25\"
+
+ports: [80, 443]
+
+env:
+- name: A
+  value: \"1\"
+- name: B   # second
+  value: \"2\"
+
+resources:
+  limits:
+    cpu: 100m
+";
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn yaml_edits_keep_comments_and_layout() {
+        assert_eq!(
+            edited(
+                "yaml",
+                VALUES_YAML,
+                &[
+                    ("replicaCount", "3"),
+                    ("image.tag", r#""{{value}}-alpine""#),
+                    ("ports.[0]", "8080"),
+                    ("env.[1].value", r#""two""#),
+                ],
+                &["env.[0].name", "resources.limits"],
+            )
+            .unwrap(),
+            "\
+# Default values.
+replicaCount: 3   # scale me
+
+image:
+  repository: nginx
+  # The tag defaults to the chart's appVersion.
+  tag: \"1.25-alpine\"
+
+ports: [8080, 443]
+
+env:
+- value: \"1\"
+- name: B   # second
+  value: \"two\"
+
+resources: {}
+"
+        );
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn yaml_strings_keep_their_quoting() {
+        let text = "a: \"1.25\"\nb: 'x'\nc: x\nd: 1\n";
+        for (set, expected) in [
+            (("a", r#""1.27""#), "a: \"1.27\"\n"),
+            (("a", r#""x\ty""#), "a: \"x\\ty\"\n"),
+            (("b", r#""it's""#), "b: 'it''s'\n"),
+            // Cannot be single-quoted: a line break would be folded.
+            (("b", r#""x\ny""#), "b: \"x\\ny\"\n"),
+            (("c", r#""y z""#), "c: y z\n"),
+            // Cannot be plain: these would read back as a number or a map.
+            (("c", r#""1.27""#), "c: '1.27'\n"),
+            (("c", r#""k: v""#), "c: 'k: v'\n"),
+            (("d", r#""2""#), "d: '2'\n"),
+        ] {
+            let edited = edited("yaml", text, &[set], &[]).unwrap();
+            assert!(edited.contains(expected), "{set:?}: {edited}");
+        }
+        assert_eq!(
+            edited("yaml", "'x' # root\n", &[("", r#""y""#)], &[]).unwrap(),
+            "'y' # root\n"
+        );
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn yaml_collections_are_written_in_block_style() {
+        assert_eq!(
+            edited(
+                "yaml",
+                VALUES_YAML,
+                &[
+                    ("replicaCount", r#"{"min": 1, "max": 3}"#),
+                    ("image", r#"["a", {"b": 1}]"#),
+                    ("env.[0]", r#"{"name": "C", "value": "3"}"#),
+                ],
+                &["env.[1]", "ports"],
+            )
+            .unwrap(),
+            "\
+# Default values.
+replicaCount: # scale me
+  min: 1
+  max: 3
+
+image:
+  - a
+  - b: 1
+
+
+env:
+- name: C
+  value: '3'
+
+resources:
+  limits:
+    cpu: 100m
+"
+        );
+    }
+
+    #[test]
+    fn unsupported_inputs_are_errors() {
+        let err = edited("json", "{}", &[], &[]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Cannot preserve the formatting of json input: only TOML and \
+             YAML are supported"
+        );
+
+        #[cfg(feature = "yaml")]
+        {
+            let err =
+                edited("yaml", "--- {a: 1}\n", &[("a", "2")], &[]).unwrap_err();
+            assert!(err.to_string().contains("document marker"), "{err}");
+
+            // An alias would lose its anchor: caught by the reparse check.
+            let err = edited("yaml", "a: &x 1\nb: *x\n", &[("a", "2")], &[])
+                .unwrap_err();
+            assert!(err.to_string().contains("unchanged"), "{err}");
+        }
+    }
+}
@@ -0,0 +1,326 @@
+/*!
+# Flattening
+
+gron-style flat documents: one `path = value` assignment per leaf, which
+plain `grep` can search and `diff` can compare line by line.
+
+```text
+name.first = "John"
+name.last = "Doe"
+hobbies.[0] = "fishing"
+tags = []
+```
+
+Paths are written in the `jg` [path syntax](crate::utils::PathFormat::Jg),
+quoting field names as needed, so each one is also a query for its value;
+values are compact JSON. The leaves are the scalars and the empty objects and
+arrays, so every document, empty containers included, survives flattening
+and [`unflatten`]. The leaf of a document that is a scalar has the empty
+path (`= 1`).
+
+# Examples
+
+[`FlatPrinter`] prints the flat lines of the documents it is handed.
+
+```
+use jsongrep::{
+    flatten, printer::FlatPrinter, query::QueryDFA, search::Searcher,
+};
+
+# colored::control::set_override(false);
+let dfa = QueryDFA::from_query_str(flatten::LEAVES_QUERY).unwrap();
+let mut printer = FlatPrinter::new(Vec::new());
+Searcher::new(&dfa)
+    .search_slice("doc.json", br#"{"a": [1, {}], "b c": null}"#, &mut printer)
+    .unwrap();
+
+let flat = String::from_utf8(printer.into_inner()).unwrap();
+assert_eq!(flat, "a.[0] = 1\na.[1] = {}\n\"b c\" = null\n");
+
+let doc = flatten::unflatten(&flat).unwrap();
+assert_eq!(doc, serde_json::json!({"a": [1, {}], "b c": null}));
+```
+
+[`FlatPrinter`]: crate::printer::FlatPrinter
+*/
+use anyhow::{Context as _, Result, bail};
+use serde_json_borrow::Value;
+use std::collections::HashSet;
+
+use crate::{
+    query::{PathType, Query},
+    utils::{PathFormat, format_path},
+};
+
+/// A query matching every value of a document, of which the
+/// [leaves](is_leaf) are flattened.
+pub const LEAVES_QUERY: &str = "(* | [*])*";
+
+/// Whether `value` is a leaf of a flat document: a scalar, or an empty object
+/// or array.
+#[must_use]
+pub fn is_leaf(value: &Value) -> bool {
+    match value {
+        Value::Object(map) => map.is_empty(),
+        Value::Array(items) => items.is_empty(),
+        _ => true,
+    }
+}
+
+/// Rebuilds the document of the flat `path = value` lines of `text`.
+///
+/// Blank lines are skipped. Containers are created as paths step into them,
+/// and array elements that no line assigns are null. A later assignment to
+/// the same path replaces an earlier one.
+///
+/// # Errors
+///
+/// Returns an error naming the line if it is not an assignment, its path is
+/// not a path of field names and indices, its value is not JSON, it steps
+/// into a value that an earlier line assigned (other than a container of the
+/// right kind), or its index leaves more array elements unassigned than
+/// `text` has lines; or if `text` has no assignments.
+pub fn unflatten(text: &str) -> Result<serde_json::Value> {
+    let mut unflattened = Unflattened {
+        doc: None,
+        unassigned: HashSet::new(),
+        gaps_left: text.lines().count(),
+    };
+    for (idx, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        unflattened
+            .assign_line(line)
+            .with_context(|| format!("Failed to unflatten line {}", idx + 1))?;
+    }
+    unflattened.doc.context("No assignments to unflatten")
+}
+
+/// A document being rebuilt by [`unflatten`].
+struct Unflattened {
+    doc: Option<serde_json::Value>,
+    /// The paths of the null array elements that fill gaps before an
+    /// assigned index, which a later line may still step into.
+    unassigned: HashSet<Vec<PathType>>,
+    /// How many more gap elements may be created. Each element of a
+    /// flattened array has a line, so no valid input needs more gap elements
+    /// than it has lines, and a huge index cannot exhaust memory.
+    gaps_left: usize,
+}
+
+impl Unflattened {
+    /// Applies the assignment `line` to the document.
+    fn assign_line(&mut self, line: &str) -> Result<()> {
+        let (path, value) = split_assignment(line).with_context(|| {
+            format!("Expected `path = value`, got {line:?}")
+        })?;
+        let steps = parse_path(path)?;
+        let value = serde_json::from_str(value)
+            .with_context(|| format!("Invalid JSON value {value:?}"))?;
+
+        // Whether `slot` was created by this line rather than assigned.
+        let mut fresh = self.doc.is_none();
+        let mut slot = self.doc.get_or_insert(serde_json::Value::Null);
+        for (depth, step) in steps.iter().enumerate() {
+            if fresh
+                || (slot.is_null() && self.unassigned.remove(&steps[..depth]))
+            {
+                *slot = match step {
+                    PathType::Field(_) => {
+                        serde_json::Value::Object(serde_json::Map::new())
+                    }
+                    PathType::Index(_) => serde_json::Value::Array(Vec::new()),
+                };
+            }
+            (slot, fresh) = match (step, slot) {
+                (PathType::Field(name), serde_json::Value::Object(map)) => {
+                    let fresh = !map.contains_key(&**name);
+                    let slot = map
+                        .entry(name.to_string())
+                        .or_insert(serde_json::Value::Null);
+                    (slot, fresh)
+                }
+                (PathType::Index(idx), serde_json::Value::Array(items)) => {
+                    let fresh = items.len() <= *idx;
+                    if fresh {
+                        let gap = idx - items.len();
+                        if gap > self.gaps_left {
+                            bail!(
+                                "Index {idx} in {path} leaves more array \
+                                 elements unassigned than the input has lines"
+                            );
+                        }
+                        self.gaps_left -= gap;
+                        for unassigned in items.len()..*idx {
+                            let mut gap_path = steps[..depth].to_vec();
+                            gap_path.push(PathType::Index(unassigned));
+                            self.unassigned.insert(gap_path);
+                        }
+                        items.resize(idx + 1, serde_json::Value::Null);
+                    }
+                    (&mut items[*idx], fresh)
+                }
+                _ => bail!(
+                    "{path} conflicts with an earlier assignment to {}",
+                    format_path(&steps[..depth], PathFormat::Jg)
+                ),
+            };
+        }
+
+        // The value replaced, and the gaps inside it, are gone.
+        match slot {
+            serde_json::Value::Null => {
+                self.unassigned.remove(&steps);
+            }
+            serde_json::Value::Array(_) | serde_json::Value::Object(_) => {
+                self.unassigned.retain(|gap| !gap.starts_with(&steps));
+            }
+            _ => {}
+        }
+        *slot = value;
+        Ok(())
+    }
+}
+
+/// Splits `path = value` at the first ` = ` outside a quoted field name.
+fn split_assignment(line: &str) -> Option<(&str, &str)> {
+    if let Some(value) = line.strip_prefix("= ") {
+        return Some(("", value));
+    }
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if quoted && c == '\\' {
+            escaped = true;
+        } else if c == '"' {
+            quoted = !quoted;
+        } else if !quoted && line[i..].starts_with(" = ") {
+            return Some((&line[..i], &line[i + 3..]));
+        }
+    }
+    None
+}
+
+/// The steps of `text`, a path in `jg` syntax such as `a.[0]."b c"`.
+///
+/// # Errors
+///
+/// Returns an error if `text` is not a query, or is a query other than a
+/// sequence of field names and indices.
+pub fn parse_path(text: &str) -> Result<Vec<PathType>> {
+    fn push_steps(query: &Query, steps: &mut Vec<PathType>) -> bool {
+        match query {
+            Query::Field(name) => {
+                steps.push(PathType::Field(name.as_str().into()));
+            }
+            Query::Index(idx) => steps.push(PathType::Index(*idx)),
+            Query::Sequence(queries) => {
+                return queries.iter().all(|query| push_steps(query, steps));
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    let query: Query =
+        text.parse().with_context(|| format!("Invalid path {text:?}"))?;
+    let mut steps = Vec::new();
+    if !push_steps(&query, &mut steps) {
+        bail!("{text:?} is not a path: it must only name fields and indices");
+    }
+    Ok(steps)
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Unit testing.")]
+mod tests {
+    use super::*;
+    use crate::{printer::FlatPrinter, query::QueryDFA, search::Searcher};
+    use serde_json::json;
+
+    /// The flat lines of `json`, as `jg --flatten` prints them.
+    fn flatten(json: &str) -> String {
+        colored::control::set_override(false);
+        let dfa = QueryDFA::from_query_str(LEAVES_QUERY).unwrap();
+        let mut printer = FlatPrinter::new(Vec::new());
+        Searcher::new(&dfa)
+            .search_slice("-", json.as_bytes(), &mut printer)
+            .unwrap();
+        String::from_utf8(printer.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn documents_round_trip_through_flat_lines() {
+        for json in [
+            r#"{"a": {"b": [1, "x = y", null]}, "c d": {}, "": [[]]}"#,
+            r#"{"q\"\\": true, "a=": 1, "l\n": "v\nw", "[0]": 2.5}"#,
+            r"[[1, 2], {}, -3]",
+            "7",
+            "{}",
+        ] {
+            let flat = flatten(json);
+            let expected: serde_json::Value =
+                serde_json::from_str(json).unwrap();
+            assert_eq!(unflatten(&flat).unwrap(), expected, "{flat}");
+        }
+        assert_eq!(flatten("7"), "= 7\n");
+        assert_eq!(
+            flatten(r#"{"a": {"b c": [1, {}]}}"#),
+            "a.\"b c\".[0] = 1\na.\"b c\".[1] = {}\n"
+        );
+    }
+
+    #[test]
+    fn unflatten_fills_gaps_and_takes_the_last_assignment() {
+        let doc = unflatten("a.[2] = 1\n\n  b = 1\nb = {\"c\": 2}\n").unwrap();
+        assert_eq!(doc, json!({"a": [null, null, 1], "b": {"c": 2}}));
+
+        // Lines may come in any order, e.g. sorted as text.
+        let text = (0..12).map(|i| format!("a.[{i}].b = {i}\n"));
+        let mut lines = text.collect::<Vec<_>>();
+        lines.sort();
+        let doc = unflatten(&lines.concat()).unwrap();
+        assert_eq!(doc["a"][10], json!({"b": 10}));
+
+        // A gap element becomes a container when a later line steps into it,
+        // but not once the array holding it is replaced.
+        let doc = unflatten("a.[1] = 1\na.[0].b = 2").unwrap();
+        assert_eq!(doc, json!({"a": [{"b": 2}, 1]}));
+        let err = unflatten("a.[1] = 1\na = [null]\na.[0].b = 2").unwrap_err();
+        assert!(format!("{err:#}").contains("conflicts"), "{err:#}");
+    }
+
+    #[test]
+    fn unflatten_reports_bad_lines() {
+        for (text, message) in [
+            ("a = 1\nb", "Failed to unflatten line 2: Expected `path = value`"),
+            ("a.* = 1", "is not a path"),
+            ("a = nope", "Invalid JSON value"),
+            ("a = 1\na.b = 2", "a.b conflicts with an earlier assignment to a"),
+            ("a = {}\na.[0] = 2", "conflicts with an earlier assignment"),
+            (
+                "a = null\na.b = 1",
+                "a.b conflicts with an earlier assignment to a",
+            ),
+            (
+                "a.[1] = null\na.[1].b = 1",
+                "conflicts with an earlier assignment",
+            ),
+            (
+                "a.[18446744073709551615] = 1",
+                "Failed to unflatten line 1: Index 18446744073709551615 in \
+                 a.[18446744073709551615] leaves more array elements \
+                 unassigned than the input has lines",
+            ),
+            ("a = 1\nb.[100000000000] = 1", "Index 100000000000 in"),
+            ("\n", "No assignments to unflatten"),
+        ] {
+            let err = format!("{:#}", unflatten(text).unwrap_err());
+            assert!(err.contains(message), "{text:?}: {err}");
+        }
+    }
+}
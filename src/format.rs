@@ -0,0 +1,618 @@
+/*!
+# Input Formats
+
+Pluggable input formats.
+
+Every format `jg` can read is a [`FormatParser`]: a name (for `--format`),
+file extensions, optional content sniffing, and a parser to JSON. A
+[`FormatRegistry`] holds the available parsers and picks one for each input;
+[`FormatRegistry::new`] starts with the built-in formats (see
+[`Format::BUILTIN`]), and downstream crates can [`FormatRegistry::register`]
+their own, then search with them through [`Searcher::registry`].
+
+Built-in formats keep their fast paths (zero-copy JSON, the streaming
+engine, DFA-guided deserialization); registered formats are parsed to an
+owned [`serde_json::Value`] and searched as a tree.
+
+Output goes through a [`DocumentWriter`], which writes documents in any of
+the built-in formats (`jg --output-format`).
+
+# Examples
+
+```
+use jsongrep::{
+    Value,
+    format::{FormatParser, FormatRegistry},
+    query::{PathType, QueryDFA},
+    search::Searcher,
+};
+
+/// `key=value` lines.
+struct Properties;
+
+impl FormatParser for Properties {
+    fn name(&self) -> &'static str {
+        "properties"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["properties"]
+    }
+
+    fn parse(&self, bytes: &[u8]) -> anyhow::Result<serde_json::Value> {
+        let text = std::str::from_utf8(bytes)?;
+        let map = text
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .map(|(k, v)| (k.trim().to_string(), v.trim().into()))
+            .collect();
+        Ok(serde_json::Value::Object(map))
+    }
+}
+
+let mut registry = FormatRegistry::new();
+registry.register(Properties);
+
+let dfa = QueryDFA::from_query_str("port").unwrap();
+let searcher = Searcher::new(&dfa)
+    .parser(registry.get("properties").unwrap());
+let mut found = Vec::new();
+searcher
+    .search_slice(
+        "app.properties",
+        b"host = example.com\nport = 8080\n",
+        &mut |_: &str, _: &[PathType], value: &Value| {
+            found.push(value.to_string());
+            Ok(())
+        },
+    )
+    .unwrap();
+assert_eq!(found, [r#""8080""#]);
+```
+
+[`Searcher::registry`]: crate::search::Searcher::registry
+*/
+use anyhow::{Context as _, Result};
+use std::{any::Any, fmt::Debug, io::Write, path::Path};
+
+use crate::{
+    query::PathType,
+    search::{Format, parse_jsonl, to_owned_json},
+    utils::{PathFormat, format_path},
+};
+
+/// A format that inputs can be parsed from.
+pub trait FormatParser: Any + Send + Sync {
+    /// The format's name, as given to `--format`.
+    fn name(&self) -> &'static str;
+
+    /// File extensions (without the dot) detected as this format.
+    fn extensions(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Whether `bytes`, from an input without a recognized extension, look
+    /// like this format, e.g. because they start with its magic number.
+    fn sniff(&self, bytes: &[u8]) -> bool {
+        let _ = bytes;
+        false
+    }
+
+    /// Parse a whole input.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `bytes` is not valid in this format.
+    fn parse(&self, bytes: &[u8]) -> Result<serde_json::Value>;
+}
+
+impl Debug for dyn FormatParser {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("FormatParser").field(&self.name()).finish()
+    }
+}
+
+/// A built-in format, recognized by the search engine (see
+/// [`builtin_format`]) so that it can take its fast paths.
+struct Builtin(Format);
+
+impl FormatParser for Builtin {
+    fn name(&self) -> &'static str {
+        self.0.name()
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        self.0.extensions()
+    }
+
+    fn parse(&self, bytes: &[u8]) -> Result<serde_json::Value> {
+        match self.0 {
+            Format::Auto | Format::Json => serde_json::from_slice(bytes)
+                .with_context(|| format!("Failed to parse as {}", self.0)),
+            Format::Jsonl => {
+                let text = str::from_utf8(bytes).map_err(|_| {
+                    anyhow::anyhow!("JSONL input is not valid UTF-8")
+                })?;
+                Ok(serde_json::to_value(parse_jsonl(text)?)?)
+            }
+            other => to_owned_json(bytes, other),
+        }
+    }
+}
+
+/// The built-in format `parser` stands for, if it is one.
+pub(crate) fn builtin_format(parser: &dyn FormatParser) -> Option<Format> {
+    (parser as &dyn Any).downcast_ref::<Builtin>().map(|b| b.0)
+}
+
+/// The set of formats available for parsing inputs.
+#[derive(Debug)]
+pub struct FormatRegistry {
+    /// In registration order; later parsers take precedence when detecting.
+    parsers: Vec<Box<dyn FormatParser>>,
+}
+
+impl Default for FormatRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FormatRegistry {
+    /// A registry of the built-in formats. Formats whose cargo feature is
+    /// disabled are still registered, and fail to parse with an error
+    /// saying so.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            parsers: Format::BUILTIN
+                .into_iter()
+                .map(|format| Box::new(Builtin(format)) as Box<_>)
+                .collect(),
+        }
+    }
+
+    /// Add `parser`, replacing any format with the same name (including a
+    /// built-in one). Its extensions and sniffing take precedence over
+    /// formats registered before it.
+    pub fn register(&mut self, parser: impl FormatParser) -> &mut Self {
+        self.parsers.retain(|other| other.name() != parser.name());
+        self.parsers.push(Box::new(parser));
+        self
+    }
+
+    /// The names of the registered formats, in registration order.
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.parsers.iter().map(|parser| parser.name())
+    }
+
+    /// The format called `name`.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&dyn FormatParser> {
+        self.parsers
+            .iter()
+            .find(|parser| parser.name() == name)
+            .map(AsRef::as_ref)
+    }
+
+    /// The format for the extension of `path`.
+    #[must_use]
+    pub fn for_path(&self, path: &Path) -> Option<&dyn FormatParser> {
+        let ext = path.extension()?.to_str()?;
+        self.parsers
+            .iter()
+            .rev()
+            .find(|parser| parser.extensions().contains(&ext))
+            .map(AsRef::as_ref)
+    }
+
+    /// The format that claims `bytes` through [`FormatParser::sniff`].
+    #[must_use]
+    pub fn sniff(&self, bytes: &[u8]) -> Option<&dyn FormatParser> {
+        self.parsers
+            .iter()
+            .rev()
+            .find(|parser| parser.sniff(bytes))
+            .map(AsRef::as_ref)
+    }
+
+    /// The format to parse an input with: by the extension of `path`, else
+    /// by sniffing `bytes`, else JSON (as `jg` does).
+    #[must_use]
+    pub fn resolve(
+        &self,
+        path: Option<&Path>,
+        bytes: &[u8],
+    ) -> &dyn FormatParser {
+        path.and_then(|path| self.for_path(path))
+            .or_else(|| self.sniff(bytes))
+            .unwrap_or(&Builtin(Format::Json))
+    }
+}
+
+/// Writes documents to a [`Write`] in one of the built-in formats.
+///
+/// Consecutive documents are separated as their format allows: JSON values
+/// and JSONL records each end with a newline, YAML documents are separated
+/// by `---`, TOML documents by a blank line, and CBOR and `MessagePack`
+/// items follow each other directly (a CBOR sequence or a `MessagePack`
+/// stream). [`Format::Auto`] writes JSON.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "yaml")] {
+/// use jsongrep::{Format, format::DocumentWriter};
+///
+/// let mut writer = DocumentWriter::new(Vec::new(), Format::Yaml);
+/// writer.write(&serde_json::json!({"a": [1, 2]})).unwrap();
+/// writer.write(&serde_json::json!("b")).unwrap();
+/// assert_eq!(
+///     String::from_utf8(writer.into_inner()).unwrap(),
+///     "a:\n- 1\n- 2\n---\nb\n"
+/// );
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct DocumentWriter<W> {
+    writer: W,
+    format: Format,
+    pretty: bool,
+    /// Whether a document has been written, to separate the next.
+    written: bool,
+}
+
+impl<W: Write> DocumentWriter<W> {
+    /// A writer of pretty-printed `format` documents to `writer`.
+    #[must_use]
+    pub const fn new(writer: W, format: Format) -> Self {
+        Self { writer, format, pretty: true, written: false }
+    }
+
+    /// Whether to pretty-print, for the formats that have a compact form:
+    /// JSON (on one line) and TOML (arrays on one line). On by default.
+    #[must_use]
+    pub const fn pretty(mut self, yes: bool) -> Self {
+        self.pretty = yes;
+        self
+    }
+
+    /// The underlying writer.
+    pub const fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Consumes the writer, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Writes `value` as the next document.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `value` cannot be represented in the format (TOML
+    /// documents must be tables, and TOML has no null), if the format's
+    /// cargo feature is disabled, or if writing fails.
+    pub fn write(&mut self, value: &serde_json::Value) -> Result<()> {
+        let bytes = serialize(value, self.format, self.pretty)?;
+        if self.written {
+            match self.format {
+                Format::Yaml => self.writer.write_all(b"---\n")?,
+                Format::Toml => self.writer.write_all(b"\n")?,
+                _ => {}
+            }
+        }
+        self.writer.write_all(&bytes)?;
+        self.written = true;
+        Ok(())
+    }
+
+    /// Writes `doc` as a whole document, as [`DocumentWriter::write`] does,
+    /// except that JSONL writes each element of a top-level array as a
+    /// record of its own: the inverse of how JSONL input is read.
+    ///
+    /// # Errors
+    ///
+    /// See [`DocumentWriter::write`].
+    pub fn write_document(&mut self, doc: &serde_json::Value) -> Result<()> {
+        match (self.format, doc) {
+            (Format::Jsonl, serde_json::Value::Array(records)) => {
+                records.iter().try_for_each(|record| self.write(record))
+            }
+            _ => self.write(doc),
+        }
+    }
+}
+
+/// `value` as one document in `format`, newline-terminated for the text
+/// formats.
+fn serialize(
+    value: &serde_json::Value,
+    format: Format,
+    pretty: bool,
+) -> Result<Vec<u8>> {
+    let mut bytes = match format {
+        Format::Auto | Format::Json if pretty => {
+            serde_json::to_vec_pretty(value)?
+        }
+        Format::Auto | Format::Json | Format::Jsonl => {
+            serde_json::to_vec(value)?
+        }
+
+        #[cfg(feature = "yaml")]
+        Format::Yaml => serde_yaml::to_string(value)
+            .context("Failed to write YAML")?
+            .into_bytes(),
+        #[cfg(not(feature = "yaml"))]
+        Format::Yaml => {
+            anyhow::bail!(
+                "YAML support not enabled. Rebuild with --features yaml"
+            )
+        }
+
+        #[cfg(feature = "toml")]
+        Format::Toml => {
+            check_toml(value)?;
+            if pretty {
+                toml::to_string_pretty(value)
+            } else {
+                toml::to_string(value)
+            }
+            .context("Failed to write TOML")?
+            .into_bytes()
+        }
+        #[cfg(not(feature = "toml"))]
+        Format::Toml => {
+            anyhow::bail!(
+                "TOML support not enabled. Rebuild with --features toml"
+            )
+        }
+
+        #[cfg(feature = "cbor")]
+        Format::Cbor => {
+            let mut bytes = Vec::new();
+            ciborium::into_writer(value, &mut bytes)
+                .context("Failed to write CBOR")?;
+            return Ok(bytes);
+        }
+        #[cfg(not(feature = "cbor"))]
+        Format::Cbor => {
+            anyhow::bail!(
+                "CBOR support not enabled. Rebuild with --features cbor"
+            )
+        }
+
+        #[cfg(feature = "msgpack")]
+        Format::Msgpack => {
+            return rmp_serde::to_vec(value)
+                .context("Failed to write MessagePack");
+        }
+        #[cfg(not(feature = "msgpack"))]
+        Format::Msgpack => {
+            anyhow::bail!(
+                "MessagePack support not enabled. Rebuild with --features msgpack"
+            )
+        }
+    };
+    if !bytes.ends_with(b"\n") {
+        bytes.push(b'\n');
+    }
+    Ok(bytes)
+}
+
+/// Fails, naming the offending path, unless `value` can be written as a
+/// TOML document: a table, without nulls.
+#[cfg_attr(not(feature = "toml"), expect(dead_code, reason = "no TOML"))]
+fn check_toml(value: &serde_json::Value) -> Result<()> {
+    if !value.is_object() {
+        anyhow::bail!(
+            "Cannot write {} as TOML: a TOML document must be a table",
+            kind(value)
+        );
+    }
+    let mut stack = vec![(Vec::new(), value)];
+    while let Some((path, value)) = stack.pop() {
+        match value {
+            serde_json::Value::Null => anyhow::bail!(
+                "Cannot write {} as TOML: TOML has no null value",
+                format_path(&path, PathFormat::Jg)
+            ),
+            serde_json::Value::Object(map) => {
+                for (key, child) in map {
+                    let mut child_path = path.clone();
+                    child_path.push(PathType::Field(key.as_str().into()));
+                    stack.push((child_path, child));
+                }
+            }
+            serde_json::Value::Array(items) => {
+                for (idx, child) in items.iter().enumerate() {
+                    let mut child_path = path.clone();
+                    child_path.push(PathType::Index(idx));
+                    stack.push((child_path, child));
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// The kind of `value`, for messages.
+const fn kind(value: &serde_json::Value) -> &'static str {
+    match value {
+        serde_json::Value::Null => "null",
+        serde_json::Value::Bool(_) => "a boolean",
+        serde_json::Value::Number(_) => "a number",
+        serde_json::Value::String(_) => "a string",
+        serde_json::Value::Array(_) => "an array",
+        serde_json::Value::Object(_) => "an object",
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Unit testing.")]
+mod tests {
+    use super::*;
+
+    /// A made-up binary format: the magic bytes `JG`, then one JSON value.
+    struct Magic;
+
+    impl FormatParser for Magic {
+        fn name(&self) -> &'static str {
+            "magic"
+        }
+
+        fn extensions(&self) -> &'static [&'static str] {
+            &["jgb", "json"]
+        }
+
+        fn sniff(&self, bytes: &[u8]) -> bool {
+            bytes.starts_with(b"JG")
+        }
+
+        fn parse(&self, bytes: &[u8]) -> Result<serde_json::Value> {
+            Ok(serde_json::from_slice(&bytes[2..])?)
+        }
+    }
+
+    #[test]
+    fn builtins_resolve_by_extension_and_default_to_json() {
+        let registry = FormatRegistry::new();
+        assert_eq!(
+            registry.names().collect::<Vec<_>>(),
+            ["json", "jsonl", "yaml", "toml", "cbor", "msgpack"]
+        );
+
+        let yaml = registry.resolve(Some(Path::new("a.yml")), b"");
+        assert_eq!(builtin_format(yaml), Some(Format::Yaml));
+        let json = registry.resolve(Some(Path::new("a.txt")), b"JG1");
+        assert_eq!(builtin_format(json), Some(Format::Json));
+        assert!(registry.get("magic").is_none());
+    }
+
+    #[test]
+    fn registered_formats_take_precedence() {
+        let mut registry = FormatRegistry::new();
+        registry.register(Magic);
+
+        for (path, bytes) in [
+            (Some(Path::new("a.jgb")), &b""[..]),
+            (Some(Path::new("a.json")), b""),
+            (None, b"JG1"),
+        ] {
+            let parser = registry.resolve(path, bytes);
+            assert_eq!(parser.name(), "magic");
+            assert_eq!(builtin_format(parser), None);
+        }
+        assert_eq!(
+            registry.get("magic").unwrap().parse(b"JG[1]").unwrap(),
+            serde_json::json!([1])
+        );
+
+        // Registering a name again replaces the earlier format.
+        registry.register(Builtin(Format::Json));
+        let json = registry.get("json").unwrap();
+        assert_eq!(builtin_format(json), Some(Format::Json));
+        assert_eq!(registry.names().count(), 7);
+    }
+
+    #[test]
+    fn builtin_parsers_parse_to_json() {
+        let registry = FormatRegistry::new();
+        let parse = |name: &str, bytes: &[u8]| {
+            registry.get(name).unwrap().parse(bytes).unwrap().to_string()
+        };
+        assert_eq!(parse("json", br#"{"a": 1}"#), r#"{"a":1}"#);
+        assert_eq!(parse("jsonl", b"1\n2\n"), "[1,2]");
+        #[cfg(feature = "yaml")]
+        assert_eq!(parse("yaml", b"a: 1"), r#"{"a":1}"#);
+    }
+
+    #[test]
+    fn document_writer_writes_every_builtin_format() {
+        let doc = serde_json::json!({"name": "a", "tags": [1, 2]});
+        let written = |format, pretty| {
+            let mut writer =
+                DocumentWriter::new(Vec::new(), format).pretty(pretty);
+            writer.write_document(&doc).unwrap();
+            writer.write_document(&doc).unwrap();
+            writer.into_inner()
+        };
+        let text = |format, pretty| {
+            String::from_utf8(written(format, pretty)).unwrap()
+        };
+
+        assert_eq!(
+            text(Format::Json, false),
+            "{\"name\":\"a\",\"tags\":[1,2]}\n".repeat(2)
+        );
+        assert_eq!(text(Format::Json, true).lines().count(), 2 * 7);
+        #[cfg(feature = "toml")]
+        assert_eq!(
+            text(Format::Toml, false),
+            "name = \"a\"\ntags = [1, 2]\n\nname = \"a\"\ntags = [1, 2]\n"
+        );
+
+        // Every format reads back what it wrote.
+        let registry = FormatRegistry::new();
+        for (enabled, format) in [
+            (cfg!(feature = "yaml"), Format::Yaml),
+            (cfg!(feature = "toml"), Format::Toml),
+            (cfg!(feature = "cbor"), Format::Cbor),
+        ] {
+            if !enabled {
+                continue;
+            }
+            let mut writer = DocumentWriter::new(Vec::new(), format);
+            writer.write(&doc).unwrap();
+            let bytes = writer.into_inner();
+            let parsed = registry.get(format.name()).unwrap().parse(&bytes);
+            assert_eq!(parsed.unwrap(), doc, "{format}");
+        }
+        #[cfg(feature = "msgpack")]
+        {
+            let msgpack = written(Format::Msgpack, true);
+            let mut reader = &msgpack[..];
+            for _ in 0..2 {
+                let item: serde_json::Value =
+                    rmp_serde::from_read(&mut reader).unwrap();
+                assert_eq!(item, doc);
+            }
+            assert!(reader.is_empty());
+        }
+    }
+
+    #[test]
+    fn jsonl_documents_write_each_record() {
+        let mut writer = DocumentWriter::new(Vec::new(), Format::Jsonl);
+        writer
+            .write_document(&serde_json::json!([{"a": 1}, [2], "3"]))
+            .unwrap();
+        writer.write(&serde_json::json!([4, 5])).unwrap();
+        assert_eq!(
+            String::from_utf8(writer.into_inner()).unwrap(),
+            "{\"a\":1}\n[2]\n\"3\"\n[4,5]\n"
+        );
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml_documents_must_be_tables_without_nulls() {
+        let mut writer = DocumentWriter::new(Vec::new(), Format::Toml);
+        for (value, message) in [
+            (
+                serde_json::json!([1]),
+                "Cannot write an array as TOML: a TOML document must be a table",
+            ),
+            (
+                serde_json::json!({"a": {"b": [1, null]}}),
+                "Cannot write a.b.[1] as TOML: TOML has no null value",
+            ),
+        ] {
+            let err = writer.write(&value).unwrap_err();
+            assert_eq!(err.to_string(), message);
+        }
+        assert!(writer.into_inner().is_empty());
+    }
+}
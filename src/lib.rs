@@ -108,13 +108,23 @@ You can try queries interactively in the [playground](https://micahkepe.com/json
 [`query`]: crate::query
 */
 pub mod commands;
+pub mod diff;
+pub mod document;
+pub mod edit;
+pub mod flatten;
+pub mod format;
+pub mod printer;
 pub mod query;
+pub mod search;
 pub mod utils;
 
 /// Re-export [`serde_json_borrow::Value`] so downstream users don't need to
 /// depend on `serde_json_borrow` directly.
 pub use serde_json_borrow::Value;
 
+pub use document::Document;
+pub use search::Format;
+
 /// Query a JSON document with a query string, returning all matches.
 ///
 /// This is the simplest entry point for the library. For repeated queries
@@ -3,24 +3,40 @@ Main binary for jsongrep.
 */
 
 use anyhow::{Context as _, Result};
-use clap::{ArgAction, CommandFactory as _, Parser, Subcommand};
+use clap::{
+    ArgAction, ArgGroup, CommandFactory as _, Parser, Subcommand,
+    builder::{PossibleValuesParser, TypedValueParser},
+};
 use clap_complete::generate;
 use colored::Colorize;
-use memmap2::{Mmap, MmapOptions};
 use serde_json_borrow::Value;
 use std::{
-    fs::OpenOptions,
+    fs::File,
     io::{
-        self, BufWriter, ErrorKind, IsTerminal as _, Read as _, Write, stdout,
+        self, BufRead, BufReader, BufWriter, ErrorKind, IsTerminal as _, Write,
+        stdout,
     },
-    path::PathBuf,
-    str::Utf8Error,
+    path::{Path, PathBuf},
 };
 
 use jsongrep::{
-    commands,
-    query::{Query, QueryDFA},
-    utils::{WriteOptions, depth, write_colored_result},
+    Format, commands, diff,
+    edit::{
+        self, Template, patch,
+        redact::{self, Redactor},
+    },
+    flatten,
+    format::{DocumentWriter, FormatParser, FormatRegistry},
+    printer::{
+        FlatPrinter, FormatPrinter, JsonPrinter, StandardPrinter, SummaryKind,
+        SummaryPrinter,
+    },
+    query::{PathType, Query, QueryDFA},
+    search::{
+        Input, STDIN_NAME, SearchMode, SearchSummary, Searcher, Sink,
+        is_broken_pipe,
+    },
+    utils::{PathFormat, WriteOptions, depth, write_colored_result},
 };
 
 /// Query an input JSON document against a jsongrep query.
@@ -33,6 +49,9 @@ use jsongrep::{
     long_about = None,
     disable_help_subcommand = true
 )]
+#[command(group(
+    ArgGroup::new("edits").args(["set", "delete", "redact"]).multiple(true)
+))]
 #[expect(clippy::struct_excessive_bools, reason = "CLI flags.")]
 struct Args {
     /// Optional subcommands.
@@ -58,7 +77,7 @@ struct Args {
     #[arg(short, long, action = ArgAction::SetTrue)]
     ignore_case: bool,
     /// Do not pretty-print the JSON output.
-    #[arg(long, action = ArgAction::SetTrue)]
+    #[arg(long, global = true, action = ArgAction::SetTrue)]
     compact: bool,
     /// Print matched strings without JSON quotes or escaping (like `jq -r`).
     ///
@@ -90,6 +109,13 @@ struct Args {
     /// Never print the path header, even in a terminal.
     #[arg(long, action = ArgAction::SetTrue, conflicts_with = "with_path")]
     no_path: bool,
+    /// Syntax of the path headers.
+    ///
+    /// `jg` paths (the default) quote field names as needed, so a printed
+    /// path can be used as a query; the others can be pasted into tools
+    /// that take JSON Pointers, `JSONPath`, or jq paths.
+    #[arg(long, value_name = "FORMAT", value_enum, default_value_t)]
+    path_format: PathFormat,
     /// Quiet: write nothing to stdout; communicate via the exit status
     /// only (errors still print to stderr).
     #[arg(
@@ -99,9 +125,184 @@ struct Args {
         conflicts_with_all = ["count", "depth"]
     )]
     quiet: bool,
+    /// Stop after NUM matches per input (like `grep -m`). With `--count`,
+    /// counts are capped at NUM. Large and non-JSON inputs are not read past
+    /// that many matches, so later errors in them go unreported.
+    #[arg(short = 'm', long, value_name = "NUM")]
+    max_count: Option<usize>,
+    /// Fail on inputs nested deeper than NUM levels (as reported by
+    /// `--depth`), e.g. to guard against hostile uploads.
+    #[arg(long, value_name = "NUM", conflicts_with = "depth")]
+    max_depth: Option<usize>,
+    /// Parse large JSON files whole and search them on all cores instead
+    /// of streaming them, trading memory for speed.
+    #[cfg(feature = "rayon")]
+    #[arg(long, action = ArgAction::SetTrue)]
+    parallel: bool,
+    /// Print results as JSON Lines events (like `rg --json`).
+    ///
+    /// One `begin`, `match`, `end`, or `summary` object per line, with the
+    /// file, format, match path (as an array of segments), and value.
+    #[arg(
+        long,
+        action = ArgAction::SetTrue,
+        conflicts_with_all = [
+            "count", "depth", "files_with_matches", "quiet", "no_display"
+        ]
+    )]
+    json: bool,
+    /// Replace the values matching QUERY with VALUE, a JSON value, and
+    /// print the whole edited document. Can be repeated.
+    ///
+    /// Strings in VALUE can refer to the match they replace: `{{value}}`
+    /// (the matched value), `{{key}}` (its field name or index), and
+    /// `{{path}}` (its path). All positional arguments are files.
+    #[arg(
+        long,
+        num_args = 2,
+        value_names = ["QUERY", "VALUE"],
+        action = ArgAction::Append,
+        conflicts_with_all = [
+            "count", "depth", "files_with_matches", "quiet", "no_display",
+            "json", "max_count"
+        ]
+    )]
+    set: Vec<String>,
+    /// Remove the object members and array elements matching QUERY and
+    /// print the rest of the document. Can be repeated; applied after any
+    /// `--set`.
+    #[arg(
+        long,
+        value_name = "QUERY",
+        action = ArgAction::Append,
+        conflicts_with_all = [
+            "count", "depth", "files_with_matches", "quiet", "no_display",
+            "json", "max_count"
+        ]
+    )]
+    delete: Vec<String>,
+    /// Redact the strings and numbers matching QUERY, or inside a match,
+    /// and print the rest of the document unchanged. Can be repeated;
+    /// applied after any `--set` and `--delete`.
+    ///
+    /// Strings become `--redact-placeholder`, numbers become 0, and
+    /// booleans and nulls are kept. JSONL input is redacted line by line as
+    /// it is read.
+    #[arg(
+        long,
+        value_name = "QUERY",
+        action = ArgAction::Append,
+        conflicts_with_all = [
+            "count", "depth", "files_with_matches", "quiet", "no_display",
+            "json", "max_count"
+        ]
+    )]
+    redact: Vec<String>,
+    /// The text redacted strings are replaced with.
+    #[arg(
+        long,
+        value_name = "TEXT",
+        default_value = redact::DEFAULT_PLACEHOLDER,
+        requires = "redact"
+    )]
+    redact_placeholder: String,
+    /// Redact to keyed hashes instead of the placeholder: strings become
+    /// `sha256:` and 16 hex digits of their HMAC-SHA256 under KEY, numbers
+    /// an integer from the same hash.
+    ///
+    /// Equal values redact to equal hashes, so redacted records can still
+    /// be joined, while without KEY a hash cannot be checked against
+    /// guesses. Keep KEY out of shell history, e.g. with
+    /// `--redact-key "$(cat key.txt)"`.
+    #[arg(
+        long,
+        value_name = "KEY",
+        requires = "redact",
+        conflicts_with = "redact_placeholder"
+    )]
+    redact_key: Option<String>,
+    /// Print the `--set`, `--delete`, and `--redact` edits as an RFC 6902
+    /// JSON Patch instead of the edited document.
+    ///
+    /// Paths are RFC 6901 JSON Pointers. `jg patch apply` applies the patch
+    /// later.
+    #[arg(long, action = ArgAction::SetTrue, requires = "edits")]
+    patch: bool,
+    /// Print the edited TOML or YAML input in its own format, changing only
+    /// the edited values: comments, key order, and layout are kept.
+    #[arg(
+        long,
+        action = ArgAction::SetTrue,
+        requires = "edits",
+        conflicts_with = "patch"
+    )]
+    preserve_format: bool,
+    /// Print the input restricted to the matches of the query: one
+    /// document of the same shape, holding only the matched values and the
+    /// objects and arrays leading to them.
+    #[arg(
+        long,
+        visible_alias = "keep",
+        action = ArgAction::SetTrue,
+        conflicts_with_all = [
+            "count", "depth", "files_with_matches", "quiet", "no_display",
+            "json", "max_count", "set", "delete", "redact"
+        ]
+    )]
+    prune: bool,
+    /// Print one `path = value` line per leaf (scalar, or empty object or
+    /// array) instead of matches, like gron, to search with `grep` or
+    /// compare with `diff`. All positional arguments are files.
+    ///
+    /// Paths are in `jg` syntax, so each is a query for its value; values
+    /// are compact JSON. With several files, lines start with `FILE:`.
+    #[arg(
+        long,
+        action = ArgAction::SetTrue,
+        conflicts_with_all = [
+            "count", "depth", "files_with_matches", "quiet", "no_display",
+            "json", "raw_output", "max_count", "set", "delete", "redact",
+            "prune", "output_format"
+        ]
+    )]
+    flatten: bool,
+    /// Rebuild the document of `--flatten` lines read from the single input
+    /// and print it.
+    #[arg(
+        long,
+        action = ArgAction::SetTrue,
+        conflicts_with_all = [
+            "count", "depth", "files_with_matches", "quiet", "no_display",
+            "json", "raw_output", "max_count", "set", "delete", "redact",
+            "prune", "flatten", "format"
+        ]
+    )]
+    unflatten: bool,
     /// Input format (auto-detects from file extension if omitted).
-    #[arg(short = 'f', long, default_value = "auto")]
-    format: Format,
+    #[arg(
+        short = 'f',
+        long,
+        default_value = "auto",
+        value_parser = PossibleValuesParser::new(format_names())
+    )]
+    format: String,
+    /// Output format of matches and edited documents.
+    ///
+    /// JSON (the default) is colored like the rest of the output; the other
+    /// formats write each match as a document of its own, without path
+    /// headers. `--compact` prints JSON on one line and TOML arrays inline.
+    #[arg(
+        short = 'o',
+        long,
+        value_name = "FORMAT",
+        default_value = "json",
+        value_parser = output_format_parser(),
+        conflicts_with_all = [
+            "count", "depth", "files_with_matches", "quiet", "no_display",
+            "json", "raw_output", "preserve_format"
+        ]
+    )]
+    output_format: Format,
 }
 
 /// Available subcommands for `jg`.
@@ -110,6 +311,65 @@ enum Commands {
     #[command(subcommand)]
     /// Generate additional documentation and/or completions.
     Generate(GenerateCommand),
+    #[command(subcommand)]
+    /// Work with RFC 6902 JSON Patches.
+    Patch(PatchCommand),
+    /// Convert a whole document from one format to another, like an empty
+    /// query with `--output-format`.
+    Convert {
+        /// Path to the document. If omitted, reads from STDIN.
+        #[arg(value_name = "FILE")]
+        input: Option<PathBuf>,
+        /// Input format (auto-detects from file extension if omitted).
+        #[arg(
+            short = 'f',
+            long,
+            default_value = "auto",
+            value_parser = PossibleValuesParser::new(format_names())
+        )]
+        format: String,
+        /// Output format. A JSONL document is an array, written one
+        /// element per line.
+        #[arg(
+            short = 'o',
+            long,
+            value_name = "FORMAT",
+            default_value = "json",
+            value_parser = output_format_parser()
+        )]
+        output_format: Format,
+    },
+    /// Compare two documents and print the values added, removed, or
+    /// changed, by path.
+    ///
+    /// Objects are compared regardless of key order, and arrays index by
+    /// index. Exits with 0 if the documents are the same and 1 if they
+    /// differ, like diff(1).
+    Diff {
+        /// Path to the old document.
+        old: PathBuf,
+        /// Path to the new document.
+        new: PathBuf,
+        /// Only compare the values this query matches in either document.
+        query: Option<String>,
+        /// Input format of both documents (auto-detects each from its file
+        /// extension if omitted).
+        #[arg(
+            short = 'f',
+            long,
+            default_value = "auto",
+            value_parser = PossibleValuesParser::new(format_names())
+        )]
+        format: String,
+        /// Print the changes as a JSON array of objects with their `op`
+        /// (added, removed, or changed), `path`, and values.
+        #[arg(long, conflicts_with = "patch")]
+        json: bool,
+        /// Print the changes as an RFC 6902 JSON Patch that turns the old
+        /// document into the new one.
+        #[arg(long)]
+        patch: bool,
+    },
 }
 
 /// Generate shell completions and man page.
@@ -126,17 +386,30 @@ enum GenerateCommand {
     },
 }
 
-/// Minimum file size for which memory-mapping is attempted.
-///
-/// For small files, it is likely that a single read call is at least as fast or faster than mmap
-/// (mmap setup and page-fault overhead dominate for small files) and avoids mmap's file-truncation
-/// hazards.
-///
-/// NOTE: in the future when globbing (<https://github.com/micahkepe/jsongrep/issues/33>) and
-/// recursive searching are enabled, can look into other heuristics for performance.
-///
-/// See: <https://burntsushi.net/ripgrep/#mechanics>.
-const MMAP_MIN_FILE_SIZE: u64 = 1 << 20; // 1 MiB
+/// Work with RFC 6902 JSON Patches, such as those printed by `--patch`.
+#[derive(Subcommand)]
+enum PatchCommand {
+    /// Apply a JSON Patch to a document and print the result as JSON.
+    ///
+    /// The patch is applied atomically: if any operation fails, nothing is
+    /// printed.
+    Apply {
+        /// Path to the JSON Patch.
+        patch: PathBuf,
+        /// Path to the document. If omitted, reads from STDIN.
+        #[arg(value_name = "FILE")]
+        input: Option<PathBuf>,
+        /// Input format of the document (auto-detects from file extension
+        /// if omitted).
+        #[arg(
+            short = 'f',
+            long,
+            default_value = "auto",
+            value_parser = PossibleValuesParser::new(format_names())
+        )]
+        format: String,
+    },
+}
 
 /// Ceiling on DFA states during query compilation. Subset construction is
 /// worst-case exponential in the query length, so a short adversarial query
@@ -146,285 +419,383 @@ const MMAP_MIN_FILE_SIZE: u64 = 1 << 20; // 1 MiB
 /// realistic query (which needs tens of states).
 const DEFAULT_MAX_DFA_STATES: usize = 1 << 18;
 
-/// Possible input sources for jsongrep.
-///
-/// Input is kept as raw bytes so that binary formats (CBOR, `MessagePack`)
-/// work from any source; UTF-8 is validated only when a text format needs
-/// it.
-enum Input {
-    /// Fully buffered input: stdin, small files, non-regular files (FIFOs,
-    /// process substitution), and the fallback when mmap fails.
-    Buffer(Vec<u8>),
-    /// A memory-mapped file from the file system. Assumes an immutable handle.
-    File(Mmap),
+/// The formats `jg` can read. Register additional `FormatParser`s here to
+/// make them available to `--format` and extension detection.
+fn format_registry() -> FormatRegistry {
+    FormatRegistry::new()
+}
+
+/// The accepted values of `--format`.
+fn format_names() -> Vec<&'static str> {
+    std::iter::once("auto").chain(format_registry().names()).collect()
 }
 
-impl Input {
-    fn to_str(&self) -> Result<&str, Utf8Error> {
-        str::from_utf8(self.to_bytes())
+/// The accepted values of `--output-format`: the built-in formats.
+fn output_format_parser() -> impl TypedValueParser<Value = Format> {
+    PossibleValuesParser::new(Format::BUILTIN.map(Format::name))
+        .map(|name| Format::from_name(&name).unwrap_or_default())
+}
+
+/// Fails when binary `format` output would go to a terminal, where it is
+/// garbage.
+fn check_output(format: Format) -> Result<()> {
+    if matches!(format, Format::Cbor | Format::Msgpack)
+        && io::stdout().is_terminal()
+    {
+        anyhow::bail!(
+            "Refusing to write {format} to a terminal; redirect the output \
+             to a file or a pipe"
+        );
     }
+    Ok(())
+}
 
-    fn to_bytes(&self) -> &[u8] {
+/// Fails, after printing the help message, when STDIN is a terminal rather
+/// than piped input, since there is then nothing to read.
+fn check_stdin() -> Result<()> {
+    if io::stdin().is_terminal() {
+        // No piped input and no file specified
+        let mut cmd = Args::command();
+        cmd.print_help()?;
+        anyhow::bail!("No input specified");
+    }
+    Ok(())
+}
+
+/// The printer for a query's results: the matches themselves, a line per
+/// input for `--count`, `-l`, `-q`, and `--no-display`, or JSON events for
+/// `--json`.
+enum Printer<W: Write> {
+    Standard(StandardPrinter<W>),
+    Summary(SummaryPrinter<W>),
+    Json(JsonPrinter<W>),
+    Format(FormatPrinter<W>),
+    Flat(FlatPrinter<W>),
+}
+
+impl<W: Write> Printer<W> {
+    fn sink(&mut self) -> &mut dyn Sink {
         match self {
-            Self::Buffer(buf) => buf.as_slice(),
-            Self::File(mmap) => mmap.as_ref(),
+            Self::Standard(printer) => printer,
+            Self::Summary(printer) => printer,
+            Self::Json(printer) => printer,
+            Self::Format(printer) => printer,
+            Self::Flat(printer) => printer,
         }
     }
 
-    fn to_json_string(&self, format: Format) -> Result<String> {
-        match format {
-            // YAML
-            #[cfg(feature = "yaml")]
-            Format::Yaml => {
-                let text = self.to_str().map_err(|_| {
-                    anyhow::anyhow!("YAML input is not valid UTF-8")
-                })?;
-                let value: serde_json::Value =
-                    serde_yaml::from_str(text).context("parse YAML input")?;
-                serde_json::to_string(&value).context("serialize YAML as JSON")
-            }
-            #[cfg(not(feature = "yaml"))]
-            Format::Yaml => {
-                anyhow::bail!(
-                    "YAML support not enabled. Rebuild with --features yaml"
-                )
-            }
+    const fn writer(&mut self) -> &mut W {
+        match self {
+            Self::Standard(printer) => printer.get_mut(),
+            Self::Summary(printer) => printer.get_mut(),
+            Self::Json(printer) => printer.get_mut(),
+            Self::Format(printer) => printer.get_mut(),
+            Self::Flat(printer) => printer.get_mut(),
+        }
+    }
+}
 
-            // TOML
-            #[cfg(feature = "toml")]
-            Format::Toml => {
-                let text = self.to_str().map_err(|_| {
-                    anyhow::anyhow!("TOML input is not valid UTF-8")
-                })?;
-                let value: serde_json::Value =
-                    toml::from_str(text).context("parse TOML input")?;
-                serde_json::to_string(&value).context("serialize TOML as JSON")
-            }
-            #[cfg(not(feature = "toml"))]
-            Format::Toml => {
-                anyhow::bail!(
-                    "TOML support not enabled. Rebuild with --features toml"
-                )
-            }
+/// Reports search results to a [`Printer`], recording whether anything
+/// matched and reporting files that fail without stopping the search.
+struct Output<W: Write> {
+    printer: Printer<W>,
+    /// Whether any input had a match.
+    matched: bool,
+}
 
-            // CBOR
-            #[cfg(feature = "cbor")]
-            Format::Cbor => {
-                let value: serde_json::Value =
-                    ciborium::from_reader(self.to_bytes())
-                        .context("parse CBOR input")?;
-                serde_json::to_string(&value).context("serialize CBOR as JSON")
-            }
-            #[cfg(not(feature = "cbor"))]
-            Format::Cbor => {
-                anyhow::bail!(
-                    "CBOR support not enabled. Rebuild with --features cbor"
-                )
-            }
+impl<W: Write> Sink for Output<W> {
+    fn begin_file(&mut self, name: &str, format: &str) -> Result<()> {
+        self.printer.sink().begin_file(name, format)
+    }
 
-            // MESSAGEPACK
-            #[cfg(feature = "msgpack")]
-            Format::Msgpack => {
-                let value: serde_json::Value =
-                    rmp_serde::from_slice(self.to_bytes())
-                        .context("parse MessagePack input")?;
-                serde_json::to_string(&value)
-                    .context("serialize MessagePack as JSON")
-            }
-            #[cfg(not(feature = "msgpack"))]
-            Format::Msgpack => {
-                anyhow::bail!(
-                    "MessagePack support not enabled. Rebuild with --features msgpack"
-                )
-            }
+    fn wants_paths(&self) -> bool {
+        match &self.printer {
+            Printer::Standard(printer) => printer.wants_paths(),
+            Printer::Summary(printer) => printer.wants_paths(),
+            Printer::Json(printer) => printer.wants_paths(),
+            Printer::Format(printer) => printer.wants_paths(),
+            Printer::Flat(printer) => printer.wants_paths(),
+        }
+    }
 
-            // Unreachable, someone made an oopsie
-            // (JSONL is parsed per line in `parse_jsonl`, borrowing from the
-            // input buffer, so it never goes through this owned-string path.)
-            Format::Auto | Format::Json | Format::Jsonl => {
-                unreachable!(
-                    "to_json_string called with Auto, Json, or Jsonl, not needed"
-                )
-            }
+    fn matched(
+        &mut self,
+        name: &str,
+        path: &[PathType],
+        value: &Value,
+    ) -> Result<()> {
+        self.printer.sink().matched(name, path, value)
+    }
+
+    fn end_file(&mut self, name: &str, count: usize) -> Result<()> {
+        if count > 0 {
+            self.matched = true;
         }
+        self.printer.sink().end_file(name, count)
     }
-}
 
-/// Whether any error in the chain is a broken pipe (the downstream consumer
-/// of stdout has gone away), which is a signal to stop printing, not an
-/// input-file failure.
-fn is_broken_pipe(err: &anyhow::Error) -> bool {
-    err.chain().any(|cause| {
-        cause
-            .downcast_ref::<io::Error>()
-            .is_some_and(|io_err| io_err.kind() == ErrorKind::BrokenPipe)
-    })
-}
+    fn file_error(&mut self, name: &str, err: anyhow::Error) -> Result<()> {
+        // Keep going, grep-style; attribute the failure. Flush pending
+        // matches first so stdout/stderr interleave in file order.
+        self.printer.writer().flush().ok();
+        eprintln!("jg: {name}: {err:#}");
+        Ok(())
+    }
 
-/// Parse input content, from the input path buffer if provided, else try STDIN.
-///
-/// # Errors
-///
-/// Returns early with an error if the file cannot be opened or read. If the input is not a file or
-/// piped input, prints the help message and exits with an error.
-fn parse_input_content(input: Option<PathBuf>) -> Result<Input> {
-    if let Some(path) = input {
-        let mut fd =
-            OpenOptions::new().read(true).open(&path).with_context(|| {
-                format!("Failed to open file {}", path.display())
-            })?;
+    fn summary(&mut self, summary: &SearchSummary) -> Result<()> {
+        self.printer.sink().summary(summary)
+    }
+}
 
-        // Only mmap large regular files. Non-regular files (FIFOs, process
-        // substitution like `jg q <(curl ...)`, character devices) cannot be
-        // mapped, and small files gain nothing from mapping. If mapping
-        // fails anyway, fall back to a plain read instead of erroring.
-        let metadata = fd.metadata().ok();
-        let is_large_regular_file = metadata
-            .as_ref()
-            .is_some_and(|m| m.is_file() && m.len() >= MMAP_MIN_FILE_SIZE);
-
-        if is_large_regular_file {
-            // SAFETY:
-            // mmap is unsafe if the backing file is modified, either by
-            // ourselves or by other processes. We will never modify the
-            // file, and if other processes do, there is not much we can do
-            // about it.
-            if let Ok(map) = unsafe { MmapOptions::new().map(&fd) } {
-                return Ok(Input::File(map));
-            }
-        }
+/// Compiles `raw` as given on the command line, honoring `-F` and `-i`.
+fn compile_query(raw: &str, args: &Args) -> Result<QueryDFA> {
+    let query: Query = if args.fixed_string {
+        // `-F`/`--fixed-string:` treat the query as a literal field name
+        // and search at any depth, equivalent to `(* | [*])*."<literal>"`
+        Query::recursive_depth_fixed_string(raw.to_string())
+    } else {
+        raw.parse().with_context(|| "Failed to parse query")?
+    };
 
-        // Capacity hint capped at the mmap threshold: only files below it
-        // (or rare mmap fallbacks) reach this path, and a stale/huge stat
-        // length must not trigger a giant allocation.
-        let capacity_hint = metadata
-            .map_or(0, |m| m.len().min(MMAP_MIN_FILE_SIZE))
-            .try_into()
-            .unwrap_or(0);
-        let mut buffer = Vec::with_capacity(capacity_hint);
-        fd.read_to_end(&mut buffer).with_context(|| {
-            format!("Failed to read file {}", path.display())
-        })?;
-        Ok(Input::Buffer(buffer))
+    Ok(if args.ignore_case {
+        QueryDFA::from_query_bounded_ignore_case(&query, DEFAULT_MAX_DFA_STATES)
     } else {
-        if io::stdin().is_terminal() {
-            // No piped input and no file specified
-            let mut cmd = Args::command();
-            cmd.print_help()?;
-            anyhow::bail!("No input specified");
-        }
-        // Read raw bytes: binary formats (CBOR, MessagePack) are valid
-        // stdin inputs; UTF-8 is only required (and validated) for text
-        // formats.
-        let mut buffer = Vec::new();
-        io::stdin().read_to_end(&mut buffer)?;
-        Ok(Input::Buffer(buffer))
+        QueryDFA::from_query_bounded(&query, DEFAULT_MAX_DFA_STATES)
+    }?)
+}
+
+/// Reads the single input of an edit or `--prune` (a file, or STDIN) and
+/// picks the parser for it.
+fn read_single_source<'a>(
+    args: &Args,
+    registry: &'a FormatRegistry,
+    explicit_format: Option<&'a dyn FormatParser>,
+) -> Result<(Input, &'a dyn FormatParser)> {
+    read_source(single_input(args)?, registry, explicit_format)
+}
+
+/// The single input of an edit, `--prune`, or `--unflatten`: a file, or
+/// STDIN if `None`.
+fn single_input(args: &Args) -> Result<Option<&Path>> {
+    if args.inputs.len() > 1 {
+        anyhow::bail!(
+            "--set, --delete, --redact, --prune, and --unflatten take a \
+             single input, but {} were given",
+            args.inputs.len()
+        );
     }
+    Ok(args.inputs.first().map(PathBuf::as_path))
 }
 
-/// Supported input formats beyond JSON.
-#[derive(Debug, Default, Clone, Copy, clap::ValueEnum)]
-enum Format {
-    #[default]
-    Auto,
-    Json,
-    Jsonl,
-    Yaml,
-    Toml,
-    Cbor,
-    Msgpack,
+/// Reads and parses `path`, or STDIN if `None`, into an owned document.
+fn read_input(
+    path: Option<&Path>,
+    registry: &FormatRegistry,
+    explicit_format: Option<&dyn FormatParser>,
+) -> Result<serde_json::Value> {
+    let (input, parser) = read_source(path, registry, explicit_format)?;
+    parser.parse(input.as_bytes())
 }
 
-impl std::fmt::Display for Format {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Auto => write!(f, "Auto"),
-            Self::Json => write!(f, "JSON"),
-            Self::Jsonl => write!(f, "JSONL"),
-            Self::Yaml => write!(f, "YAML"),
-            Self::Toml => write!(f, "TOML"),
-            Self::Cbor => write!(f, "CBOR"),
-            Self::Msgpack => write!(f, "MessagePack"),
+/// Reads `path`, or STDIN if `None`, and picks the parser for it.
+fn read_source<'a>(
+    path: Option<&Path>,
+    registry: &'a FormatRegistry,
+    explicit_format: Option<&'a dyn FormatParser>,
+) -> Result<(Input, &'a dyn FormatParser)> {
+    let input = if let Some(path) = path {
+        Input::from_path(path)?
+    } else {
+        check_stdin()?;
+        Input::from_reader(io::stdin().lock())
+            .with_context(|| format!("Failed to read {STDIN_NAME}"))?
+    };
+    let parser = explicit_format
+        .unwrap_or_else(|| registry.resolve(path, input.as_bytes()));
+    Ok((input, parser))
+}
+
+/// Prints a whole document in `format`, pretty unless `compact`; JSON is
+/// colored like matches are.
+fn write_document<W: Write>(
+    writer: &mut W,
+    doc: &serde_json::Value,
+    compact: bool,
+    format: Format,
+) -> Result<()> {
+    if format == Format::Json {
+        write_colored_result(
+            writer,
+            &Value::from(doc),
+            &[],
+            &WriteOptions { pretty: !compact, ..Default::default() },
+        )?;
+    } else {
+        match DocumentWriter::new(&mut *writer, format)
+            .pretty(!compact)
+            .write_document(doc)
+        {
+            Err(err) if is_broken_pipe(&err) => return Ok(()),
+            result => result?,
         }
     }
+    flush_output(writer)
 }
 
-fn detect_format(path: Option<&PathBuf>, explicit: Format) -> Format {
-    // Use explicit if user overrode the default.
-    if !matches!(explicit, Format::Auto) {
-        return explicit;
+/// Applies the `--set`, then the `--delete`, then the `--redact` edits to
+/// the single input, each in order, and prints the edited document as JSON,
+/// or JSONL for a JSONL input (with `--patch`, the edits as a JSON Patch;
+/// with `--preserve-format`, the edited input in its own format). Returns
+/// whether any query matched.
+fn run_edits<W: Write>(
+    args: &Args,
+    registry: &FormatRegistry,
+    explicit_format: Option<&dyn FormatParser>,
+    writer: &mut W,
+) -> Result<bool> {
+    let redactor = args.redact_key.as_ref().map_or_else(
+        || Redactor::Placeholder(args.redact_placeholder.clone()),
+        |key| Redactor::Hash(key.as_bytes().to_vec()),
+    );
+    let redactions = args
+        .redact
+        .iter()
+        .map(|query| compile_query(query, args))
+        .collect::<Result<Vec<_>>>()?;
+
+    // Redacting JSONL only ever needs one record at a time, so stream it
+    // (when the output is JSONL too).
+    let path = single_input(args)?;
+    let jsonl = explicit_format
+        .or_else(|| path.and_then(|path| registry.for_path(path)))
+        .is_some_and(|parser| parser.name() == "jsonl");
+    if jsonl
+        && matches!(args.output_format, Format::Json | Format::Jsonl)
+        && args.set.is_empty()
+        && args.delete.is_empty()
+        && !args.patch
+        && !args.preserve_format
+    {
+        return redact_jsonl(path, &redactions, &redactor, writer);
     }
-    let Some(path) = path else {
-        // NOTE: we don't support streaming type inference, maybe someday
-        return Format::Json;
-    };
 
-    match path.extension().and_then(|e| e.to_str()) {
-        Some("ndjson" | "jsonl") => Format::Jsonl,
-        Some("yaml" | "yml") => Format::Yaml,
-        Some("msgpack" | "mp") => Format::Msgpack,
-        Some("toml") => Format::Toml,
-        Some("cbor") => Format::Cbor,
-        _ => Format::Json,
+    let (input, parser) = read_single_source(args, registry, explicit_format)?;
+    let mut doc = parser.parse(input.as_bytes())?;
+
+    // Later edits are planned against the document as edited so far, so the
+    // operations stay valid when replayed in order.
+    let mut applied = Vec::new();
+    for edit in args.set.chunks_exact(2) {
+        let dfa = compile_query(&edit[0], args)?;
+        let template = Template::parse(&edit[1])?;
+        let ops = edit::plan_set(&doc, &dfa, &template);
+        edit::apply(&mut doc, &ops)?;
+        applied.extend(ops);
+    }
+    for query in &args.delete {
+        let ops = edit::plan_delete(&doc, &compile_query(query, args)?);
+        edit::apply(&mut doc, &ops)?;
+        applied.extend(ops);
+    }
+    for dfa in &redactions {
+        let ops = redact::plan_redact(&doc, dfa, &redactor);
+        edit::apply(&mut doc, &ops)?;
+        applied.extend(ops);
+    }
+
+    if args.patch {
+        write_document(
+            writer,
+            &patch::to_patch(&applied),
+            args.compact,
+            args.output_format,
+        )?;
+    } else if args.preserve_format {
+        let text = std::str::from_utf8(input.as_bytes())
+            .context("Input is not valid UTF-8")?;
+        let edited = edit::source::apply(parser.name(), text, &applied)?;
+        writer.write_all(edited.as_bytes())?;
+        flush_output(writer)?;
+    } else {
+        // JSONL stays one record per line, as when redacting it.
+        let format = match args.output_format {
+            Format::Json if jsonl => Format::Jsonl,
+            format => format,
+        };
+        write_document(writer, &doc, args.compact, format)?;
     }
+    Ok(!applied.is_empty())
 }
 
-/// Parse JSONL/NDJSON input line by line into a single top-level array,
-/// borrowing each record directly from the input buffer.
-///
-/// Compared to concatenating all lines into a synthetic `[...]` JSON string
-/// and re-parsing it, this avoids a second full-input-sized allocation and
-/// reports parse errors with the actual line number of the offending record.
-fn parse_jsonl(text: &str) -> Result<Value<'_>> {
-    let mut records = Vec::new();
-    for (idx, line) in text.lines().enumerate() {
+/// Redacts the JSONL records of `path`, or STDIN if `None`, as they are
+/// read, printing each one on its own line. Returns whether any query
+/// matched.
+fn redact_jsonl<W: Write>(
+    path: Option<&Path>,
+    queries: &[QueryDFA],
+    redactor: &Redactor,
+    writer: &mut W,
+) -> Result<bool> {
+    let reader: Box<dyn BufRead> = if let Some(path) = path {
+        Box::new(BufReader::new(File::open(path).with_context(|| {
+            format!("Failed to open file {}", path.display())
+        })?))
+    } else {
+        check_stdin()?;
+        Box::new(io::stdin().lock())
+    };
+
+    let mut matched = false;
+    // Records are numbered as `parse_jsonl` numbers them: blank lines are
+    // skipped, so `[2]` is the third record whatever lines surround it.
+    let mut index = 0;
+    for (line_idx, line) in reader.lines().enumerate() {
+        let line = line.with_context(|| {
+            format!("Failed to read JSONL line {}", line_idx + 1)
+        })?;
         let line = line.trim();
         if line.is_empty() {
             continue;
         }
-        let record: Value = serde_json::from_str(line).with_context(|| {
-            format!("Failed to parse JSONL line {}", idx + 1)
-        })?;
-        records.push(record);
-    }
-    Ok(Value::Array(records))
-}
-
-/// Parses the input and invokes `f` with a borrowed [`Value`] to preserve zero-copy path for
-/// JSON/Auto and JSONL `Format`s.
-fn with_json<F, T>(input: Option<PathBuf>, format: Format, f: F) -> Result<T>
-where
-    F: FnOnce(&Value) -> Result<T>,
-{
-    let input_content = parse_input_content(input)?;
-
-    // For JSON/Auto we borrow directly from the mmap/stdin buffer,
-    // preserving the zero-copy path that serde_json_borrow provides. JSONL
-    // is parsed per line, likewise borrowing from the input buffer. For
-    // other formats, we convert to an owned JSON string first and then
-    // borrow from that.
-    match format {
-        Format::Json | Format::Auto => {
-            let json_str =
-                input_content.to_str().context("Input is not valid UTF-8")?;
-            let json: Value = serde_json::from_str(json_str)
-                .with_context(|| format!("Failed to parse as {format}"))?;
-            f(&json)
-        }
-        Format::Jsonl => {
-            let text = input_content.to_str().map_err(|_| {
-                anyhow::anyhow!("JSONL input is not valid UTF-8")
+        let mut record: serde_json::Value = serde_json::from_str(line)
+            .with_context(|| {
+                format!("Failed to parse JSONL line {}", line_idx + 1)
             })?;
-            let json = parse_jsonl(text)?;
-            f(&json)
+        for dfa in queries {
+            let ops = redact::plan_redact_record(&record, index, dfa, redactor);
+            edit::apply(&mut record, &ops)?;
+            matched |= !ops.is_empty();
+        }
+        index += 1;
+
+        let written = write_colored_result(
+            writer,
+            &Value::from(&record),
+            &[],
+            &WriteOptions { pretty: false, ..Default::default() },
+        )?;
+        if !written {
+            // Broken pipe: nobody is reading any more.
+            return Ok(matched);
         }
-        other => {
-            let json_string_owned = input_content.to_json_string(other)?;
-            let json: Value = serde_json::from_str(&json_string_owned)
-                .with_context(|| format!("Failed to parse as {format}"))?;
-            f(&json)
+        // Piped records are passed on as they arrive, as in `tail -f |
+        // jg --redact ...`.
+        if path.is_none() {
+            flush_output(writer)?;
         }
     }
+    flush_output(writer)?;
+    Ok(matched)
+}
+
+/// Flushes `writer`, ignoring a broken pipe (the consumer has gone away, so
+/// there is nobody left to read the output).
+fn flush_output<W: Write>(writer: &mut W) -> Result<()> {
+    match writer.flush() {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == ErrorKind::BrokenPipe => Ok(()),
+        Err(err) => Err(err.into()),
+    }
 }
 
 /// Entry point for main binary.
@@ -472,7 +843,7 @@ fn run(mut args: Args) -> Result<bool> {
         args.compact = true;
     }
 
-    match args.command {
+    match args.command.take() {
         Some(Commands::Generate(cmd)) => match cmd {
             GenerateCommand::Shell { shell } => {
                 let mut cmd = Args::command();
@@ -485,6 +856,61 @@ fn run(mut args: Args) -> Result<bool> {
                 )?;
             }
         },
+        Some(Commands::Patch(PatchCommand::Apply {
+            patch: patch_path,
+            input,
+            format,
+        })) => {
+            let registry = format_registry();
+            let json_patch = read_input(Some(&patch_path), &registry, None)?;
+            let mut doc =
+                read_input(input.as_deref(), &registry, registry.get(&format))?;
+            patch::apply_patch(&mut doc, &json_patch)?;
+            let mut writer = BufWriter::new(stdout().lock());
+            write_document(&mut writer, &doc, args.compact, Format::Json)?;
+            matched = true;
+        }
+        Some(Commands::Convert { input, format, output_format }) => {
+            check_output(output_format)?;
+            let registry = format_registry();
+            let doc =
+                read_input(input.as_deref(), &registry, registry.get(&format))?;
+            let mut writer = BufWriter::new(stdout().lock());
+            write_document(&mut writer, &doc, args.compact, output_format)?;
+            matched = true;
+        }
+        Some(Commands::Diff { old, new, query, format, json, patch }) => {
+            let registry = format_registry();
+            let explicit_format = registry.get(&format);
+            let old = read_input(Some(&old), &registry, explicit_format)?;
+            let new = read_input(Some(&new), &registry, explicit_format)?;
+            let changes = match query {
+                Some(query) => diff::diff_matches(
+                    &old,
+                    &new,
+                    &compile_query(&query, &args)?,
+                ),
+                None => diff::diff(&old, &new),
+            };
+
+            let mut writer = BufWriter::new(stdout().lock());
+            if json || patch {
+                let doc = if patch {
+                    diff::to_patch(&changes)
+                } else {
+                    diff::to_json(&changes)
+                };
+                write_document(&mut writer, &doc, args.compact, Format::Json)?;
+            } else {
+                match diff::write_changes(&mut writer, &changes) {
+                    Err(err) if err.kind() == ErrorKind::BrokenPipe => {}
+                    result => result?,
+                }
+                flush_output(&mut writer)?;
+            }
+            // Like diff(1): success means no differences.
+            matched = changes.is_empty();
+        }
         None => {
             // NOTE: use single, locked stdout handle to avoid interleaving
             let stdout = stdout().lock();
@@ -498,48 +924,86 @@ fn run(mut args: Args) -> Result<bool> {
                 stdout.is_terminal()
             };
             let mut writer = BufWriter::new(stdout);
+            check_output(args.output_format)?;
 
-            // `--depth` takes only files, no query string. Clap parses the
-            // first positional into `query`; move it into `inputs`.
-            if args.depth
+            // An explicit `--format` (validated by clap) overrides detection.
+            let registry = format_registry();
+            let explicit_format = registry.get(&args.format);
+
+            // `--depth`, edits, and flat documents take only files, no
+            // query string. Clap parses the first positional into `query`;
+            // move it into `inputs`.
+            let edits = !args.set.is_empty()
+                || !args.delete.is_empty()
+                || !args.redact.is_empty();
+            if (args.depth || edits || args.flatten || args.unflatten)
                 && let Some(query) = args.query.take()
             {
                 args.inputs.insert(0, PathBuf::from(query));
             }
 
+            if args.unflatten {
+                let (input, _) =
+                    read_source(single_input(&args)?, &registry, None)?;
+                let text = std::str::from_utf8(input.as_bytes())
+                    .context("Input is not valid UTF-8")?;
+                let doc = flatten::unflatten(text)?;
+                write_document(
+                    &mut writer,
+                    &doc,
+                    args.compact,
+                    args.output_format,
+                )?;
+                return Ok(true);
+            }
+
+            if edits {
+                return run_edits(
+                    &args,
+                    &registry,
+                    explicit_format,
+                    &mut writer,
+                );
+            }
+
             // short circuit to only perform the depth computation
             if args.depth && !args.inputs.is_empty() {
                 let multi = args.inputs.len() > 1;
                 let mut failed_inputs = 0usize;
-                for path in args.inputs {
-                    let format = detect_format(Some(&path), args.format);
+                for path in &args.inputs {
                     let name = path.display().to_string();
-                    let file_result = with_json(Some(path), format, |json| {
-                        if multi {
-                            // Attribute per file, grep -c style.
-                            let styled_name = if args.porcelain {
-                                name.normal()
-                            } else {
-                                name.bold().magenta()
-                            };
-                            writeln!(
-                                writer,
-                                "{}:{}",
-                                styled_name,
-                                depth(json)
-                            )?;
-                        } else if args.porcelain {
-                            writeln!(writer, "{}", depth(json))?;
-                        } else {
-                            writeln!(
-                                writer,
-                                "{} {}",
-                                "Depth:".bold().blue(),
-                                depth(json)
-                            )?;
-                        }
-                        Ok(())
-                    });
+                    let file_result =
+                        Input::from_path(path).and_then(|input| {
+                            let parser = explicit_format.unwrap_or_else(|| {
+                                registry.resolve(Some(path), input.as_bytes())
+                            });
+                            input.with_json_as(parser, |json| {
+                                if multi {
+                                    // Attribute per file, grep -c style.
+                                    let styled_name = if args.porcelain {
+                                        name.normal()
+                                    } else {
+                                        name.bold().magenta()
+                                    };
+                                    writeln!(
+                                        writer,
+                                        "{}:{}",
+                                        styled_name,
+                                        depth(json)
+                                    )?;
+                                } else if args.porcelain {
+                                    writeln!(writer, "{}", depth(json))?;
+                                } else {
+                                    writeln!(
+                                        writer,
+                                        "{} {}",
+                                        "Depth:".bold().blue(),
+                                        depth(json)
+                                    )?;
+                                }
+                                Ok(())
+                            })
+                        });
                     if let Err(err) = file_result {
                         if multi && !is_broken_pipe(&err) {
                             writer.flush().ok();
@@ -553,12 +1017,8 @@ fn run(mut args: Args) -> Result<bool> {
                     }
                 }
 
+                flush_output(&mut writer)?;
                 if failed_inputs > 0 {
-                    match writer.flush() {
-                        Ok(()) => {}
-                        Err(err) if err.kind() == ErrorKind::BrokenPipe => {}
-                        Err(err) => return Err(err.into()),
-                    }
                     anyhow::bail!(
                         "{failed_inputs} input file(s) could not be processed"
                     );
@@ -566,179 +1026,147 @@ fn run(mut args: Args) -> Result<bool> {
                 return Ok(true);
             }
 
-            let raw_query = args.query.ok_or_else(|| {
-                anyhow::anyhow!("Query string required unless using subcommand")
-            })?;
-
-            let query: Query = if args.fixed_string {
-                // `-F`/`--fixed-string:` treat the query as a literal field name
-                // and search at any depth, equivalent to `(* | [*])*."<literal>"`
-                Query::recursive_depth_fixed_string(raw_query)
-            } else {
-                raw_query.parse().with_context(|| "Failed to parse query")?
-            };
-
             // Compile the DFA once; run it against every input.
-            let dfa = if args.ignore_case {
-                QueryDFA::from_query_bounded_ignore_case(
-                    &query,
-                    DEFAULT_MAX_DFA_STATES,
-                )
+            let dfa = if args.flatten {
+                QueryDFA::from_query_str(flatten::LEAVES_QUERY)?
             } else {
-                QueryDFA::from_query_bounded(&query, DEFAULT_MAX_DFA_STATES)
-            }?;
+                let raw_query = args.query.as_deref().ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Query string required unless using subcommand"
+                    )
+                })?;
+                compile_query(raw_query, &args)?
+            };
 
-            if args.count || args.depth {
-                args.no_display = true;
+            if args.prune {
+                let (input, parser) =
+                    read_single_source(&args, &registry, explicit_format)?;
+                let doc = parser.parse(input.as_bytes())?;
+                let Some(pruned) = edit::prune(&doc, &dfa) else {
+                    return Ok(false);
+                };
+                write_document(
+                    &mut writer,
+                    &pruned,
+                    args.compact,
+                    args.output_format,
+                )?;
+                return Ok(true);
             }
 
             let multi = args.inputs.len() > 1;
-            let inputs: Vec<Option<PathBuf>> = if args.inputs.is_empty() {
-                vec![None]
+            let summary = if args.count {
+                Some(SummaryKind::Count)
+            } else if args.files_with_matches {
+                Some(SummaryKind::FilesWithMatches)
+            } else if args.quiet || args.no_display {
+                Some(SummaryKind::Quiet)
             } else {
-                args.inputs.into_iter().map(Some).collect()
+                None
             };
+            // `--count`, `-l`, `-q`, and `--no-display` only need the number
+            // of matches (or whether there is one), so they skip building
+            // match paths and values entirely.
+            let (mode, printer) = match summary {
+                _ if args.json => (
+                    SearchMode::Matches,
+                    Printer::Json(JsonPrinter::new(writer)),
+                ),
+                _ if args.flatten => (
+                    SearchMode::Matches,
+                    Printer::Flat(
+                        FlatPrinter::new(writer).with_filename(multi),
+                    ),
+                ),
+                None if args.output_format != Format::Json => (
+                    SearchMode::Matches,
+                    Printer::Format(FormatPrinter::new(
+                        DocumentWriter::new(writer, args.output_format)
+                            .pretty(!args.compact),
+                    )),
+                ),
+                Some(kind) => (
+                    kind.search_mode(),
+                    Printer::Summary(
+                        SummaryPrinter::new(writer, kind)
+                            .with_filename(multi)
+                            .label(!args.porcelain),
+                    ),
+                ),
+                None => (
+                    SearchMode::Matches,
+                    Printer::Standard(
+                        StandardPrinter::new(
+                            writer,
+                            WriteOptions {
+                                pretty: !args.compact,
+                                show_path,
+                                raw: args.raw_output,
+                                path_format: args.path_format,
+                            },
+                        )
+                        .heading(multi),
+                    ),
+                ),
+            };
+            let mut searcher = Searcher::new(&dfa)
+                .registry(&registry)
+                .mode(mode)
+                .max_count(args.max_count)
+                .max_depth(args.max_depth);
+            #[cfg(feature = "rayon")]
+            {
+                searcher = searcher.parallel(args.parallel);
+            }
+            if let Some(parser) = explicit_format {
+                searcher = searcher.parser(parser);
+            }
 
-            // Errors in one file must not prevent searching the rest
-            // (grep semantics); remember and report at the end.
-            let mut failed_inputs = 0usize;
-            let mut printed_block = false;
-
-            for input in inputs {
-                let format = detect_format(input.as_ref(), args.format);
-                let name = input.as_ref().map_or_else(
-                    || "(standard input)".to_string(),
-                    |p| p.display().to_string(),
-                );
-
-                let file_result = with_json(input, format, |json| {
-                    let results = dfa.find(json);
-                    if !results.is_empty() {
-                        matched = true;
-                    }
-
-                    if args.quiet {
-                        return Ok(());
-                    }
-
-                    if args.files_with_matches {
-                        if !results.is_empty() {
-                            writeln!(writer, "{name}")?;
-                        }
-                        return Ok(());
-                    }
-
-                    if args.count {
-                        if multi {
-                            // grep -c style per-file attribution.
-                            let styled_name = if args.porcelain {
-                                name.normal()
-                            } else {
-                                name.bold().magenta()
-                            };
-                            writeln!(
-                                writer,
-                                "{}:{}",
-                                styled_name,
-                                results.len()
-                            )?;
-                        } else if args.porcelain {
-                            writeln!(writer, "{}", results.len())?;
-                        } else {
-                            writeln!(
-                                writer,
-                                "{} {}",
-                                "Found matches:".bold().blue(),
-                                results.len()
-                            )
-                            .with_context(|| "Failed to write to stdout")?;
-                        }
-                    }
-
-                    if args.depth {
-                        if args.porcelain {
-                            writeln!(writer, "{}", depth(json))?;
-                        } else {
-                            writeln!(
-                                writer,
-                                "{} {}",
-                                "Depth:".bold().blue(),
-                                depth(json)
-                            )?;
-                        }
-                    }
-
-                    if !args.no_display && !results.is_empty() {
-                        // ripgrep-style headings: with several inputs, name
-                        // the file once before its matches, with a blank
-                        // line between file blocks.
-                        if multi {
-                            if printed_block {
-                                writeln!(writer)?;
-                            }
-                            let styled_name = if args.porcelain {
-                                name.normal()
-                            } else {
-                                name.bold().green()
-                            };
-                            writeln!(writer, "{styled_name}")?;
-                        }
-                        printed_block = true;
-
-                        let pretty = !args.compact;
-                        for result in &results {
-                            write_colored_result(
-                                &mut writer,
-                                result.value,
-                                &result.path,
-                                &WriteOptions {
-                                    pretty,
-                                    show_path,
-                                    raw: args.raw_output,
-                                },
-                            )?;
-                        }
-                    }
+            let mut output = Output { printer, matched: false };
 
-                    Ok(())
-                });
-
-                if let Err(err) = file_result {
-                    if multi && !is_broken_pipe(&err) {
-                        // Keep going, grep-style; attribute the failure.
-                        // Flush pending matches first so stdout/stderr
-                        // interleave in file order.
-                        writer.flush().ok();
-                        eprintln!("jg: {name}: {err:#}");
-                        failed_inputs += 1;
-                    } else if multi {
-                        // The output pipe is gone: nothing more can be
-                        // printed, so stop quietly (same as single-input
-                        // broken-pipe handling).
-                        break;
-                    } else {
-                        return Err(err);
+            if multi {
+                // Errors in one file must not prevent searching the rest
+                // (grep semantics); `Output::file_error` reports each one
+                // and the total is reported at the end.
+                match searcher.search_paths(&args.inputs, &mut output) {
+                    Ok(summary) if summary.failed > 0 => {
+                        // Flush what we printed before reporting the
+                        // failure.
+                        flush_output(output.printer.writer())?;
+                        anyhow::bail!(
+                            "{} input file(s) could not be processed",
+                            summary.failed
+                        );
                     }
+                    Ok(_) => {}
+                    // The output pipe is gone: nothing more can be printed,
+                    // so stop quietly (same as single-input broken-pipe
+                    // handling).
+                    Err(err) if is_broken_pipe(&err) => {}
+                    Err(err) => return Err(err),
                 }
+            } else {
+                let count = if let Some(path) = args.inputs.first() {
+                    searcher.search_path(path, &mut output)?
+                } else {
+                    check_stdin()?;
+                    searcher.search_reader(
+                        STDIN_NAME,
+                        io::stdin().lock(),
+                        &mut output,
+                    )?
+                };
+                // A single input closes with the same summary as several.
+                output.summary(&SearchSummary {
+                    searched: 1,
+                    matched: usize::from(count > 0),
+                    matches: count,
+                    failed: 0,
+                })?;
             }
 
-            if failed_inputs > 0 {
-                // Flush what we printed before reporting the failure.
-                match writer.flush() {
-                    Ok(()) => {}
-                    Err(err) if err.kind() == ErrorKind::BrokenPipe => {}
-                    Err(err) => return Err(err.into()),
-                }
-                anyhow::bail!(
-                    "{failed_inputs} input file(s) could not be processed"
-                );
-            }
-
-            match writer.flush() {
-                Ok(()) => {}
-                Err(err) if err.kind() == ErrorKind::BrokenPipe => {}
-                Err(err) => return Err(err.into()),
-            }
+            flush_output(output.printer.writer())?;
+            matched = output.matched;
         }
     }
 
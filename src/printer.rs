@@ -0,0 +1,639 @@
+/*!
+# Printers
+
+[`Sink`]s that write search results, in the spirit of ripgrep's
+`grep-printer` crate:
+
+- [`StandardPrinter`]: the human-readable output of `jg`, i.e. matched
+  values with optional path headers, and file headings when searching
+  several inputs.
+- [`SummaryPrinter`]: one line per input instead of its matches: the
+  number of matches (`jg --count`), the names of matching inputs
+  (`jg -l`), or nothing at all (`jg -q`).
+- [`JsonPrinter`]: one JSON object per line for each search event, for
+  other programs to consume.
+- [`FormatPrinter`]: each matched value as a document in another format,
+  such as YAML (`jg --output-format`).
+- [`FlatPrinter`]: a `path = value` line per matched leaf, searched with
+  [`LEAVES_QUERY`] to flatten whole documents (`jg --flatten`).
+
+Printers write to any [`Write`] implementation and are handed to a
+[`Searcher`] like any other sink. Colors follow the [`colored`] crate's
+global settings.
+
+# Examples
+
+```
+use jsongrep::{
+    printer::JsonPrinter, query::QueryDFA, search::Searcher,
+};
+
+let dfa = QueryDFA::from_query_str("users[*].name").unwrap();
+let input = br#"{"users": [{"name": "Alice"}]}"#;
+
+let mut printer = JsonPrinter::new(Vec::new());
+Searcher::new(&dfa)
+    .search_slice("users.json", input, &mut printer)
+    .unwrap();
+
+let output = String::from_utf8(printer.into_inner()).unwrap();
+let lines: Vec<_> = output.lines().collect();
+assert_eq!(
+    lines[..2],
+    [
+        r#"{"type":"begin","data":{"file":"users.json","format":"json"}}"#,
+        r#"{"type":"match","data":{"file":"users.json","path":["users",0,"name"],"value":"Alice"}}"#,
+    ]
+);
+assert!(lines[2].starts_with(
+    r#"{"type":"end","data":{"file":"users.json","matches":1,"elapsed":"#
+));
+```
+
+[`Searcher`]: crate::search::Searcher
+[`LEAVES_QUERY`]: crate::flatten::LEAVES_QUERY
+*/
+use anyhow::Result;
+use colored::Colorize;
+use serde::{Serialize, Serializer};
+use serde_json_borrow::Value;
+use std::{
+    io::Write,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    flatten::is_leaf,
+    format::DocumentWriter,
+    query::PathType,
+    search::{SearchMode, SearchSummary, Sink, is_broken_pipe},
+    utils::{PathFormat, WriteOptions, format_path, write_colored_result},
+};
+
+/// Prints matched values the way `jg` does by default.
+///
+/// Each match is written with [`write_colored_result`]. With
+/// [`StandardPrinter::heading`] set, the matches of each input are preceded
+/// by its name, and blocks of different inputs are separated by a blank
+/// line (like ripgrep's `--heading`).
+#[derive(Debug)]
+pub struct StandardPrinter<W> {
+    writer: W,
+    options: WriteOptions,
+    heading: bool,
+    /// Whether any input's block has been printed, to separate the next.
+    printed_block: bool,
+    /// Whether the current input's heading has been printed.
+    printed_heading: bool,
+}
+
+impl<W: Write> StandardPrinter<W> {
+    /// A printer writing matches to `writer` formatted with `options`,
+    /// without file headings.
+    #[must_use]
+    pub const fn new(writer: W, options: WriteOptions) -> Self {
+        Self {
+            writer,
+            options,
+            heading: false,
+            printed_block: false,
+            printed_heading: false,
+        }
+    }
+
+    /// Whether to print the name of each input above its matches.
+    #[must_use]
+    pub const fn heading(mut self, yes: bool) -> Self {
+        self.heading = yes;
+        self
+    }
+
+    /// The underlying writer.
+    pub const fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Consumes the printer, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> Sink for StandardPrinter<W> {
+    fn begin_file(&mut self, _name: &str, _format: &str) -> Result<()> {
+        self.printed_heading = false;
+        Ok(())
+    }
+
+    fn wants_paths(&self) -> bool {
+        // Paths live in a shared arena; only materialize them when the
+        // header is actually shown.
+        self.options.show_path
+    }
+
+    fn matched(
+        &mut self,
+        name: &str,
+        path: &[PathType],
+        value: &Value,
+    ) -> Result<()> {
+        if !self.printed_heading {
+            if self.heading {
+                if self.printed_block {
+                    writeln!(self.writer)?;
+                }
+                writeln!(self.writer, "{}", name.bold().green())?;
+            }
+            self.printed_block = true;
+            self.printed_heading = true;
+        }
+        write_colored_result(&mut self.writer, value, path, &self.options)?;
+        Ok(())
+    }
+}
+
+/// Prints each matched value as a document of its own with a
+/// [`DocumentWriter`], without paths or file headings.
+#[derive(Debug)]
+pub struct FormatPrinter<W> {
+    writer: DocumentWriter<W>,
+}
+
+impl<W: Write> FormatPrinter<W> {
+    /// A printer writing matches with `writer`.
+    #[must_use]
+    pub const fn new(writer: DocumentWriter<W>) -> Self {
+        Self { writer }
+    }
+
+    /// The underlying writer.
+    pub const fn get_mut(&mut self) -> &mut W {
+        self.writer.get_mut()
+    }
+
+    /// Consumes the printer, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner()
+    }
+}
+
+impl<W: Write> Sink for FormatPrinter<W> {
+    fn wants_paths(&self) -> bool {
+        false
+    }
+
+    fn matched(
+        &mut self,
+        _: &str,
+        _: &[PathType],
+        value: &Value,
+    ) -> Result<()> {
+        match self.writer.write(&serde_json::to_value(value)?) {
+            // Nobody is reading any more, as with `write_colored_result`.
+            Err(err) if is_broken_pipe(&err) => Ok(()),
+            result => result,
+        }
+    }
+}
+
+/// Prints each matched [leaf](is_leaf) as a flat `path = value` line (see
+/// [`crate::flatten`]), with the path in `jg` syntax and the value as compact
+/// JSON. Matches that are not leaves are skipped.
+#[derive(Debug)]
+pub struct FlatPrinter<W> {
+    writer: W,
+    with_filename: bool,
+}
+
+impl<W: Write> FlatPrinter<W> {
+    /// A printer writing flat lines to `writer`.
+    #[must_use]
+    pub const fn new(writer: W) -> Self {
+        Self { writer, with_filename: false }
+    }
+
+    /// Whether to prefix each line with the input name, as `name:line`
+    /// (like `grep` with several files).
+    #[must_use]
+    pub const fn with_filename(mut self, yes: bool) -> Self {
+        self.with_filename = yes;
+        self
+    }
+
+    /// The underlying writer.
+    pub const fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Consumes the printer, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> Sink for FlatPrinter<W> {
+    fn matched(
+        &mut self,
+        name: &str,
+        path: &[PathType],
+        value: &Value,
+    ) -> Result<()> {
+        if !is_leaf(value) {
+            return Ok(());
+        }
+        if self.with_filename {
+            write!(self.writer, "{}:", name.bold().magenta())?;
+        }
+        // A scalar document's only leaf has the empty path.
+        if !path.is_empty() {
+            let path = format_path(path, PathFormat::Jg);
+            write!(self.writer, "{} ", path.bold().magenta())?;
+        }
+        write!(self.writer, "= ")?;
+        write_colored_result(
+            &mut self.writer,
+            value,
+            &[],
+            &WriteOptions { pretty: false, ..Default::default() },
+        )?;
+        Ok(())
+    }
+}
+
+/// What a [`SummaryPrinter`] prints for each input.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SummaryKind {
+    /// The number of matches (`jg --count`).
+    #[default]
+    Count,
+    /// The name of the input, if it has a match (`jg -l`).
+    FilesWithMatches,
+    /// Nothing (`jg -q`).
+    Quiet,
+}
+
+impl SummaryKind {
+    /// The cheapest [`SearchMode`] that finds what this kind prints.
+    #[must_use]
+    pub const fn search_mode(self) -> SearchMode {
+        match self {
+            Self::Count => SearchMode::Count,
+            Self::FilesWithMatches | Self::Quiet => SearchMode::Any,
+        }
+    }
+}
+
+/// Prints a line per input summarizing its matches instead of the matches
+/// themselves.
+///
+/// Search with the [`SummaryKind::search_mode`] of the printer's kind to
+/// skip building matches that are never printed.
+#[derive(Debug)]
+pub struct SummaryPrinter<W> {
+    writer: W,
+    kind: SummaryKind,
+    with_filename: bool,
+    label: bool,
+}
+
+impl<W: Write> SummaryPrinter<W> {
+    /// A printer writing a `kind` summary of each input to `writer`.
+    #[must_use]
+    pub const fn new(writer: W, kind: SummaryKind) -> Self {
+        Self { writer, kind, with_filename: false, label: true }
+    }
+
+    /// Whether to prefix counts with the input name, as `name:count`
+    /// (like `grep -c` with several files).
+    #[must_use]
+    pub const fn with_filename(mut self, yes: bool) -> Self {
+        self.with_filename = yes;
+        self
+    }
+
+    /// Whether to label counts without a file name, as
+    /// `Found matches: count`, rather than printing the bare number. On by
+    /// default.
+    #[must_use]
+    pub const fn label(mut self, yes: bool) -> Self {
+        self.label = yes;
+        self
+    }
+
+    /// The underlying writer.
+    pub const fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Consumes the printer, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> Sink for SummaryPrinter<W> {
+    fn wants_paths(&self) -> bool {
+        false
+    }
+
+    fn matched(&mut self, _: &str, _: &[PathType], _: &Value) -> Result<()> {
+        Ok(())
+    }
+
+    fn end_file(&mut self, name: &str, count: usize) -> Result<()> {
+        match self.kind {
+            SummaryKind::Count if self.with_filename => {
+                writeln!(self.writer, "{}:{count}", name.bold().magenta())?;
+            }
+            SummaryKind::Count if self.label => {
+                writeln!(
+                    self.writer,
+                    "{} {count}",
+                    "Found matches:".bold().blue()
+                )?;
+            }
+            SummaryKind::Count => writeln!(self.writer, "{count}")?,
+            SummaryKind::FilesWithMatches if count > 0 => {
+                writeln!(self.writer, "{name}")?;
+            }
+            SummaryKind::FilesWithMatches | SummaryKind::Quiet => {}
+        }
+        Ok(())
+    }
+}
+
+/// Prints each search event as a JSON object on its own line (JSON Lines),
+/// like `rg --json`.
+///
+/// Every object has a `type` and a `data` member:
+///
+/// - `begin`: `{"file", "format"}`, before an input is searched.
+/// - `match`: `{"file", "path", "value"}` for each match, with the path
+///   as an array of field names (strings) and indices (numbers).
+/// - `end`: `{"file", "matches", "elapsed"}` after an input was searched.
+/// - `summary`: `{"searched", "matched", "matches", "failed", "elapsed"}`,
+///   the [`SearchSummary`] after a multi-input search.
+///
+/// `elapsed` is the time since the input's `begin` (for `end`) or since the
+/// printer was created (for `summary`), as
+/// `{"secs": 0, "nanos": 12345, "human": "0.000012s"}`.
+#[derive(Debug)]
+pub struct JsonPrinter<W> {
+    writer: W,
+    /// When the printer was created.
+    started: Instant,
+    /// When the current input was begun.
+    file_started: Instant,
+}
+
+impl<W: Write> JsonPrinter<W> {
+    /// A printer writing events to `writer`.
+    #[must_use]
+    pub fn new(writer: W) -> Self {
+        let now = Instant::now();
+        Self { writer, started: now, file_started: now }
+    }
+
+    /// The underlying writer.
+    pub const fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Consumes the printer, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Writes one event line.
+    fn event(&mut self, event: &Event) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, event)?;
+        writeln!(self.writer)?;
+        Ok(())
+    }
+}
+
+/// A [`JsonPrinter`] event.
+#[derive(Serialize)]
+#[serde(tag = "type", content = "data", rename_all = "lowercase")]
+enum Event<'a> {
+    Begin {
+        file: &'a str,
+        format: &'a str,
+    },
+    Match {
+        file: &'a str,
+        #[serde(serialize_with = "serialize_path")]
+        path: &'a [PathType],
+        value: &'a Value<'a>,
+    },
+    End {
+        file: &'a str,
+        matches: usize,
+        elapsed: Elapsed,
+    },
+    Summary {
+        #[serde(flatten)]
+        summary: &'a SearchSummary,
+        elapsed: Elapsed,
+    },
+}
+
+/// Serializes a match path as an array of field names and indices.
+fn serialize_path<S: Serializer>(
+    path: &&[PathType],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(path.iter().map(|segment| match segment {
+        PathType::Index(i) => serde_json::Value::from(*i),
+        PathType::Field(f) => serde_json::Value::from(&**f),
+    }))
+}
+
+/// A duration in a [`JsonPrinter`] event.
+#[derive(Serialize)]
+struct Elapsed {
+    secs: u64,
+    nanos: u32,
+    human: String,
+}
+
+impl From<Duration> for Elapsed {
+    fn from(duration: Duration) -> Self {
+        Self {
+            secs: duration.as_secs(),
+            nanos: duration.subsec_nanos(),
+            human: format!("{:.6}s", duration.as_secs_f64()),
+        }
+    }
+}
+
+impl<W: Write> Sink for JsonPrinter<W> {
+    fn begin_file(&mut self, name: &str, format: &str) -> Result<()> {
+        self.file_started = Instant::now();
+        self.event(&Event::Begin { file: name, format })
+    }
+
+    fn matched(
+        &mut self,
+        name: &str,
+        path: &[PathType],
+        value: &Value,
+    ) -> Result<()> {
+        self.event(&Event::Match { file: name, path, value })
+    }
+
+    fn end_file(&mut self, name: &str, count: usize) -> Result<()> {
+        let elapsed = self.file_started.elapsed().into();
+        self.event(&Event::End { file: name, matches: count, elapsed })
+    }
+
+    fn summary(&mut self, summary: &SearchSummary) -> Result<()> {
+        let elapsed = self.started.elapsed().into();
+        self.event(&Event::Summary { summary, elapsed })
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Unit testing.")]
+mod tests {
+    use super::*;
+    use crate::{query::QueryDFA, search::Searcher};
+
+    const INPUTS: [(&str, &[u8]); 2] = [
+        ("a.json", br#"{"x": [1, 2], "y": {"x": "z"}}"#),
+        ("b.json", br#"{"y": 3}"#),
+    ];
+
+    /// Searches [`INPUTS`] for `x` at any depth, reporting to `sink`.
+    fn search<S: Sink>(sink: &mut S, mode: SearchMode) {
+        colored::control::set_override(false);
+        let dfa = QueryDFA::from_query_str("(* | [*])*.x").unwrap();
+        let searcher = Searcher::new(&dfa).mode(mode);
+        for (name, bytes) in INPUTS {
+            searcher.search_slice(name, bytes, sink).unwrap();
+        }
+    }
+
+    #[test]
+    fn standard_printer_prints_headings_between_files() {
+        let options =
+            WriteOptions { show_path: true, raw: true, ..Default::default() };
+        let mut printer =
+            StandardPrinter::new(Vec::new(), options).heading(true);
+        search(&mut printer, SearchMode::Matches);
+        assert_eq!(
+            String::from_utf8(printer.into_inner()).unwrap(),
+            "a.json\nx:\n[1,2]\ny.x:\nz\n"
+        );
+
+        let mut printer = StandardPrinter::new(Vec::new(), options);
+        search(&mut printer, SearchMode::Matches);
+        search(&mut printer, SearchMode::Matches);
+        assert_eq!(
+            String::from_utf8(printer.into_inner()).unwrap(),
+            "x:\n[1,2]\ny.x:\nz\n".repeat(2)
+        );
+    }
+
+    #[test]
+    fn summary_printer_prints_each_kind() {
+        let cases = [
+            (SummaryKind::Count, true, "a.json:2\nb.json:0\n"),
+            (SummaryKind::Count, false, "2\n0\n"),
+            (SummaryKind::FilesWithMatches, false, "a.json\n"),
+            (SummaryKind::Quiet, true, ""),
+        ];
+        for (kind, with_filename, expected) in cases {
+            let mut printer = SummaryPrinter::new(Vec::new(), kind)
+                .with_filename(with_filename)
+                .label(false);
+            search(&mut printer, kind.search_mode());
+            assert_eq!(
+                String::from_utf8(printer.into_inner()).unwrap(),
+                expected,
+                "{kind:?}"
+            );
+        }
+
+        let mut printer = SummaryPrinter::new(Vec::new(), SummaryKind::Count);
+        search(&mut printer, SearchMode::Count);
+        assert_eq!(
+            String::from_utf8(printer.into_inner()).unwrap(),
+            "Found matches: 2\nFound matches: 0\n"
+        );
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn format_printer_writes_a_document_per_match() {
+        let mut printer = FormatPrinter::new(DocumentWriter::new(
+            Vec::new(),
+            crate::Format::Yaml,
+        ));
+        search(&mut printer, SearchMode::Matches);
+        assert_eq!(
+            String::from_utf8(printer.into_inner()).unwrap(),
+            "- 1\n- 2\n---\nz\n"
+        );
+    }
+
+    #[test]
+    fn flat_printer_prints_leaves() {
+        let dfa =
+            QueryDFA::from_query_str(crate::flatten::LEAVES_QUERY).unwrap();
+        let mut printer = FlatPrinter::new(Vec::new()).with_filename(true);
+        for (name, bytes) in INPUTS {
+            Searcher::new(&dfa)
+                .search_slice(name, bytes, &mut printer)
+                .unwrap();
+        }
+        assert_eq!(
+            String::from_utf8(printer.into_inner()).unwrap(),
+            "a.json:x.[0] = 1\na.json:x.[1] = 2\na.json:y.x = \"z\"\n\
+             b.json:y = 3\n"
+        );
+    }
+
+    #[test]
+    fn json_printer_prints_every_event() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = INPUTS.map(|(name, bytes)| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, bytes).unwrap();
+            path
+        });
+
+        let dfa = QueryDFA::from_query_str("y").unwrap();
+        let mut printer = JsonPrinter::new(Vec::new());
+        Searcher::new(&dfa).search_paths(&paths, &mut printer).unwrap();
+
+        let events: Vec<serde_json::Value> =
+            String::from_utf8(printer.into_inner())
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect();
+        let types: Vec<_> = events
+            .iter()
+            .map(|event| event["type"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            types,
+            ["begin", "match", "end", "begin", "match", "end", "summary"]
+        );
+        assert_eq!(events[0]["data"]["format"], "json");
+        assert_eq!(events[1]["data"]["path"], serde_json::json!(["y"]));
+        assert_eq!(events[4]["data"]["value"], 3);
+        assert_eq!(events[5]["data"]["matches"], 1);
+
+        let mut summary = events[6]["data"].clone();
+        let elapsed = summary.as_object_mut().unwrap().remove("elapsed");
+        assert!(elapsed.unwrap()["human"].as_str().unwrap().ends_with('s'));
+        assert_eq!(
+            summary,
+            serde_json::json!({
+                "searched": 2, "matched": 2, "matches": 2, "failed": 0
+            })
+        );
+    }
+}
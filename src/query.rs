@@ -2,11 +2,17 @@
 This module provides the main query engine implementation, as well as the parser for the query
 language and the intermediary AST representations of queries.
 */
+pub mod arena;
 pub mod ast;
 pub(crate) mod common;
+pub mod de;
 pub mod dfa;
+pub mod index;
+pub mod limits;
 pub(crate) mod nfa;
 pub mod parser;
+pub mod stream;
+pub mod tree;
 
 use serde_json_borrow::Value;
 
@@ -29,8 +35,14 @@ pub trait QueryEngine {
 }
 
 // Re-exports
+pub use arena::{Match, Matches, PathArena, PathId, PathSegment};
 pub use ast::*;
 pub use common::{JSONPointer, PathType};
+pub use de::OwnedMatch;
 pub use dfa::*;
+pub use index::DocumentIndex;
+pub use limits::{LimitReason, LimitedResults, SearchLimits};
 pub use nfa::*;
 pub use parser::*;
+pub use stream::{StreamError, StreamMatch};
+pub use tree::{JsonTree, LeafKind};
@@ -0,0 +1,367 @@
+/*!
+# Path Arena
+
+Shared storage for the paths of query matches.
+
+[`QueryDFA::find`](crate::query::QueryDFA::find) returns every match with its
+own `Vec<PathType>`, cloning the full path and allocating a fresh key string
+per field segment. For queries that match most of the document (e.g.
+`(* | [*])*`), those allocations dominate the search.
+
+[`QueryDFA::find_matches`](crate::query::QueryDFA::find_matches) instead
+records paths in a [`PathArena`]: a parent-pointer tree of segments built
+during traversal, where field segments borrow their key directly from the
+document. Every match holds a [`PathId`] into the arena, and sibling matches
+share their common prefix. Paths are only materialized on demand, as a
+`Vec<PathType>`, an RFC 6901 JSON Pointer, or the dotted display string.
+
+# Examples
+
+```
+use jsongrep::{Value, query::QueryDFA};
+
+let json: Value =
+    serde_json::from_str(r#"{"users": [{"name": "Ada"}, {"name": "Bob"}]}"#)
+        .unwrap();
+let dfa = QueryDFA::from_query_str("users[*].name").unwrap();
+let matches = dfa.find_matches(&json);
+
+assert_eq!(matches.len(), 2);
+let first = matches.get(0).unwrap();
+assert_eq!(first.to_string(), "users.[0].name");
+assert_eq!(first.to_json_pointer(), "/users/0/name");
+assert_eq!(first.value.to_string(), r#""Ada""#);
+```
+*/
+use serde_json_borrow::Value;
+use std::fmt::Display;
+
+use crate::query::common::PathType;
+
+/// A single step of a path stored in a [`PathArena`], borrowing field names
+/// from the queried document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PathSegment<'a> {
+    /// An index into an array.
+    Index(usize),
+    /// A field of an object.
+    Field(&'a str),
+}
+
+impl From<PathSegment<'_>> for PathType {
+    fn from(segment: PathSegment<'_>) -> Self {
+        match segment {
+            PathSegment::Index(i) => Self::Index(i),
+            PathSegment::Field(key) => Self::Field(key.to_string().into()),
+        }
+    }
+}
+
+impl Display for PathSegment<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Index(i) => write!(f, "[{i}]"),
+            Self::Field(s) => write!(f, "{s}"),
+        }
+    }
+}
+
+/// Handle to a path stored in a [`PathArena`].
+///
+/// [`PathId::ROOT`] is the empty path of the document root.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PathId(usize);
+
+impl PathId {
+    /// The empty path, pointing at the document root.
+    pub const ROOT: Self = Self(0);
+
+    /// Whether this is the empty path of the document root.
+    #[must_use]
+    pub const fn is_root(self) -> bool {
+        self.0 == 0
+    }
+}
+
+/// A node in the arena: one segment plus a pointer to the path it extends.
+#[derive(Debug, Clone)]
+struct PathNode<'a> {
+    parent: PathId,
+    segment: PathSegment<'a>,
+}
+
+/// A parent-pointer tree of path segments.
+///
+/// Each stored path is a single node that points at the path it extends, so
+/// pushing a segment is `O(1)` and paths with a common prefix share it.
+#[derive(Debug, Clone)]
+pub struct PathArena<'a> {
+    /// `nodes[0]` is a sentinel for [`PathId::ROOT`]; its segment is never
+    /// read.
+    nodes: Vec<PathNode<'a>>,
+}
+
+impl Default for PathArena<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> PathArena<'a> {
+    /// Creates an arena holding only the root path.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![PathNode {
+                parent: PathId::ROOT,
+                segment: PathSegment::Index(0),
+            }],
+        }
+    }
+
+    /// Extends the path `parent` by one `segment`, returning the new path.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `parent` does not belong to this arena.
+    pub fn push(&mut self, parent: PathId, segment: PathSegment<'a>) -> PathId {
+        assert!(parent.0 < self.nodes.len(), "path id out of bounds");
+        self.nodes.push(PathNode { parent, segment });
+        PathId(self.nodes.len() - 1)
+    }
+
+    /// The number of stored segments (excluding the root).
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.nodes.len() - 1
+    }
+
+    /// Whether the arena holds no segments.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops every node pushed after the arena had `len` segments. Used by
+    /// the traversal to discard paths under subtrees that produced no
+    /// matches, so the arena only ever holds ancestors of matches.
+    pub(crate) fn truncate(&mut self, len: usize) {
+        self.nodes.truncate(len + 1);
+    }
+
+    /// Appends every path of `other` beneath `at`, so that `other`'s root
+    /// becomes `at`, and returns the mapping from `other`'s ids to the new
+    /// ids. Used to merge the arenas of subtrees searched in parallel.
+    #[cfg(feature = "rayon")]
+    pub(crate) fn graft(
+        &mut self,
+        other: &Self,
+        at: PathId,
+    ) -> impl Fn(PathId) -> PathId + use<> {
+        let offset = self.len();
+        let map = move |id: PathId| {
+            if id.is_root() { at } else { PathId(id.0 + offset) }
+        };
+        self.nodes.extend(other.nodes[1..].iter().map(|node| PathNode {
+            parent: map(node.parent),
+            segment: node.segment,
+        }));
+        map
+    }
+
+    /// The segments of the path `id`, from the root down.
+    #[must_use]
+    pub fn segments(&self, id: PathId) -> Vec<PathSegment<'a>> {
+        let mut segments = Vec::new();
+        let mut current = id;
+        while !current.is_root() {
+            let node = &self.nodes[current.0];
+            segments.push(node.segment);
+            current = node.parent;
+        }
+        segments.reverse();
+        segments
+    }
+
+    /// Materializes the path `id` as owned [`PathType`] segments, the form
+    /// used by [`JSONPointer`](crate::query::JSONPointer).
+    #[must_use]
+    pub fn to_path(&self, id: PathId) -> Vec<PathType> {
+        self.segments(id).into_iter().map(PathType::from).collect()
+    }
+
+    /// Renders the path `id` as an [RFC 6901] JSON Pointer, e.g.
+    /// `/users/0/name`. The root path is the empty string.
+    ///
+    /// [RFC 6901]: https://www.rfc-editor.org/rfc/rfc6901
+    #[must_use]
+    pub fn to_json_pointer(&self, id: PathId) -> String {
+        let mut pointer = String::new();
+        for segment in self.segments(id) {
+            pointer.push('/');
+            match segment {
+                PathSegment::Index(i) => pointer.push_str(&i.to_string()),
+                PathSegment::Field(key) => {
+                    pointer
+                        .push_str(&key.replace('~', "~0").replace('/', "~1"));
+                }
+            }
+        }
+        pointer
+    }
+
+    /// Renders the path `id` in the dotted form used by the `jg` path
+    /// headers, e.g. `users.[0].name`.
+    #[must_use]
+    pub fn display(&self, id: PathId) -> String {
+        let mut out = String::new();
+        for (i, segment) in self.segments(id).iter().enumerate() {
+            if i > 0 {
+                out.push('.');
+            }
+            out.push_str(&segment.to_string());
+        }
+        out
+    }
+}
+
+/// A single match from [`Matches`]: a path handle plus the matched value.
+#[derive(Debug, Clone, Copy)]
+pub struct Match<'m, 'a> {
+    /// The arena the path lives in.
+    arena: &'m PathArena<'a>,
+    /// The path to the matched value.
+    pub path: PathId,
+    /// A reference to the matched value in the document.
+    pub value: &'a Value<'a>,
+}
+
+impl<'a> Match<'_, 'a> {
+    /// The segments of this match's path, from the root down.
+    #[must_use]
+    pub fn segments(&self) -> Vec<PathSegment<'a>> {
+        self.arena.segments(self.path)
+    }
+
+    /// See [`PathArena::to_path`].
+    #[must_use]
+    pub fn to_path(&self) -> Vec<PathType> {
+        self.arena.to_path(self.path)
+    }
+
+    /// See [`PathArena::to_json_pointer`].
+    #[must_use]
+    pub fn to_json_pointer(&self) -> String {
+        self.arena.to_json_pointer(self.path)
+    }
+}
+
+impl Display for Match<'_, '_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.arena.display(self.path))
+    }
+}
+
+/// The result of [`QueryDFA::find_matches`](crate::query::QueryDFA::find_matches):
+/// every match in document order, with paths stored in a shared
+/// [`PathArena`].
+#[derive(Debug, Clone, Default)]
+pub struct Matches<'a> {
+    /// Storage for all match paths.
+    pub arena: PathArena<'a>,
+    /// `(path, value)` for each match, in document order.
+    pub hits: Vec<(PathId, &'a Value<'a>)>,
+}
+
+impl<'a> Matches<'a> {
+    /// The number of matches.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.hits.len()
+    }
+
+    /// Whether there are no matches.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.hits.is_empty()
+    }
+
+    /// The `i`-th match, if any.
+    #[must_use]
+    pub fn get(&self, i: usize) -> Option<Match<'_, 'a>> {
+        self.hits.get(i).map(|&(path, value)| Match {
+            arena: &self.arena,
+            path,
+            value,
+        })
+    }
+
+    /// Appends the matches of `other`, a search rooted at `at`, after the
+    /// existing ones.
+    #[cfg(feature = "rayon")]
+    pub(crate) fn append(&mut self, other: &Self, at: PathId) {
+        let map = self.arena.graft(&other.arena, at);
+        self.hits
+            .extend(other.hits.iter().map(|&(id, value)| (map(id), value)));
+    }
+
+    /// Iterates over the matches in document order.
+    pub fn iter(&self) -> impl Iterator<Item = Match<'_, 'a>> {
+        self.hits.iter().map(|&(path, value)| Match {
+            arena: &self.arena,
+            path,
+            value,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn root_path_is_empty() {
+        let arena = PathArena::new();
+        assert!(arena.is_empty());
+        assert!(arena.segments(PathId::ROOT).is_empty());
+        assert_eq!(arena.to_json_pointer(PathId::ROOT), "");
+        assert_eq!(arena.display(PathId::ROOT), "");
+    }
+
+    #[test]
+    fn paths_share_prefix() {
+        let mut arena = PathArena::new();
+        let users = arena.push(PathId::ROOT, PathSegment::Field("users"));
+        let first = arena.push(users, PathSegment::Index(0));
+        let second = arena.push(users, PathSegment::Index(1));
+
+        assert_eq!(arena.len(), 3);
+        assert_eq!(
+            arena.to_path(first),
+            vec![
+                PathType::Field("users".to_string().into()),
+                PathType::Index(0)
+            ]
+        );
+        assert_eq!(arena.display(second), "users.[1]");
+    }
+
+    #[test]
+    fn json_pointer_escapes_tilde_and_slash() {
+        let mut arena = PathArena::new();
+        let id = arena.push(PathId::ROOT, PathSegment::Field("a/b~c"));
+        let id = arena.push(id, PathSegment::Index(3));
+        assert_eq!(arena.to_json_pointer(id), "/a~1b~0c/3");
+    }
+
+    #[test]
+    fn truncate_discards_unused_branches() {
+        let mut arena = PathArena::new();
+        let kept = arena.push(PathId::ROOT, PathSegment::Field("kept"));
+        let mark = arena.len();
+        arena.push(kept, PathSegment::Field("dropped"));
+        arena.truncate(mark);
+        assert_eq!(arena.len(), 1);
+        assert_eq!(arena.display(kept), "kept");
+    }
+}
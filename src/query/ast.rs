@@ -26,8 +26,9 @@ assert_eq!(query, Query::Sequence(vec![Query::field("foo")]));
 ```
 */
 use std::{
+    borrow::Cow,
     cmp::PartialEq,
-    fmt::Display,
+    fmt::{Display, Write as _},
     ops::{Bound, RangeBounds},
     str::FromStr,
 };
@@ -114,13 +115,7 @@ impl Query {
 impl Display for Query {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Field(name) => {
-                if needs_quoting(name) {
-                    write!(f, "\"{}\"", escape_for_quoted_field(name))
-                } else {
-                    write!(f, "{name}")
-                }
-            }
+            Self::Field(name) => write!(f, "{}", quote_field(name)),
             Self::Index(idx) => write!(f, "[{idx}]"),
             Self::Range(start, end) => {
                 write!(f, "[")?;
@@ -220,27 +215,46 @@ impl Display for Query {
 
 /// Returns `true` if a field name contains characters that require quoting
 /// in the query DSL. This mirrors the pest grammar's `unquoted_field` rule,
-/// which forbids reserved characters, whitespace, and double quotes.
+/// which forbids reserved characters, whitespace, and double quotes; control
+/// characters are quoted too, so that they can be escaped.
 fn needs_quoting(name: &str) -> bool {
     // An empty field name cannot be represented unquoted
     name.is_empty()
         || name.contains(|c: char| {
             matches!(c, '.' | '|' | '*' | '?' | '[' | ']' | '(' | ')' | '/')
                 || c.is_whitespace()
+                || c.is_control()
                 || c == '"'
                 || c == '\\'
         })
 }
 
+/// A field name as written in the query DSL: as is, or quoted and escaped
+/// when it [needs quoting](needs_quoting).
+pub(crate) fn quote_field(name: &str) -> Cow<'_, str> {
+    if needs_quoting(name) {
+        Cow::Owned(format!("\"{}\"", escape_for_quoted_field(name)))
+    } else {
+        Cow::Borrowed(name)
+    }
+}
+
 /// Escape characters inside a quoted field name for display. This is the
-/// inverse of `unescape_json_string` in the parser: `"` -> `\"` and
-/// `\` -> `\\`.
+/// inverse of `unescape_json_string` in the parser: `"` -> `\"`,
+/// `\` -> `\\`, and control characters to JSON escapes such as `\n`, so
+/// that a quoted field always fits on one line.
 fn escape_for_quoted_field(name: &str) -> String {
     let mut result = String::with_capacity(name.len());
     for c in name.chars() {
         match c {
             '"' => result.push_str("\\\""),
             '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(result, "\\u{:04x}", u32::from(c));
+            }
             _ => result.push(c),
         }
     }
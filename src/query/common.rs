@@ -7,18 +7,22 @@ predicate definitions for JSON automaton.
 */
 use serde::Serialize;
 use serde_json_borrow::Value;
-use std::rc::Rc;
+use std::sync::Arc;
 
 /// A JSON pointer that points to a value in a JSON document.
+///
+/// `T` is the document type that was searched (see
+/// [`JsonTree`](crate::query::tree::JsonTree)), defaulting to the borrowed
+/// [`Value`].
 #[derive(PartialEq, Eq, Debug, Clone)]
-pub struct JSONPointer<'a> {
+pub struct JSONPointer<'a, T = Value<'a>> {
     /// The path to the value in the JSON document, e.g., \["foo", "bar", "bass"\].
     pub path: Vec<PathType>,
     /// A reference to the value in the JSON document.
-    pub value: &'a Value<'a>,
+    pub value: &'a T,
 }
 
-impl std::fmt::Display for JSONPointer<'_> {
+impl<T: std::fmt::Debug> std::fmt::Display for JSONPointer<'_, T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "path: {:#?}", self.path)?;
         write!(f, "value: {:?}", self.value)
@@ -31,7 +35,7 @@ pub enum PathType {
     /// Represents an index in an array, e.g., "foo\[3\]".
     Index(usize),
     /// Represents a field in an object, e.g., "foo.bar".
-    Field(Rc<String>),
+    Field(Arc<str>),
 }
 
 impl std::fmt::Display for PathType {
@@ -50,7 +54,7 @@ pub enum TransitionLabel {
     /// Matches a specific field name, e.g., "foo"
     /// Any field that doesn't match will be lumped together in the catch-all
     /// "other" symbol, which refers to key ID `0`.
-    Field(Rc<String>),
+    Field(Arc<str>),
     /// Matches any field name, e.g., "*".
     FieldWildcard,
     /// Matches a range of indices, e.g., "\[start:end\]".
@@ -0,0 +1,464 @@
+/*!
+# DFA-guided Deserialization
+
+Runs a [`QueryDFA`] directly against any serde [`Deserializer`], so that
+documents in YAML, TOML, `MessagePack` and other self-describing formats can
+be queried without first converting them to JSON text.
+
+The query is driven by a [`DeserializeSeed`] that tracks the DFA state of the
+value being deserialized:
+
+- map keys and sequence indices are fed to the DFA as they are read;
+- values whose state is dead are skipped with [`IgnoredAny`], so they are
+  never built;
+- values under accepting states are collected as owned
+  [`serde_json::Value`]s together with their path.
+
+# Examples
+
+```
+use jsongrep::query::QueryDFA;
+
+let json = r#"{"services": {"web": {"image": "nginx"}, "db": {"image": "postgres"}}}"#;
+let dfa = QueryDFA::from_query_str("services.*.image").unwrap();
+
+let mut deserializer = serde_json::Deserializer::from_str(json);
+let matches = dfa.find_deserialize(&mut deserializer).unwrap();
+assert_eq!(matches.len(), 2);
+assert_eq!(matches[1].value, "postgres");
+```
+*/
+use serde::Deserialize as _;
+use serde::de::{
+    self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess,
+    Visitor,
+};
+use std::fmt;
+
+use crate::query::{QueryDFA, common::PathType};
+
+/// A match found by [`QueryDFA::find_deserialize`]: the path to the value plus
+/// an owned copy of the value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnedMatch {
+    /// The path to the value in the document.
+    pub path: Vec<PathType>,
+    /// The matched value.
+    pub value: serde_json::Value,
+}
+
+impl QueryDFA {
+    /// Execute this compiled query while deserializing a document from
+    /// `deserializer`, returning all matches in document order.
+    ///
+    /// Only the matched values are built; everything the query cannot reach
+    /// is skipped by the deserializer. Map keys that are not strings (e.g.
+    /// integer keys in YAML) are matched by their string form, like the
+    /// conversion to JSON does.
+    ///
+    /// # Errors
+    ///
+    /// Returns the deserializer's error if the input is malformed.
+    pub fn find_deserialize<'de, D>(
+        &self,
+        deserializer: D,
+    ) -> Result<Vec<OwnedMatch>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        self.find_deserialize_with_limit(deserializer, usize::MAX)
+    }
+
+    /// Like [`QueryDFA::find_deserialize`], but stops deserializing once
+    /// `max_matches` matches are found (like `grep -m`). The rest of the
+    /// input is then not read, so errors in it go unreported.
+    ///
+    /// # Errors
+    ///
+    /// Returns the deserializer's error if the input is malformed before
+    /// the limit is reached.
+    pub fn find_deserialize_with_limit<'de, D>(
+        &self,
+        deserializer: D,
+        max_matches: usize,
+    ) -> Result<Vec<OwnedMatch>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let mut path = Vec::new();
+        let mut results = Vec::new();
+        if max_matches == 0 {
+            return Ok(results);
+        }
+        let found = DfaSeed {
+            dfa: self,
+            state: self.start_state,
+            limit: max_matches,
+            path: &mut path,
+            results: &mut results,
+        }
+        .deserialize(deserializer);
+        match found {
+            // The seed bails out with an error to stop the deserializer.
+            Err(_) if results.len() >= max_matches => {}
+            found => found?,
+        }
+        results.truncate(max_matches);
+        Ok(results)
+    }
+}
+
+/// The error a [`DfaSeed`] stops the deserializer with once it has found
+/// enough matches.
+const LIMIT_REACHED: &str = "match limit reached";
+
+/// A [`DeserializeSeed`] that deserializes one value in the DFA state
+/// `state`, appending any matches at or below it to `results`.
+struct DfaSeed<'s> {
+    dfa: &'s QueryDFA,
+    state: usize,
+    /// Stop once `results` holds this many matches.
+    limit: usize,
+    path: &'s mut Vec<PathType>,
+    results: &'s mut Vec<OwnedMatch>,
+}
+
+impl<'de> DeserializeSeed<'de> for DfaSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> Result<(), D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let accepting = self.dfa.is_accepting_state(self.state);
+        let live = self.dfa.has_transitions(self.state);
+
+        match (accepting, live) {
+            // Nothing at or below this value can match.
+            (false, false) => {
+                IgnoredAny::deserialize(deserializer)?;
+            }
+            // The value matches, but nothing below it can.
+            (true, false) => {
+                let value = serde_json::Value::deserialize(deserializer)?;
+                self.results
+                    .push(OwnedMatch { path: self.path.clone(), value });
+            }
+            // The value matches and so may values below it: build it once,
+            // then search the built value for the nested matches, cloning
+            // each of them once. The first match is the value itself.
+            (true, true) => {
+                let value = serde_json::Value::deserialize(deserializer)?;
+                let nested = self
+                    .dfa
+                    .find_tree_from(self.state, &value)
+                    .into_iter()
+                    .skip(1)
+                    .map(|m| OwnedMatch {
+                        path: [self.path.as_slice(), &m.path].concat(),
+                        value: m.value.clone(),
+                    })
+                    .collect::<Vec<_>>();
+                self.results
+                    .push(OwnedMatch { path: self.path.clone(), value });
+                self.results.extend(nested);
+            }
+            (false, true) => deserializer.deserialize_any(self)?,
+        }
+        Ok(())
+    }
+}
+
+impl<'de> Visitor<'de> for DfaSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("any value")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<(), A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        while let Some(key) = map.next_key_seed(KeySeed)? {
+            let symbol_id = self.dfa.get_field_symbol_id(&key);
+            if let Some(next) = self.dfa.transition(self.state, symbol_id) {
+                self.path
+                    .push(PathType::Field(self.dfa.path_key(symbol_id, &key)));
+                map.next_value_seed(DfaSeed {
+                    dfa: self.dfa,
+                    state: next,
+                    limit: self.limit,
+                    path: &mut *self.path,
+                    results: &mut *self.results,
+                })?;
+                self.path.pop();
+                if self.results.len() >= self.limit {
+                    return Err(de::Error::custom(LIMIT_REACHED));
+                }
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<(), A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut idx = 0;
+        loop {
+            let next = self
+                .dfa
+                .get_index_symbol_id(idx)
+                .and_then(|id| self.dfa.transition(self.state, id));
+            let more = if let Some(next) = next {
+                self.path.push(PathType::Index(idx));
+                let more = seq
+                    .next_element_seed(DfaSeed {
+                        dfa: self.dfa,
+                        state: next,
+                        limit: self.limit,
+                        path: &mut *self.path,
+                        results: &mut *self.results,
+                    })?
+                    .is_some();
+                self.path.pop();
+                if self.results.len() >= self.limit {
+                    return Err(de::Error::custom(LIMIT_REACHED));
+                }
+                more
+            } else {
+                seq.next_element::<IgnoredAny>()?.is_some()
+            };
+            if !more {
+                return Ok(());
+            }
+            idx += 1;
+        }
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<(), D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        self.deserialize(deserializer)
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<(), D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        self.deserialize(deserializer)
+    }
+
+    // Scalars in a non-accepting state cannot match anything.
+    fn visit_bool<E>(self, _: bool) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_i64<E>(self, _: i64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_i128<E>(self, _: i128) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_u64<E>(self, _: u64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_u128<E>(self, _: u128) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_f64<E>(self, _: f64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_str<E>(self, _: &str) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_bytes<E>(self, _: &[u8]) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_none<E>(self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_unit<E>(self) -> Result<(), E> {
+        Ok(())
+    }
+}
+
+/// Reads a map key as a string, accepting scalar keys of other types in
+/// their display form.
+struct KeySeed;
+
+impl<'de> DeserializeSeed<'de> for KeySeed {
+    type Value = String;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<String, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl Visitor<'_> for KeySeed {
+    type Value = String;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a string or scalar map key")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<String, E> {
+        Ok(v.to_string())
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<String, E> {
+        Ok(v)
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<String, E> {
+        Ok(v.to_string())
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<String, E> {
+        Ok(v.to_string())
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<String, E> {
+        Ok(v.to_string())
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<String, E> {
+        Ok(v.to_string())
+    }
+
+    fn visit_unit<E>(self) -> Result<String, E> {
+        Ok("null".to_string())
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<String, E>
+    where
+        E: de::Error,
+    {
+        String::from_utf8(v.to_vec())
+            .map_err(|_| E::invalid_type(de::Unexpected::Bytes(v), &self))
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Unit testing.")]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use serde_json_borrow::Value;
+
+    /// Assert that deserializing `json` with the seed yields the same paths
+    /// and values as the tree-walking engine.
+    fn assert_agrees(json: &str, query: &str) {
+        let dfa = QueryDFA::from_query_str(query).unwrap();
+        let tree: Value = serde_json::from_str(json).unwrap();
+        let expected = dfa.find(&tree);
+
+        let mut de = serde_json::Deserializer::from_str(json);
+        let found = dfa.find_deserialize(&mut de).unwrap();
+
+        assert_eq!(found.len(), expected.len(), "query `{query}`");
+        for (f, e) in found.iter().zip(&expected) {
+            assert_eq!(f.path, e.path, "query `{query}`");
+            assert_eq!(f.value, serde_json::Value::from(e.value));
+        }
+    }
+
+    #[test]
+    fn agrees_with_tree_engine() {
+        let json = r#"{
+            "a": {"b": [1, {"c": 2}], "c": 3},
+            "list": [[1, 2], [3, [4]]],
+            "c": {"c": {"c": null}}
+        }"#;
+        for query in [
+            "",
+            "a.b[1].c",
+            "list[*][1]",
+            "(* | [*])*.c",
+            "(* | [*])*",
+            "c*",
+            "missing",
+        ] {
+            assert_agrees(json, query);
+        }
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn yaml_integer_keys_match_by_string_form() {
+        let yaml = "ports:\n  80: http\n  443: https\n";
+        let dfa = QueryDFA::from_query_str("ports.443").unwrap();
+        let found = dfa
+            .find_deserialize(serde_yaml::Deserializer::from_str(yaml))
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].value, "https");
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn dead_subtrees_are_ignored_not_built() {
+        // `skip` holds a YAML value serde_json cannot represent (a map with
+        // a sequence key); it must be skipped rather than rejected.
+        let yaml = "skip:\n  ? [1, 2]\n  : x\nkeep: 1\n";
+        let dfa = QueryDFA::from_query_str("keep").unwrap();
+        let found = dfa
+            .find_deserialize(serde_yaml::Deserializer::from_str(yaml))
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].value, 1);
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml_tables_and_arrays() {
+        let toml_src = "[[bin]]\nname = \"a\"\n\n[[bin]]\nname = \"b\"\n";
+        let dfa = QueryDFA::from_query_str("bin[*].name").unwrap();
+        let found =
+            dfa.find_deserialize(toml::Deserializer::new(toml_src)).unwrap();
+        let names: Vec<_> = found.iter().map(|m| m.value.clone()).collect();
+        assert_eq!(names, ["a", "b"]);
+    }
+
+    #[test]
+    fn deserializing_stops_at_the_limit() {
+        let dfa = QueryDFA::from_query_str("(* | [*])*.c").unwrap();
+        let json =
+            r#"{"a": {"c": 1}, "b": [{"c": 2}, {"c": {"c": 3}}], "x": oops"#;
+        let found = |max| {
+            let mut de = serde_json::Deserializer::from_str(json);
+            dfa.find_deserialize_with_limit(&mut de, max).map(|found| {
+                found.into_iter().map(|m| m.value).collect::<Vec<_>>()
+            })
+        };
+        assert_eq!(found(0).unwrap(), Vec::<serde_json::Value>::new());
+        assert_eq!(found(1).unwrap(), [1]);
+        assert_eq!(found(2).unwrap(), [1, 2]);
+        // The nested match is found along with its parent.
+        assert_eq!(found(3).unwrap(), [1.into(), 2.into(), json!({"c": 3})]);
+        // The malformed tail is only read when more matches are wanted.
+        assert!(found(5).is_err());
+    }
+
+    #[test]
+    fn malformed_input_is_an_error() {
+        let dfa = QueryDFA::from_query_str("a").unwrap();
+        let mut de = serde_json::Deserializer::from_str(r#"{"a": [1,"#);
+        assert!(dfa.find_deserialize(&mut de).is_err());
+    }
+}
@@ -20,13 +20,15 @@ use serde_json_borrow::Value;
 use std::{
     collections::{HashMap, VecDeque},
     fmt::Display,
-    rc::Rc,
+    sync::Arc,
 };
 
 use crate::query::{
     QueryNFA, QueryParseError,
+    arena::{Matches, PathId, PathSegment},
     ast::Query,
-    common::{JSONPointer, PathType, TransitionLabel},
+    common::{JSONPointer, TransitionLabel},
+    tree::{Children, JsonTree, open_children},
 };
 
 /// Error returned when DFA determinization exceeds a configured state
@@ -116,6 +118,10 @@ impl From<StateLimitExceeded> for QueryCompileError {
 
 /// Represents a Deterministic Finite Automaton (DFA) for JSON queries. An
 /// important thing to note is that the alphabet depends on the query.
+///
+/// A compiled `QueryDFA` is immutable during search and is `Send + Sync`, so
+/// it can be compiled once and shared across threads, e.g. in an `Arc` or a
+/// global registry.
 #[non_exhaustive]
 #[derive(Debug)]
 pub struct QueryDFA {
@@ -381,6 +387,178 @@ impl QueryDFA {
         DFAQueryEngine::find_with_dfa(json, self)
     }
 
+    /// Execute this compiled query against any [`JsonTree`], such as a
+    /// [`serde_json::Value`] or a YAML, TOML or CBOR value, returning all
+    /// matches without converting the document first.
+    ///
+    /// [`QueryDFA::find`] is this method specialized to the borrowed
+    /// [`Value`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jsongrep::query::QueryDFA;
+    ///
+    /// let json = serde_json::json!({"a": {"b": [true, false]}});
+    /// let query = QueryDFA::from_query_str("a.b[1]").unwrap();
+    /// let results = query.find_tree(&json);
+    /// assert_eq!(results[0].value, &serde_json::Value::Bool(false));
+    /// ```
+    #[must_use]
+    pub fn find_tree<'a, T: JsonTree>(
+        &self,
+        json: &'a T,
+    ) -> Vec<JSONPointer<'a, T>> {
+        let mut results = Vec::new();
+        DFAQueryEngine::traverse_json(
+            self,
+            self.start_state,
+            json,
+            &mut results,
+        );
+        results
+    }
+
+    /// Like [`QueryDFA::find_tree`], for a subtree reached in the DFA state
+    /// `state`, with paths relative to the subtree.
+    pub(super) fn find_tree_from<'a, T: JsonTree>(
+        &self,
+        state: usize,
+        json: &'a T,
+    ) -> Vec<JSONPointer<'a, T>> {
+        let mut results = Vec::new();
+        DFAQueryEngine::traverse_json(self, state, json, &mut results);
+        results
+    }
+
+    /// Like [`QueryDFA::find_tree`], for a document that is element `index`
+    /// of a top-level array, such as a JSONL record read on its own.
+    ///
+    /// Matches are those the query finds under `[index]` of the array, with
+    /// paths relative to the element.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jsongrep::query::QueryDFA;
+    ///
+    /// let record = serde_json::json!({"id": 7});
+    /// let query = QueryDFA::from_query_str("[1].id").unwrap();
+    /// assert!(query.find_tree_at_index(0, &record).is_empty());
+    /// assert_eq!(query.find_tree_at_index(1, &record)[0].value, 7);
+    /// ```
+    #[must_use]
+    pub fn find_tree_at_index<'a, T: JsonTree>(
+        &self,
+        index: usize,
+        json: &'a T,
+    ) -> Vec<JSONPointer<'a, T>> {
+        let mut results = Vec::new();
+        if let Some(state) = self
+            .get_index_symbol_id(index)
+            .and_then(|id| self.transition(self.start_state, id))
+        {
+            DFAQueryEngine::traverse_json(self, state, json, &mut results);
+        }
+        results
+    }
+
+    /// Count the matches of this compiled query in a document without
+    /// materializing them.
+    ///
+    /// The DFA is walked exactly as in [`QueryDFA::find`], but no paths or
+    /// pointers are built, so counting allocates nothing regardless of the
+    /// number of matches. Works on any [`JsonTree`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jsongrep::{Value, query::QueryDFA};
+    ///
+    /// let json: Value = serde_json::from_str(r#"{"a": [1, 2, 3]}"#).unwrap();
+    /// let query = QueryDFA::from_query_str("a[*]").unwrap();
+    /// assert_eq!(query.count(&json), 3);
+    /// ```
+    #[must_use]
+    pub fn count<T: JsonTree>(&self, json: &T) -> usize {
+        DFAQueryEngine::count_tree(self, json, usize::MAX)
+    }
+
+    /// Like [`QueryDFA::count`], but stops walking the document once `max`
+    /// matches were counted (like `grep -m`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jsongrep::{Value, query::QueryDFA};
+    ///
+    /// let json: Value = serde_json::from_str(r#"{"a": [1, 2, 3]}"#).unwrap();
+    /// let query = QueryDFA::from_query_str("a[*]").unwrap();
+    /// assert_eq!(query.count_up_to(&json, 2), 2);
+    /// assert_eq!(query.count_up_to(&json, 5), 3);
+    /// ```
+    #[must_use]
+    pub fn count_up_to<T: JsonTree>(&self, json: &T, max: usize) -> usize {
+        DFAQueryEngine::count_tree(self, json, max)
+    }
+
+    /// Whether this compiled query matches anywhere in a document. Like
+    /// [`QueryDFA::count`], but stops at the first match.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jsongrep::{Value, query::QueryDFA};
+    ///
+    /// let json: Value = serde_json::from_str(r#"{"a": [1, 2, 3]}"#).unwrap();
+    /// assert!(QueryDFA::from_query_str("a[2]").unwrap().is_match(&json));
+    /// assert!(!QueryDFA::from_query_str("a[3]").unwrap().is_match(&json));
+    /// ```
+    #[must_use]
+    pub fn is_match<T: JsonTree>(&self, json: &T) -> bool {
+        DFAQueryEngine::count_tree(self, json, 1) > 0
+    }
+
+    /// Execute this compiled query against a JSON document, storing the
+    /// match paths in a shared [`PathArena`] instead of cloning a
+    /// `Vec<PathType>` per match.
+    ///
+    /// Field segments borrow their keys from the document, so no per-match
+    /// or per-key allocation happens during the search. Paths can be
+    /// materialized afterwards with [`Match::to_path`],
+    /// [`Match::to_json_pointer`] or `Display`. Prefer this over
+    /// [`QueryDFA::find`] for queries that match a large part of the
+    /// document, such as `(* | [*])*`.
+    ///
+    /// [`PathArena`]: crate::query::arena::PathArena
+    /// [`Match::to_path`]: crate::query::arena::Match::to_path
+    /// [`Match::to_json_pointer`]: crate::query::arena::Match::to_json_pointer
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jsongrep::{Value, query::QueryDFA};
+    ///
+    /// let json: Value = serde_json::from_str(r#"{"a": [1, 2]}"#).unwrap();
+    /// let query = QueryDFA::from_query_str("a[*]").unwrap();
+    /// let matches = query.find_matches(&json);
+    /// let paths: Vec<String> = matches.iter().map(|m| m.to_string()).collect();
+    /// assert_eq!(paths, ["a.[0]", "a.[1]"]);
+    /// ```
+    #[must_use]
+    pub fn find_matches<'a>(&self, json: &'a Value<'a>) -> Matches<'a> {
+        let mut matches = Matches::default();
+        DFAQueryEngine::traverse_arena::<false>(
+            self,
+            self.start_state,
+            PathId::ROOT,
+            None,
+            json,
+            &mut matches,
+        );
+        matches
+    }
+
     /// Check if a given state is accepting/final.
     #[must_use]
     pub fn is_accepting_state(&self, state: usize) -> bool {
@@ -410,6 +588,32 @@ impl QueryDFA {
         id.copied().unwrap_or(TransitionLabel::other_idx())
     }
 
+    /// The shared key string to record in a match path for the document key
+    /// `key`, which resolved to `symbol_id`.
+    ///
+    /// Reuses the interned key from the query alphabet when it is the same
+    /// string as the document key (true for a case-sensitive Field symbol;
+    /// the equality check guards against externally mutated
+    /// `key_to_key_id`); otherwise allocates.
+    pub(crate) fn path_key(&self, symbol_id: usize, key: &str) -> Arc<str> {
+        match self.alphabet.get(symbol_id) {
+            Some(TransitionLabel::Field(interned))
+                if !self.case_insensitive && &**interned == key =>
+            {
+                Arc::clone(interned)
+            }
+            _ => Arc::from(key),
+        }
+    }
+
+    /// Whether `state` has at least one outgoing transition, i.e. whether
+    /// any value below a node in this state can still match.
+    pub(crate) fn has_transitions(&self, state: usize) -> bool {
+        self.transitions
+            .get(state)
+            .is_some_and(|row| row.iter().any(Option::is_some))
+    }
+
     /// Get the symbol index for an array index by performing a binary search
     /// over the sorted vector of all range entries.
     #[must_use]
@@ -502,8 +706,8 @@ impl DFABuilder {
                 };
                 if !self.key_to_key_id.contains_key(&normalized) {
                     let symbol_id = self.alphabet.len();
-                    self.alphabet.push(TransitionLabel::Field(Rc::new(
-                        normalized.clone(),
+                    self.alphabet.push(TransitionLabel::Field(Arc::from(
+                        normalized.as_str(),
                     )));
                     self.key_to_key_id.insert(normalized, symbol_id);
                 }
@@ -823,90 +1027,310 @@ pub struct DFAQueryEngine;
 impl DFAQueryEngine {
     /// Performs a depth-first search over the JSON document AST, accumulating
     /// results as it traverses and finds final states.
-    fn traverse_json<'a>(
+    ///
+    /// The walk starts in `start` and keeps the open containers on an
+    /// explicit stack rather than recursing, so it handles arbitrarily deep
+    /// documents.
+    fn traverse_json<'a, T: JsonTree>(
         dfa: &QueryDFA,
-        current_state: usize,
-        path: &mut Vec<PathType>,
-        value: &'a Value<'a>,
-        results: &mut Vec<JSONPointer<'a>>,
+        start: usize,
+        value: &'a T,
+        results: &mut Vec<JSONPointer<'a, T>>,
     ) {
-        // Check if current state is accepting
-        if dfa.is_accepting_state(current_state) {
-            results.push(JSONPointer {
-                path: path.clone(), // clone path only for result
-                value,
-            });
+        let mut path = Vec::new();
+        // (state, remaining children) of each open container on `path`.
+        let mut stack = Vec::new();
+        let mut pending = Some((start, value));
+
+        loop {
+            if let Some((state, value)) = pending.take() {
+                // Check if current state is accepting
+                if dfa.is_accepting_state(state) {
+                    results.push(JSONPointer {
+                        path: path.clone(), // clone path only for result
+                        value,
+                    });
+                }
+                match open_children(value) {
+                    Some(children) => stack.push((state, children)),
+                    // Leaf JSON nodes - backtrack right away
+                    None => {
+                        path.pop();
+                    }
+                }
+            }
+
+            let Some((state, children)) = stack.last_mut() else { break };
+            match children.next_step(dfa, *state) {
+                Some(Some((next_state, edge, child))) => {
+                    // Extend the current path and visit the child next
+                    path.push(edge.to_path(dfa));
+                    pending = Some((next_state, child));
+                }
+                // No valid transition: skip this child
+                Some(None) => {}
+                // Container exhausted: backtrack
+                None => {
+                    stack.pop();
+                    path.pop();
+                }
+            }
         }
+    }
+}
 
-        match value {
-            Value::Object(map) => {
-                for (key, val) in map.as_vec() {
-                    // Borrow the document key as a plain `&str` once, up
-                    // front (CowStr derefs to str).
-                    let key: &str = key;
+impl DFAQueryEngine {
+    /// Counting variant of [`DFAQueryEngine::traverse_json`]: returns the
+    /// number of matches under `value` without recording them, stopping
+    /// once `limit` is reached.
+    fn count_tree<T: JsonTree>(
+        dfa: &QueryDFA,
+        value: &T,
+        limit: usize,
+    ) -> usize {
+        if limit == 0 {
+            return 0;
+        }
+        let mut count = 0;
+        let mut stack = Vec::new();
+        let mut pending = Some((dfa.start_state, value));
+
+        loop {
+            if let Some((state, value)) = pending.take() {
+                if dfa.is_accepting_state(state) {
+                    count += 1;
+                    if count >= limit {
+                        return count;
+                    }
+                }
+                if let Some(children) = open_children(value) {
+                    stack.push((state, children));
+                }
+            }
 
-                    // Get symbol ID for this field
-                    let symbol_id = dfa.get_field_symbol_id(key);
+            let Some((state, children)) = stack.last_mut() else {
+                return count;
+            };
+            match children.next_step(dfa, *state) {
+                Some(Some((next_state, _, child))) => {
+                    pending = Some((next_state, child));
+                }
+                Some(None) => {}
+                None => {
+                    stack.pop();
+                }
+            }
+        }
+    }
+}
 
-                    // Try to transition on this symbol
-                    if let Some(next_state) =
-                        dfa.transition(current_state, symbol_id)
-                    {
-                        // Reuse the interned key from the query alphabet when
-                        // it is the same string as the document key (true for
-                        // a case-sensitive Field symbol; the equality check
-                        // guards against externally mutated `key_to_key_id`);
-                        // otherwise allocate.
-                        let key_rc: Rc<String> =
-                            match dfa.alphabet.get(symbol_id) {
-                                Some(TransitionLabel::Field(interned))
-                                    if !dfa.case_insensitive
-                                        && interned.as_str() == key =>
-                                {
-                                    Rc::clone(interned)
-                                }
-                                _ => Rc::new(key.to_string()),
-                            };
-                        path.push(PathType::Field(key_rc));
+impl DFAQueryEngine {
+    /// Arena-backed variant of [`DFAQueryEngine::traverse_json`], searching
+    /// the subtree `value` reached from `parent` along `segment` (`None` for
+    /// the document root).
+    ///
+    /// Each edge taken pushes one borrowed segment onto `matches.arena`, and
+    /// segments under subtrees that produced no match are truncated away on
+    /// backtrack, so the arena only holds ancestors of matches. Like
+    /// [`DFAQueryEngine::traverse_json`], the walk uses an explicit stack.
+    fn traverse_arena<'a, const PAR: bool>(
+        dfa: &QueryDFA,
+        state: usize,
+        parent: PathId,
+        segment: Option<PathSegment<'a>>,
+        value: &'a Value<'a>,
+        matches: &mut Matches<'a>,
+    ) {
+        // (state, path, arena/hit marks, remaining children) of each open
+        // container.
+        let mut stack = Vec::new();
+        let mut pending = Some((state, parent, segment, value));
+
+        loop {
+            if let Some((state, parent, segment, value)) = pending.take() {
+                let marks = (matches.arena.len(), matches.hits.len());
+                let path = segment
+                    .map_or(parent, |seg| matches.arena.push(parent, seg));
+                if dfa.is_accepting_state(state) {
+                    matches.hits.push((path, value));
+                }
 
-                        // Recurse on the extended path
-                        Self::traverse_json(
-                            dfa, next_state, path, val, results,
-                        );
+                let children = match value {
+                    Value::Object(map) => Some(Children::Entries(map.iter())),
+                    Value::Array(vals) => {
+                        Some(Children::Elements(vals.iter().enumerate()))
+                    }
+                    Value::Null
+                    | Value::Bool(_)
+                    | Value::Number(_)
+                    | Value::Str(_) => None,
+                };
+                #[cfg(feature = "rayon")]
+                let children = if PAR
+                    && Self::par_children(dfa, state, path, value, matches)
+                {
+                    None
+                } else {
+                    children
+                };
 
-                        // Backtrack by removing what we just added
-                        path.pop();
+                match children {
+                    Some(children) => {
+                        stack.push((state, path, marks, children));
                     }
+                    None => Self::drop_if_unmatched(matches, marks),
                 }
             }
-            Value::Array(vals) => {
-                for (idx, val) in vals.iter().enumerate() {
-                    // Get symbol ID for this index
-                    if let Some(symbol_id) = dfa.get_index_symbol_id(idx) {
-                        // Try to transition on this symbol
-                        if let Some(next_state) =
-                            dfa.transition(current_state, symbol_id)
-                        {
-                            // Extend the current path
-                            path.push(PathType::Index(idx));
-
-                            // Recurse on the extended path
-                            Self::traverse_json(
-                                dfa, next_state, path, val, results,
-                            );
 
-                            // Backtrack
-                            path.pop();
-                        }
+            let Some((state, path, _, children)) = stack.last_mut() else {
+                break;
+            };
+            let step = match children {
+                Children::Entries(entries) => {
+                    entries.next().map(|(key, val)| {
+                        let symbol_id = dfa.get_field_symbol_id(key);
+                        dfa.transition(*state, symbol_id)
+                            .map(|next| (next, PathSegment::Field(key), val))
+                    })
+                }
+                Children::Elements(elements) => {
+                    elements.next().map(|(idx, val)| {
+                        dfa.get_index_symbol_id(idx)
+                            .and_then(|id| dfa.transition(*state, id))
+                            .map(|next| (next, PathSegment::Index(idx), val))
+                    })
+                }
+            };
+            match step {
+                Some(Some((next_state, segment, child))) => {
+                    pending = Some((next_state, *path, Some(segment), child));
+                }
+                Some(None) => {}
+                None => {
+                    if let Some((_, _, marks, _)) = stack.pop() {
+                        Self::drop_if_unmatched(matches, marks);
                     }
-                    // If get_index_symbol_id returns None, skip this index (no valid transition)
                 }
             }
-            // Leaf JSON nodes - no further traversal needed
-            Value::Null | Value::Bool(_) | Value::Number(_) | Value::Str(_) => {
-            }
         }
     }
+
+    /// Drops the arena segments pushed since `marks` were taken if no match
+    /// was recorded since then.
+    fn drop_if_unmatched(
+        matches: &mut Matches<'_>,
+        (arena_mark, hits_mark): (usize, usize),
+    ) {
+        if matches.hits.len() == hits_mark {
+            matches.arena.truncate(arena_mark);
+        }
+    }
+}
+
+/// Containers with at least this many children are split across threads by
+/// [`QueryDFA::par_find_matches`]; smaller ones are not worth the overhead.
+#[cfg(feature = "rayon")]
+const PAR_MIN_CHILDREN: usize = 512;
+
+#[cfg(feature = "rayon")]
+impl QueryDFA {
+    /// Parallel variant of [`QueryDFA::find_matches`], available with the
+    /// `rayon` feature.
+    ///
+    /// Arrays and objects with many children (at any depth) are split into
+    /// chunks that are searched on the global rayon thread pool, each into
+    /// its own arena; the chunks are then stitched back together, so the
+    /// result is identical to [`QueryDFA::find_matches`], in document
+    /// order. Small documents are searched on the calling thread.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jsongrep::{Value, query::QueryDFA};
+    ///
+    /// let text = format!("[{}]", vec!["{\"id\": 1}"; 2000].join(","));
+    /// let json: Value = serde_json::from_str(&text).unwrap();
+    /// let query = QueryDFA::from_query_str("[*].id").unwrap();
+    /// assert_eq!(query.par_find_matches(&json).len(), 2000);
+    /// ```
+    #[must_use]
+    pub fn par_find_matches<'a>(&self, json: &'a Value<'a>) -> Matches<'a> {
+        let mut matches = Matches::default();
+        DFAQueryEngine::traverse_arena::<true>(
+            self,
+            self.start_state,
+            PathId::ROOT,
+            None,
+            json,
+            &mut matches,
+        );
+        matches
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl DFAQueryEngine {
+    /// If `value` has at least [`PAR_MIN_CHILDREN`] children, searches the
+    /// ones the DFA can step into in parallel chunks, appends the results to
+    /// `matches` in order, and returns `true`. Returns `false` without doing
+    /// anything otherwise.
+    fn par_children<'a>(
+        dfa: &QueryDFA,
+        current_state: usize,
+        path: PathId,
+        value: &'a Value<'a>,
+        matches: &mut Matches<'a>,
+    ) -> bool {
+        use rayon::prelude::*;
+
+        let children: Vec<(PathSegment<'a>, usize, &'a Value<'a>)> = match value
+        {
+            Value::Object(map) if map.len() >= PAR_MIN_CHILDREN => map
+                .iter()
+                .filter_map(|(key, val)| {
+                    let symbol_id = dfa.get_field_symbol_id(key);
+                    dfa.transition(current_state, symbol_id)
+                        .map(|next| (PathSegment::Field(key), next, val))
+                })
+                .collect(),
+            Value::Array(vals) if vals.len() >= PAR_MIN_CHILDREN => vals
+                .iter()
+                .enumerate()
+                .filter_map(|(idx, val)| {
+                    dfa.get_index_symbol_id(idx)
+                        .and_then(|id| dfa.transition(current_state, id))
+                        .map(|next| (PathSegment::Index(idx), next, val))
+                })
+                .collect(),
+            _ => return false,
+        };
+
+        // A few chunks per thread keeps the load balanced when subtrees
+        // differ in size.
+        let chunk_size =
+            children.len().div_ceil(rayon::current_num_threads() * 4).max(1);
+        let chunks: Vec<Matches<'a>> = children
+            .par_chunks(chunk_size)
+            .map(|chunk| {
+                let mut local = Matches::default();
+                for &(segment, next_state, val) in chunk {
+                    Self::traverse_arena::<true>(
+                        dfa,
+                        next_state,
+                        PathId::ROOT,
+                        Some(segment),
+                        val,
+                        &mut local,
+                    );
+                }
+                local
+            })
+            .collect();
+        for chunk in &chunks {
+            matches.append(chunk, path);
+        }
+        true
+    }
 }
 
 impl DFAQueryEngine {
@@ -919,16 +1343,7 @@ impl DFAQueryEngine {
         json: &'a Value<'a>,
         dfa: &QueryDFA,
     ) -> Vec<JSONPointer<'a>> {
-        let mut results = Vec::new();
-        let mut path = Vec::new();
-        Self::traverse_json(
-            dfa,
-            dfa.start_state,
-            &mut path,
-            json,
-            &mut results,
-        );
-        results
+        dfa.find_tree(json)
     }
 }
 
@@ -940,7 +1355,7 @@ mod tests {
 
     use super::*;
     use crate::query::QueryBuilder;
-    use crate::query::common::JSONPointer;
+    use crate::query::common::{JSONPointer, PathType};
 
     /// Creates the following simple JSON object for testing:
     /// ````
@@ -1042,8 +1457,8 @@ mod tests {
         assert_eq!(
             matches[0].path,
             vec![
-                PathType::Field(Rc::new("foo".to_string())),
-                PathType::Field(Rc::new("bar".to_string())),
+                PathType::Field(Arc::from("foo")),
+                PathType::Field(Arc::from("bar")),
             ]
         );
         assert_eq!(matches[0].value, &Value::Str(Cow::Borrowed("val")));
@@ -1365,23 +1780,20 @@ mod tests {
         // Expected [(root object), top level c object, c1, c2]
         assert_eq!(matches.len(), 4);
         assert_eq!(matches[0].value, &json); // the root object
-        assert_eq!(
-            matches[1].path,
-            vec![PathType::Field(Rc::from("c".to_string()))]
-        );
+        assert_eq!(matches[1].path, vec![PathType::Field(Arc::from("c"))]);
         assert_eq!(
             matches[2].path,
             vec![
-                PathType::Field(Rc::from("c".to_string())),
-                PathType::Field(Rc::from("c".to_string()))
+                PathType::Field(Arc::from("c")),
+                PathType::Field(Arc::from("c"))
             ]
         );
         assert_eq!(
             matches[3].path,
             vec![
-                PathType::Field(Rc::from("c".to_string())),
-                PathType::Field(Rc::from("c".to_string())),
-                PathType::Field(Rc::from("c".to_string()))
+                PathType::Field(Arc::from("c")),
+                PathType::Field(Arc::from("c")),
+                PathType::Field(Arc::from("c"))
             ]
         );
     }
@@ -1676,7 +2088,7 @@ mod tests {
         assert_eq!(matches.len(), 1);
         assert_eq!(
             matches[0].path,
-            vec![PathType::Field(Rc::new("/activities".to_string()))]
+            vec![PathType::Field(Arc::from("/activities"))]
         );
     }
 
@@ -1703,8 +2115,8 @@ mod tests {
         assert_eq!(
             matches[0].path,
             vec![
-                PathType::Field(Rc::new("paths".to_string())),
-                PathType::Field(Rc::new("/activities".to_string())),
+                PathType::Field(Arc::from("paths")),
+                PathType::Field(Arc::from("/activities")),
             ]
         );
     }
@@ -1733,8 +2145,8 @@ mod tests {
         assert_eq!(
             matches[0].path,
             vec![
-                PathType::Field(Rc::new("paths".to_string())),
-                PathType::Field(Rc::new("/activities".to_string())),
+                PathType::Field(Arc::from("paths")),
+                PathType::Field(Arc::from("/activities")),
             ]
         );
     }
@@ -2052,17 +2464,142 @@ mod tests {
         for m in &matches {
             match (&m.path[0], &m.path[2]) {
                 (PathType::Field(foo), PathType::Field(x)) => {
-                    assert_eq!(foo.as_str(), "foo");
-                    assert_eq!(x.as_str(), "x");
+                    assert_eq!(&**foo, "foo");
+                    assert_eq!(&**x, "x");
                     // Interned: strong count > 1 because the alphabet holds
-                    // the same Rc.
-                    assert!(Rc::strong_count(foo) > 1);
+                    // the same Arc.
+                    assert!(Arc::strong_count(foo) > 1);
                 }
                 other => panic!("unexpected path shape: {other:?}"),
             }
         }
     }
 
+    #[test]
+    fn find_matches_agrees_with_find() {
+        let input = r#"{ "a": [ {"b": 1}, {"c": {"b": 2}} ], "b": 3 }"#;
+        let json: Value = serde_json::from_str(input).expect("hardcoded json");
+
+        let dfa = QueryDFA::from_query_str("(* | [*])*").expect("valid query");
+        let expected = dfa.find(&json);
+        let matches = dfa.find_matches(&json);
+
+        assert_eq!(matches.len(), expected.len());
+        for (m, e) in matches.iter().zip(&expected) {
+            assert_eq!(m.to_path(), e.path);
+            assert_eq!(m.value, e.value);
+        }
+    }
+
+    #[test]
+    fn find_matches_arena_only_holds_match_ancestors() {
+        // Only `x.y` matches; the sibling subtree under `z` must not leave
+        // segments behind in the arena.
+        let input = r#"{ "z": { "y": { "q": 1 } }, "x": { "y": 2 } }"#;
+        let json: Value = serde_json::from_str(input).expect("hardcoded json");
+
+        let dfa = QueryDFA::from_query_str("*.y").expect("valid query");
+        let matches = dfa.find_matches(&json);
+
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches.arena.len(), 4);
+        assert_eq!(matches.get(1).unwrap().to_json_pointer(), "/x/y");
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_find_matches_agrees_with_find_matches() {
+        // A large array of objects, one of which holds a wide object, so
+        // both kinds of container are split, at different depths.
+        let wide: Vec<String> =
+            (0..1500).map(|i| format!(r#""k{i}": {{"b": {i}}}"#)).collect();
+        let mut items: Vec<String> =
+            (0..3000).map(|i| format!(r#"{{"b": {i}, "c": [{i}]}}"#)).collect();
+        items[1234] = format!("{{{}}}", wide.join(","));
+        let input = format!(r#"{{"items": [{}], "b": -1}}"#, items.join(","));
+        let json: Value = serde_json::from_str(&input).expect("generated json");
+
+        for query in ["(* | [*])*", "items[*].b", "items[1234].*.b", "**.b"] {
+            let dfa = QueryDFA::from_query_str(query).expect("valid query");
+            let expected = dfa.find_matches(&json);
+            let actual = dfa.par_find_matches(&json);
+            assert_eq!(actual.len(), expected.len(), "{query}");
+            for (a, e) in actual.iter().zip(expected.iter()) {
+                assert_eq!(a.to_path(), e.to_path(), "{query}");
+                assert!(std::ptr::eq(a.value, e.value), "{query}");
+            }
+        }
+    }
+
+    #[test]
+    fn count_and_is_match_agree_with_find() {
+        let input = r#"{ "a": [ {"b": 1}, {"c": {"b": 2}} ], "b": 3 }"#;
+        let json: Value = serde_json::from_str(input).expect("hardcoded json");
+
+        for query in ["(* | [*])*", "**.b", "a[*].b", "a[5]", ""] {
+            let dfa = QueryDFA::from_query_str(query).expect("valid query");
+            let expected = dfa.find(&json).len();
+            assert_eq!(dfa.count(&json), expected, "{query}");
+            assert_eq!(dfa.is_match(&json), expected > 0, "{query}");
+        }
+    }
+
+    #[test]
+    fn deeply_nested_document_does_not_overflow_stack() {
+        // `[[[...[1]...]]]`, far deeper than any call stack could recurse.
+        const DEPTH: usize = 200_000;
+        let mut json = Value::Number(1u64.into());
+        for _ in 0..DEPTH {
+            json = Value::Array(vec![json]);
+        }
+
+        // Walks every level without matching (`find` clones the full path
+        // per match, which would be quadratic here).
+        let dead_end = QueryDFA::from_query_str("[*]*.x").expect("valid query");
+        assert!(dead_end.find(&json).is_empty());
+
+        let dfa = QueryDFA::from_query_str("[*]*").expect("valid query");
+        assert_eq!(dfa.count(&json), DEPTH + 1);
+        let matches = dfa.find_matches(&json);
+        assert_eq!(matches.len(), DEPTH + 1);
+        assert_eq!(matches.get(DEPTH).unwrap().segments().len(), DEPTH);
+
+        // Dropping the value would itself recurse once per level, so take
+        // it apart from the outside in.
+        drop(matches);
+        while let Value::Array(mut items) = json {
+            json = items.pop().unwrap();
+        }
+    }
+
+    #[test]
+    fn compiled_query_and_matches_are_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<QueryDFA>();
+        assert_send_sync::<PathType>();
+        assert_send_sync::<JSONPointer<'static>>();
+    }
+
+    #[test]
+    fn shared_dfa_searches_from_many_threads() {
+        let dfa = Arc::new(
+            QueryDFA::from_query_str("(* | [*])*.id").expect("valid query"),
+        );
+        std::thread::scope(|scope| {
+            for n in 0..4u64 {
+                let dfa = Arc::clone(&dfa);
+                scope.spawn(move || {
+                    let input = format!(r#"{{ "items": [ {{"id": {n}}} ] }}"#);
+                    let json: Value =
+                        serde_json::from_str(&input).expect("valid json");
+                    let matches = dfa.find(&json);
+                    assert_eq!(matches.len(), 1);
+                    assert_eq!(matches[0].value, &Value::Number(n.into()));
+                });
+            }
+        });
+    }
+
     #[test]
     fn case_insensitive_from_query_str() {
         let input = r#"{ "Foo": { "BAR": "found" } }"#;
@@ -0,0 +1,555 @@
+/*!
+# Document Index
+
+A pre-built index for running many queries against one document.
+
+Every [`QueryDFA::find`] walks the document from the root, so a linter that
+runs hundreds of queries against one large document walks it hundreds of
+times. A [`DocumentIndex`] is built once per document and records:
+
+- every node in document (pre-)order, with its parent and the edge leading
+  to it, so that each subtree is a contiguous range of node ids;
+- an inverted index from each object key to the nodes reached through it.
+
+Together these answer "does this subtree contain key `k` anywhere?" with a
+binary search. [`QueryDFA::find_indexed`] uses that to skip subtrees that
+lack a key every remaining match needs (e.g. everything outside `a` for
+`a.b`, or every subtree without a `foo` for `**.foo`), and, for
+suffix-anchored queries such as `(* | [*])*.foo` where a match is always
+reached through one of a few keys, jumps straight to the nodes under those
+keys instead of walking the tree at all.
+
+# Examples
+
+```
+use jsongrep::{Value, query::{DocumentIndex, QueryDFA}};
+
+let json: Value =
+    serde_json::from_str(r#"{"a": {"foo": 1}, "b": [{"foo": 2}, {"bar": 3}]}"#)
+        .unwrap();
+let index = DocumentIndex::build(&json);
+
+for query in ["(* | [*])*.foo", "b[*].bar", "a"] {
+    let dfa = QueryDFA::from_query_str(query).unwrap();
+    assert_eq!(dfa.find_indexed(&index), dfa.find(&json));
+}
+```
+*/
+use serde_json_borrow::Value;
+use std::collections::HashMap;
+
+use crate::query::{
+    common::{JSONPointer, PathType, TransitionLabel},
+    dfa::QueryDFA,
+};
+
+/// How a node was reached from its parent.
+#[derive(Debug, Clone, Copy)]
+enum IndexEdge<'a> {
+    /// The root has no parent.
+    Root,
+    /// Reached through an object key.
+    Field(&'a str),
+    /// Reached through an array index.
+    Index(usize),
+}
+
+/// One node of a [`DocumentIndex`].
+#[derive(Debug)]
+struct IndexNode<'a> {
+    value: &'a Value<'a>,
+    /// Id of the parent node; the root is its own parent.
+    parent: usize,
+    edge: IndexEdge<'a>,
+    /// One past the id of the last node in this subtree. The subtree of
+    /// node `n` is exactly the ids `n..end`.
+    end: usize,
+    /// Number of edges from the root.
+    depth: usize,
+}
+
+/// A key → node inverted index over one document, for running many queries
+/// against it with [`QueryDFA::find_indexed`].
+///
+/// Building the index walks the document once and costs a few words per
+/// node; it borrows the document, so it lives no longer than it.
+#[derive(Debug)]
+pub struct DocumentIndex<'a> {
+    /// Every node, in document order; the root is node `0`.
+    nodes: Vec<IndexNode<'a>>,
+    /// Ids of the nodes reached through each key, in ascending order.
+    keys: HashMap<&'a str, Vec<usize>>,
+}
+
+impl<'a> DocumentIndex<'a> {
+    /// Index `json`.
+    ///
+    /// Containers are kept on an explicit stack, so arbitrarily deep
+    /// documents are indexed without overflowing the call stack.
+    #[must_use]
+    pub fn build(json: &'a Value<'a>) -> Self {
+        let mut index = Self {
+            nodes: vec![IndexNode {
+                value: json,
+                parent: 0,
+                edge: IndexEdge::Root,
+                end: 0,
+                depth: 0,
+            }],
+            keys: HashMap::new(),
+        };
+        // (node id, index of the next child) for each open container.
+        let mut stack = vec![(0, 0)];
+
+        while let Some((id, next)) = stack.last_mut() {
+            let parent = *id;
+            let Some((edge, child)) =
+                child_at(index.nodes[parent].value, *next)
+            else {
+                index.nodes[parent].end = index.nodes.len();
+                stack.pop();
+                continue;
+            };
+            *next += 1;
+
+            let child_id = index.nodes.len();
+            if let IndexEdge::Field(key) = edge {
+                index.keys.entry(key).or_default().push(child_id);
+            }
+            index.nodes.push(IndexNode {
+                value: child,
+                parent,
+                edge,
+                end: 0,
+                depth: index.nodes[parent].depth + 1,
+            });
+            stack.push((child_id, 0));
+        }
+
+        index
+    }
+
+    /// The number of nodes in the document, including the root.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Always `false`: a document has at least its root.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// The indexed document.
+    #[must_use]
+    pub fn root(&self) -> &'a Value<'a> {
+        self.nodes[0].value
+    }
+
+    /// Whether `key` appears anywhere in the document.
+    #[must_use]
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.keys.contains_key(key)
+    }
+
+    /// Every value stored under `key`, at any depth, in document order.
+    pub fn values_with_key(
+        &self,
+        key: &str,
+    ) -> impl Iterator<Item = &'a Value<'a>> + '_ {
+        self.keys.get(key).into_iter().flatten().map(|&id| self.nodes[id].value)
+    }
+
+    /// Whether `key` appears strictly below node `id`.
+    fn subtree_has_key(&self, id: usize, key: &str) -> bool {
+        self.keys.get(key).is_some_and(|ids| {
+            let first_after = ids.partition_point(|&other| other <= id);
+            ids.get(first_after)
+                .is_some_and(|&other| other < self.nodes[id].end)
+        })
+    }
+
+    /// The number of DFA steps needed to check every node under `keys`.
+    fn candidate_cost(&self, keys: &[&str]) -> usize {
+        keys.iter()
+            .filter_map(|key| self.keys.get(key))
+            .flatten()
+            .map(|&id| self.nodes[id].depth)
+            .sum()
+    }
+
+    /// Ids of the nodes on the path from the root (exclusive) to `id`
+    /// (inclusive), appended to `out` in root-to-node order.
+    fn path_ids(&self, mut id: usize, out: &mut Vec<usize>) {
+        let start = out.len();
+        while id != 0 {
+            out.push(id);
+            id = self.nodes[id].parent;
+        }
+        out[start..].reverse();
+    }
+}
+
+/// The `i`-th child of `value` and the edge leading to it, if any.
+fn child_at<'a>(
+    value: &'a Value<'a>,
+    i: usize,
+) -> Option<(IndexEdge<'a>, &'a Value<'a>)> {
+    match value {
+        Value::Object(obj) => obj
+            .get_key_value_at(i)
+            .map(|(key, child)| (IndexEdge::Field(key), child)),
+        Value::Array(arr) => {
+            arr.get(i).map(|child| (IndexEdge::Index(i), child))
+        }
+        _ => None,
+    }
+}
+
+/// What [`QueryDFA::find_indexed`] derives from a DFA before searching.
+struct IndexPlan<'q> {
+    /// Whether an accepting state is reachable from each state.
+    live: Vec<bool>,
+    /// For each state, the keys that every path from it to an accepting
+    /// state steps through; a subtree lacking any of them cannot match.
+    required: Vec<Vec<&'q str>>,
+    /// When every transition into an accepting state is on a named key,
+    /// those keys: apart from the root, only nodes reached through one of
+    /// them can match.
+    final_keys: Option<Vec<&'q str>>,
+}
+
+impl<'q> IndexPlan<'q> {
+    fn new(dfa: &'q QueryDFA) -> Self {
+        // Predecessors of each state, with the symbol stepped on.
+        let mut incoming = vec![Vec::new(); dfa.num_states];
+        for (from, row) in dfa.transitions.iter().enumerate() {
+            for (symbol, to) in row.iter().enumerate() {
+                if let Some(to) = *to {
+                    incoming[to].push((from, symbol));
+                }
+            }
+        }
+
+        let live = Self::reaches_accepting(dfa, &incoming, None);
+        let mut required = vec![Vec::new(); dfa.num_states];
+        let mut final_keys = Some(Vec::new());
+
+        // Case-insensitive queries match keys the index doesn't store
+        // verbatim, so keys can neither prune nor be jumped to.
+        if dfa.case_insensitive {
+            return Self { live, required, final_keys: None };
+        }
+
+        for (symbol, label) in dfa.alphabet.iter().enumerate() {
+            let TransitionLabel::Field(key) = label else {
+                continue;
+            };
+            let without = Self::reaches_accepting(dfa, &incoming, Some(symbol));
+            for (state, keys) in required.iter_mut().enumerate() {
+                if live[state] && !without[state] {
+                    keys.push(&**key);
+                }
+            }
+        }
+
+        for (state, preds) in incoming.iter().enumerate() {
+            if !dfa.is_accepting_state(state) {
+                continue;
+            }
+            for &(_, symbol) in preds {
+                match (&mut final_keys, dfa.alphabet.get(symbol)) {
+                    (Some(keys), Some(TransitionLabel::Field(key))) => {
+                        if !keys.contains(&&**key) {
+                            keys.push(&**key);
+                        }
+                    }
+                    _ => final_keys = None,
+                }
+            }
+        }
+
+        Self { live, required, final_keys }
+    }
+
+    /// Which states can reach an accepting state without stepping on
+    /// `avoid`.
+    fn reaches_accepting(
+        dfa: &QueryDFA,
+        incoming: &[Vec<(usize, usize)>],
+        avoid: Option<usize>,
+    ) -> Vec<bool> {
+        let mut reaches: Vec<bool> =
+            (0..dfa.num_states).map(|s| dfa.is_accepting_state(s)).collect();
+        let mut queue: Vec<usize> =
+            (0..dfa.num_states).filter(|&s| reaches[s]).collect();
+        while let Some(state) = queue.pop() {
+            for &(from, symbol) in &incoming[state] {
+                if Some(symbol) != avoid && !reaches[from] {
+                    reaches[from] = true;
+                    queue.push(from);
+                }
+            }
+        }
+        reaches
+    }
+}
+
+impl QueryDFA {
+    /// Execute this compiled query against an indexed document.
+    ///
+    /// Returns the same matches, in the same order, as [`QueryDFA::find`] on
+    /// the indexed document, but uses the index to skip subtrees that
+    /// cannot match and, for queries whose matches are always reached
+    /// through a few named keys, to visit only the nodes under those keys.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jsongrep::{Value, query::{DocumentIndex, QueryDFA}};
+    ///
+    /// let json: Value =
+    ///     serde_json::from_str(r#"{"a": [{"id": 1}, {"id": 2}]}"#).unwrap();
+    /// let index = DocumentIndex::build(&json);
+    ///
+    /// let dfa = QueryDFA::from_query_str("**.id").unwrap();
+    /// assert_eq!(dfa.find_indexed(&index).len(), 0); // `**` skips arrays
+    ///
+    /// let dfa = QueryDFA::from_query_str("(* | [*])*.id").unwrap();
+    /// assert_eq!(dfa.find_indexed(&index).len(), 2);
+    /// ```
+    #[must_use]
+    pub fn find_indexed<'a>(
+        &self,
+        index: &DocumentIndex<'a>,
+    ) -> Vec<JSONPointer<'a>> {
+        let plan = IndexPlan::new(self);
+
+        let ids = match plan.final_keys.as_deref() {
+            // Checking a candidate costs a DFA step per edge above it, so
+            // only jump when that undercuts walking the whole document.
+            Some(keys) if index.candidate_cost(keys) < index.len() => {
+                self.find_candidates(index, keys)
+            }
+            _ => self.walk_index(index, &plan),
+        };
+
+        ids.into_iter()
+            .map(|id| JSONPointer {
+                path: self.index_path(index, id),
+                value: index.nodes[id].value,
+            })
+            .collect()
+    }
+
+    /// Ids of the matching nodes among the root and the nodes under `keys`,
+    /// in document order.
+    fn find_candidates(
+        &self,
+        index: &DocumentIndex<'_>,
+        keys: &[&str],
+    ) -> Vec<usize> {
+        let mut candidates: Vec<usize> = keys
+            .iter()
+            .filter_map(|key| index.keys.get(key))
+            .flatten()
+            .copied()
+            .collect();
+        if self.is_accepting_state(self.start_state) {
+            candidates.push(0);
+        }
+        // Each node has one incoming edge, so there are no duplicates.
+        candidates.sort_unstable();
+
+        let mut path = Vec::new();
+        candidates.retain(|&id| {
+            path.clear();
+            index.path_ids(id, &mut path);
+            path.iter()
+                .try_fold(self.start_state, |state, &step| {
+                    self.step(state, index.nodes[step].edge)
+                })
+                .is_some_and(|state| self.is_accepting_state(state))
+        });
+        candidates
+    }
+
+    /// Ids of the matching nodes, in document order, found by walking the
+    /// index from the root and skipping subtrees the plan rules out.
+    fn walk_index(
+        &self,
+        index: &DocumentIndex<'_>,
+        plan: &IndexPlan<'_>,
+    ) -> Vec<usize> {
+        let mut results = Vec::new();
+        // (state, next child id, end of the subtree) for each open node.
+        let mut stack = Vec::new();
+        let mut pending = Some((self.start_state, 0));
+
+        loop {
+            if let Some((state, id)) = pending.take() {
+                if self.is_accepting_state(state) {
+                    results.push(id);
+                }
+                let node = &index.nodes[id];
+                let prunable = !plan.live[state]
+                    || plan.required[state]
+                        .iter()
+                        .any(|key| !index.subtree_has_key(id, key));
+                if !prunable && node.end > id + 1 {
+                    stack.push((state, id + 1, node.end));
+                }
+            }
+
+            let Some((state, next, end)) = stack.last_mut() else {
+                return results;
+            };
+            if *next == *end {
+                stack.pop();
+                continue;
+            }
+            let child = *next;
+            *next = index.nodes[child].end;
+            if let Some(next_state) = self.step(*state, index.nodes[child].edge)
+            {
+                pending = Some((next_state, child));
+            }
+        }
+    }
+
+    /// The state reached from `state` by stepping along `edge`.
+    fn step(&self, state: usize, edge: IndexEdge<'_>) -> Option<usize> {
+        let symbol = match edge {
+            IndexEdge::Root => return Some(state),
+            IndexEdge::Field(key) => self.get_field_symbol_id(key),
+            IndexEdge::Index(idx) => self.get_index_symbol_id(idx)?,
+        };
+        self.transition(state, symbol)
+    }
+
+    /// The match path of node `id`.
+    fn index_path(
+        &self,
+        index: &DocumentIndex<'_>,
+        id: usize,
+    ) -> Vec<PathType> {
+        let mut ids = Vec::new();
+        index.path_ids(id, &mut ids);
+        ids.into_iter()
+            .map(|step| match index.nodes[step].edge {
+                IndexEdge::Field(key) => PathType::Field(
+                    self.path_key(self.get_field_symbol_id(key), key),
+                ),
+                IndexEdge::Index(idx) => PathType::Index(idx),
+                IndexEdge::Root => unreachable!("the root is not on a path"),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Unit testing.")]
+mod tests {
+    use super::*;
+
+    const DOC: &str = r#"{
+        "a": {"foo": 1, "b": {"foo": {"foo": 2}}},
+        "list": [{"foo": 3}, {"bar": 4}, [{"foo": 5}]],
+        "foo": [6, 7],
+        "bar": {"baz": null}
+    }"#;
+
+    #[test]
+    fn build_records_every_node_in_document_order() {
+        let json: Value = serde_json::from_str(DOC).unwrap();
+        let index = DocumentIndex::build(&json);
+
+        assert_eq!(index.len(), 19);
+        assert_eq!(index.nodes[0].end, index.len());
+        assert!(index.contains_key("baz"));
+        assert!(!index.contains_key("qux"));
+
+        let foos: Vec<_> = index
+            .values_with_key("foo")
+            .map(|v| serde_json::to_string(v).unwrap())
+            .collect();
+        assert_eq!(foos, ["1", r#"{"foo":2}"#, "2", "3", "5", "[6,7]"]);
+    }
+
+    #[test]
+    fn find_indexed_agrees_with_find() {
+        let json: Value = serde_json::from_str(DOC).unwrap();
+        let index = DocumentIndex::build(&json);
+
+        for query in [
+            "",
+            "a",
+            "a.b.foo",
+            "**.foo",
+            "(* | [*])*.foo",
+            "(* | [*])*.foo.foo",
+            "(* | [*])*",
+            "list[*].foo",
+            "list[1:].bar",
+            "list[2][0]",
+            "foo[*]",
+            "bar.*",
+            "missing.foo",
+            "a | bar.baz",
+            "**.b?.foo",
+        ] {
+            let dfa = QueryDFA::from_query_str(query).unwrap();
+            assert_eq!(dfa.find_indexed(&index), dfa.find(&json), "{query}");
+        }
+    }
+
+    #[test]
+    fn case_insensitive_queries_skip_key_pruning() {
+        let json: Value =
+            serde_json::from_str(r#"{"A": {"Foo": 1}, "b": {"FOO": 2}}"#)
+                .unwrap();
+        let index = DocumentIndex::build(&json);
+        let query = crate::query::QueryBuilder::new()
+            .field_wildcard()
+            .field("foo")
+            .build();
+        let dfa = QueryDFA::from_query_ignore_case(&query);
+        assert_eq!(dfa.find_indexed(&index), dfa.find(&json));
+        assert_eq!(dfa.find_indexed(&index).len(), 2);
+    }
+
+    #[test]
+    fn plan_prunes_and_jumps_by_key() {
+        let dfa = QueryDFA::from_query_str("(* | [*])*.foo").unwrap();
+        let plan = IndexPlan::new(&dfa);
+        assert_eq!(plan.final_keys, Some(vec!["foo"]));
+        assert_eq!(plan.required[dfa.start_state], ["foo"]);
+
+        // A match can also end on an index, so there is nothing to jump to.
+        let dfa = QueryDFA::from_query_str("a.b[*]").unwrap();
+        let plan = IndexPlan::new(&dfa);
+        assert_eq!(plan.final_keys, None);
+        assert_eq!(plan.required[dfa.start_state], ["a", "b"]);
+    }
+
+    #[test]
+    fn deeply_nested_document_does_not_overflow_stack() {
+        const DEPTH: usize = 200_000;
+        let mut json = Value::Null;
+        for _ in 0..DEPTH {
+            json = Value::Object(vec![("k", json)].into());
+        }
+        let index = DocumentIndex::build(&json);
+        assert_eq!(index.len(), DEPTH + 1);
+
+        // No `x` anywhere, so the walk stops at the root.
+        let dfa = QueryDFA::from_query_str("**.x").unwrap();
+        assert!(dfa.find_indexed(&index).is_empty());
+        drop(index);
+
+        while let Value::Object(obj) = json {
+            json = obj.into_vec().pop().unwrap().1;
+        }
+    }
+}
@@ -0,0 +1,307 @@
+/*!
+# Search Limits
+
+Budgets for running queries over untrusted documents.
+
+[`QueryDFA::from_query_bounded`] caps the cost of *compiling* a query, but a
+compiled query can still be run against an adversarial document: one with
+millions of matches, deeply nested arrays, or simply so many nodes that a
+search blows a request's latency budget. [`QueryDFA::find_with_limits`]
+runs a search under a [`SearchLimits`] and, when a limit is hit, stops and
+returns the matches found so far together with the [`LimitReason`].
+
+# Examples
+
+```
+use jsongrep::{Value, query::{LimitReason, QueryDFA, SearchLimits}};
+
+let json: Value = serde_json::from_str(r#"{"a": [1, 2, 3, 4]}"#).unwrap();
+let dfa = QueryDFA::from_query_str("a[*]").unwrap();
+
+let limits = SearchLimits { max_matches: Some(2), ..SearchLimits::default() };
+let found = dfa.find_with_limits(&json, &limits);
+
+assert_eq!(found.results.len(), 2);
+assert_eq!(found.stopped, Some(LimitReason::MaxMatches));
+```
+*/
+use serde_json_borrow::Value;
+use std::{fmt::Display, time::Instant};
+
+use crate::query::{
+    common::{JSONPointer, PathType},
+    dfa::QueryDFA,
+    tree::{JsonTree, open_children},
+};
+
+/// The deadline is only checked once per this many visited nodes, since
+/// reading the clock costs far more than a DFA step.
+const DEADLINE_CHECK_INTERVAL: usize = 1024;
+
+/// Bounds on a single search; `None` means unbounded. The default is fully
+/// unbounded.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchLimits {
+    /// Stop once this many matches have been collected.
+    pub max_matches: Option<usize>,
+    /// Stop after visiting this many document nodes, matched or not. Only
+    /// nodes the DFA can step into are visited; dead subtrees are skipped
+    /// without being counted.
+    pub max_nodes_visited: Option<usize>,
+    /// Stop on reaching a node nested deeper than this; the root is at
+    /// depth 0.
+    pub max_depth: Option<usize>,
+    /// Stop once this instant has passed. Checked every 1024 nodes.
+    pub deadline: Option<Instant>,
+}
+
+impl SearchLimits {
+    /// Whether no limit is set.
+    #[must_use]
+    pub const fn is_unbounded(&self) -> bool {
+        self.max_matches.is_none()
+            && self.max_nodes_visited.is_none()
+            && self.max_depth.is_none()
+            && self.deadline.is_none()
+    }
+}
+
+/// Which limit of a [`SearchLimits`] ended a search early.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum LimitReason {
+    /// A further match was found after `max_matches` were collected.
+    MaxMatches,
+    /// `max_nodes_visited` nodes were visited.
+    MaxNodesVisited,
+    /// A node deeper than `max_depth` was reached.
+    MaxDepth,
+    /// The `deadline` passed.
+    Deadline,
+}
+
+impl Display for LimitReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MaxMatches => write!(f, "match limit reached"),
+            Self::MaxNodesVisited => write!(f, "node visit limit reached"),
+            Self::MaxDepth => write!(f, "depth limit reached"),
+            Self::Deadline => write!(f, "search deadline passed"),
+        }
+    }
+}
+
+/// The result of [`QueryDFA::find_with_limits`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LimitedResults<'a, T = Value<'a>> {
+    /// The matches found, in document order. When the search stopped
+    /// early, this is every match found before the limit was hit.
+    pub results: Vec<JSONPointer<'a, T>>,
+    /// Why the search stopped early, or `None` if it ran to completion.
+    pub stopped: Option<LimitReason>,
+    /// How many document nodes were visited.
+    pub nodes_visited: usize,
+}
+
+impl<T> LimitedResults<'_, T> {
+    /// Whether the search ran to completion, i.e. `results` holds every
+    /// match.
+    #[must_use]
+    pub const fn is_complete(&self) -> bool {
+        self.stopped.is_none()
+    }
+}
+
+impl QueryDFA {
+    /// Execute this compiled query against a document under `limits`.
+    ///
+    /// Behaves like [`QueryDFA::find_tree`] until a limit is hit, then stops
+    /// and returns the matches found so far along with the reason. The
+    /// match limit only counts as hit when a match beyond it exists, so a
+    /// document with exactly `max_matches` matches completes normally.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jsongrep::{Value, query::{LimitReason, QueryDFA, SearchLimits}};
+    ///
+    /// let json: Value = serde_json::from_str(r#"[[[[1]]], 2]"#).unwrap();
+    /// let dfa = QueryDFA::from_query_str("[*]*").unwrap();
+    ///
+    /// let limits = SearchLimits { max_depth: Some(2), ..Default::default() };
+    /// let found = dfa.find_with_limits(&json, &limits);
+    /// assert_eq!(found.stopped, Some(LimitReason::MaxDepth));
+    /// assert_eq!(found.results.len(), 3); // root, [0], [0][0]
+    /// ```
+    #[must_use]
+    pub fn find_with_limits<'a, T: JsonTree>(
+        &self,
+        json: &'a T,
+        limits: &SearchLimits,
+    ) -> LimitedResults<'a, T> {
+        let mut search = LimitedSearch {
+            dfa: self,
+            limits,
+            nodes_visited: 0,
+            path: Vec::new(),
+            results: Vec::new(),
+        };
+        let stopped = search.run(json).err();
+        LimitedResults {
+            results: search.results,
+            stopped,
+            nodes_visited: search.nodes_visited,
+        }
+    }
+}
+
+/// State for one [`QueryDFA::find_with_limits`] call.
+struct LimitedSearch<'a, 'q, T> {
+    dfa: &'q QueryDFA,
+    limits: &'q SearchLimits,
+    nodes_visited: usize,
+    path: Vec<PathType>,
+    results: Vec<JSONPointer<'a, T>>,
+}
+
+impl<'a, T: JsonTree> LimitedSearch<'a, '_, T> {
+    /// Depth-first search from `root`, as in [`QueryDFA::find_tree`],
+    /// bailing out with the reason once a limit is hit.
+    fn run(&mut self, root: &'a T) -> Result<(), LimitReason> {
+        // (state, remaining children) of each open container; its length is
+        // the depth of the next node visited.
+        let mut stack = Vec::new();
+        let mut pending = Some((self.dfa.start_state, root));
+
+        loop {
+            if let Some((state, value)) = pending.take() {
+                self.check_visit(stack.len())?;
+
+                if self.dfa.is_accepting_state(state) {
+                    if self
+                        .limits
+                        .max_matches
+                        .is_some_and(|m| self.results.len() >= m)
+                    {
+                        return Err(LimitReason::MaxMatches);
+                    }
+                    self.results
+                        .push(JSONPointer { path: self.path.clone(), value });
+                }
+
+                match open_children(value) {
+                    Some(children) => stack.push((state, children)),
+                    None => {
+                        self.path.pop();
+                    }
+                }
+            }
+
+            let Some((state, children)) = stack.last_mut() else {
+                return Ok(());
+            };
+            match children.next_step(self.dfa, *state) {
+                Some(Some((next_state, edge, child))) => {
+                    self.path.push(edge.to_path(self.dfa));
+                    pending = Some((next_state, child));
+                }
+                Some(None) => {}
+                None => {
+                    stack.pop();
+                    self.path.pop();
+                }
+            }
+        }
+    }
+
+    /// Accounts for visiting one node at `depth` and checks the node, depth
+    /// and time limits.
+    fn check_visit(&mut self, depth: usize) -> Result<(), LimitReason> {
+        if self
+            .limits
+            .max_nodes_visited
+            .is_some_and(|m| self.nodes_visited >= m)
+        {
+            return Err(LimitReason::MaxNodesVisited);
+        }
+        if self.limits.max_depth.is_some_and(|m| depth > m) {
+            return Err(LimitReason::MaxDepth);
+        }
+        if let Some(deadline) = self.limits.deadline
+            && self.nodes_visited.is_multiple_of(DEADLINE_CHECK_INTERVAL)
+            && Instant::now() >= deadline
+        {
+            return Err(LimitReason::Deadline);
+        }
+        self.nodes_visited += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Unit testing.")]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const DOC: &str = r#"{"a": [1, 2, 3], "b": {"c": {"d": 4}}}"#;
+
+    fn search(
+        query: &str,
+        limits: &SearchLimits,
+    ) -> (usize, Option<LimitReason>) {
+        let json: Value = serde_json::from_str(DOC).unwrap();
+        let dfa = QueryDFA::from_query_str(query).unwrap();
+        let found = dfa.find_with_limits(&json, limits);
+        (found.results.len(), found.stopped)
+    }
+
+    #[test]
+    fn unbounded_agrees_with_find() {
+        let json: Value = serde_json::from_str(DOC).unwrap();
+        let dfa = QueryDFA::from_query_str("(* | [*])*").unwrap();
+        let found = dfa.find_with_limits(&json, &SearchLimits::default());
+        assert!(found.is_complete());
+        assert_eq!(found.results, dfa.find(&json));
+        assert_eq!(found.nodes_visited, 8);
+    }
+
+    #[test]
+    fn max_matches_only_trips_when_a_match_is_withheld() {
+        let exact = SearchLimits { max_matches: Some(3), ..Default::default() };
+        assert_eq!(search("a[*]", &exact), (3, None));
+
+        let fewer = SearchLimits { max_matches: Some(2), ..Default::default() };
+        assert_eq!(search("a[*]", &fewer), (2, Some(LimitReason::MaxMatches)));
+
+        let zero = SearchLimits { max_matches: Some(0), ..Default::default() };
+        assert_eq!(search("a[*]", &zero), (0, Some(LimitReason::MaxMatches)));
+    }
+
+    #[test]
+    fn max_nodes_visited_returns_partial_results() {
+        // Visits the root, `a`, then `a[0]` before the budget runs out.
+        let limits =
+            SearchLimits { max_nodes_visited: Some(3), ..Default::default() };
+        assert_eq!(
+            search("(* | [*])*", &limits),
+            (3, Some(LimitReason::MaxNodesVisited))
+        );
+    }
+
+    #[test]
+    fn max_depth_stops_before_deep_nodes() {
+        let limits = SearchLimits { max_depth: Some(2), ..Default::default() };
+        assert_eq!(search("a[*]", &limits), (3, None));
+        assert_eq!(search("b.c.d", &limits), (0, Some(LimitReason::MaxDepth)));
+    }
+
+    #[test]
+    fn past_deadline_stops_immediately() {
+        let limits = SearchLimits {
+            deadline: Instant::now().checked_sub(Duration::from_secs(1)),
+            ..Default::default()
+        };
+        assert_eq!(search("a", &limits), (0, Some(LimitReason::Deadline)));
+    }
+}
@@ -14,7 +14,7 @@ For reference:
 
 - [Wikipedia: Glushkov's construction algorithm](https://en.wikipedia.org/wiki/Glushkov's_construction_algorithm)
 */
-use std::{fmt::Display, rc::Rc};
+use std::{fmt::Display, sync::Arc};
 
 use crate::query::{ast::Query, common::TransitionLabel};
 
@@ -216,8 +216,8 @@ impl QueryNFA {
         match query {
             Query::Field(name) => {
                 // create a new key state if it does not exist
-                let name_rc: Rc<String> = Rc::new(name.clone());
-                self.pos_to_label.push(TransitionLabel::Field(name_rc));
+                let name_arc: Arc<str> = Arc::from(name.as_str());
+                self.pos_to_label.push(TransitionLabel::Field(name_arc));
             }
             Query::FieldWildcard => {
                 let field_wildcard = TransitionLabel::FieldWildcard;
@@ -0,0 +1,776 @@
+/*!
+# Streaming Search
+
+A search engine that drives a [`QueryDFA`] directly over JSON text, without
+first building a [`Value`] tree for the whole document.
+
+[`QueryDFA::find`] needs the fully parsed document, so searching a 20 GB
+export takes 20+ GB of memory even for a query like `features[*].id`. The
+streaming engine instead tokenizes the input one token at a time and feeds
+each object key and array index to the DFA:
+
+- subtrees whose DFA state is dead are skipped: they are checked against the
+  JSON grammar like the rest of the input, but no value is built for them;
+- containers whose state is live are descended into, keeping only a stack of
+  open containers and the current path;
+- only values under accepting states are returned, as raw text spans into the
+  input that can be parsed on demand with [`StreamMatch::parse`].
+
+Memory use is therefore proportional to the nesting depth plus the matches,
+not the document size. The input is a borrowed buffer, which is typically a
+memory-mapped file: the OS pages the file in and out as the scan proceeds.
+
+# Examples
+
+```
+use jsongrep::query::QueryDFA;
+
+let input = r#"{"features": [{"id": 1}, {"id": 2, "skip": [[[]]]}]}"#;
+let dfa = QueryDFA::from_query_str("features[*].id").unwrap();
+
+let matches = dfa.find_stream(input).unwrap();
+assert_eq!(matches.len(), 2);
+assert_eq!(matches[1].raw, "2");
+assert_eq!(matches[1].parse().unwrap().to_string(), "2");
+```
+*/
+use serde_json_borrow::Value;
+use std::{borrow::Cow, fmt::Display, ops::ControlFlow};
+
+use crate::query::{QueryDFA, common::PathType};
+
+/// A match found by the streaming engine: the path to the value plus the raw
+/// JSON text of the value in the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamMatch<'a> {
+    /// The path to the value in the JSON document.
+    pub path: Vec<PathType>,
+    /// The JSON text of the matched value, borrowed from the input.
+    pub raw: &'a str,
+    /// Byte offset of the value in the input.
+    pub offset: usize,
+}
+
+impl<'a> StreamMatch<'a> {
+    /// Parse the matched text into a [`Value`] borrowing from the input.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the matched text is not valid JSON, which cannot
+    /// happen for matches of the streaming engine: it validates the whole
+    /// input as it scans it.
+    pub fn parse(&self) -> serde_json::Result<Value<'a>> {
+        serde_json::from_str(self.raw)
+    }
+}
+
+/// Error returned when the streaming engine cannot tokenize its input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct StreamError {
+    /// Byte offset in the input at which the error was detected.
+    pub offset: usize,
+    /// What was expected or found at `offset`.
+    pub message: &'static str,
+}
+
+impl std::error::Error for StreamError {}
+
+impl Display for StreamError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid JSON at byte {}: {}", self.offset, self.message)
+    }
+}
+
+impl QueryDFA {
+    /// Search JSON text with the streaming engine, returning all matches in
+    /// document order.
+    ///
+    /// Unlike [`QueryDFA::find`], this does not parse the whole document:
+    /// subtrees the query cannot match are skipped, and matched values are
+    /// returned as raw text spans (see the [module docs](self)).
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StreamError`] if the input is not well-formed JSON,
+    /// including in the subtrees the query skips, so an input is accepted
+    /// exactly when [`QueryDFA::find`] could parse it.
+    pub fn find_stream<'a>(
+        &self,
+        input: &'a str,
+    ) -> Result<Vec<StreamMatch<'a>>, StreamError> {
+        let mut results = Vec::new();
+        self.find_stream_with(input, |m| results.push(m))?;
+        Ok(results)
+    }
+
+    /// Callback form of [`QueryDFA::find_stream`]: `on_match` receives each
+    /// match in document order.
+    ///
+    /// A match on a container can only be reported once the container is
+    /// closed, so matches nested inside another match are held back until
+    /// the outermost one is complete; all other matches are reported as
+    /// soon as their value ends.
+    ///
+    /// # Errors
+    ///
+    /// See [`QueryDFA::find_stream`]. Matches reported before the error
+    /// was detected have already been passed to `on_match`.
+    pub fn find_stream_with<'a, F>(
+        &self,
+        input: &'a str,
+        mut on_match: F,
+    ) -> Result<(), StreamError>
+    where
+        F: FnMut(StreamMatch<'a>),
+    {
+        self.try_find_stream_with(input, |m| {
+            on_match(m);
+            ControlFlow::Continue(())
+        })
+    }
+
+    /// Like [`QueryDFA::find_stream_with`], but the search stops as soon as
+    /// `on_match` returns [`ControlFlow::Break`], e.g. once enough matches
+    /// were found. The rest of the input is then not read, so errors in it
+    /// go unreported.
+    ///
+    /// # Errors
+    ///
+    /// See [`QueryDFA::find_stream`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jsongrep::query::QueryDFA;
+    /// use std::ops::ControlFlow;
+    ///
+    /// let dfa = QueryDFA::from_query_str("[*]").unwrap();
+    /// let mut first = None;
+    /// dfa.try_find_stream_with("[1, 2, oops", |m| {
+    ///     first = Some(m.raw);
+    ///     ControlFlow::Break(())
+    /// })
+    /// .unwrap();
+    /// assert_eq!(first, Some("1"));
+    /// ```
+    pub fn try_find_stream_with<'a, F>(
+        &self,
+        input: &'a str,
+        on_match: F,
+    ) -> Result<(), StreamError>
+    where
+        F: FnMut(StreamMatch<'a>) -> ControlFlow<()>,
+    {
+        StreamSearcher {
+            dfa: self,
+            input,
+            bytes: input.as_bytes(),
+            pos: 0,
+            frames: Vec::new(),
+            path: Vec::new(),
+            pending: Vec::new(),
+            open_matches: 0,
+            stopped: false,
+            on_match,
+        }
+        .run()
+    }
+}
+
+/// The kind of an open container on the streaming stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ContainerKind {
+    Object,
+    Array,
+}
+
+/// An open container whose DFA state is live.
+#[derive(Debug)]
+struct Frame {
+    kind: ContainerKind,
+    /// DFA state at this container.
+    state: usize,
+    /// Index into `pending` if the container itself is a match.
+    match_slot: Option<usize>,
+    /// Index of the next array element.
+    next_index: usize,
+    /// Whether at least one member has been read (a `,` is expected next).
+    need_separator: bool,
+    /// Whether the last token was a `,` (a member is required next).
+    after_comma: bool,
+}
+
+/// A match whose value may not have ended yet.
+#[derive(Debug)]
+struct Pending {
+    path: Vec<PathType>,
+    start: usize,
+    end: usize,
+}
+
+/// Tokenizer state for a single streaming search.
+struct StreamSearcher<'d, 'a, F> {
+    dfa: &'d QueryDFA,
+    input: &'a str,
+    bytes: &'a [u8],
+    pos: usize,
+    /// Stack of open, live containers.
+    frames: Vec<Frame>,
+    /// Path to the value currently being read.
+    path: Vec<PathType>,
+    /// Matches not yet reported, in document order.
+    pending: Vec<Pending>,
+    /// Number of entries in `pending` whose value has not ended.
+    open_matches: usize,
+    /// Whether `on_match` asked to stop.
+    stopped: bool,
+    on_match: F,
+}
+
+impl<'a, F> StreamSearcher<'_, 'a, F>
+where
+    F: FnMut(StreamMatch<'a>) -> ControlFlow<()>,
+{
+    fn run(mut self) -> Result<(), StreamError> {
+        self.begin_value(self.dfa.start_state)?;
+
+        while !self.stopped
+            && let Some(frame) = self.frames.last_mut()
+        {
+            let kind = frame.kind;
+            let state = frame.state;
+            let close = match kind {
+                ContainerKind::Object => b'}',
+                ContainerKind::Array => b']',
+            };
+
+            self.skip_whitespace();
+            let byte = self.peek()?;
+            let frame = self.frames.last_mut().expect("non-empty stack");
+            if byte == close && !frame.after_comma {
+                self.pos += 1;
+                self.close_container();
+                continue;
+            }
+            if frame.need_separator {
+                if byte != b',' {
+                    return Err(self.error("expected ',' or closing bracket"));
+                }
+                frame.need_separator = false;
+                frame.after_comma = true;
+                self.pos += 1;
+                continue;
+            }
+            frame.need_separator = true;
+            frame.after_comma = false;
+
+            let next_state = match kind {
+                ContainerKind::Object => {
+                    let key = self.parse_key()?;
+                    self.skip_whitespace();
+                    if self.peek()? != b':' {
+                        return Err(self.error("expected ':'"));
+                    }
+                    self.pos += 1;
+                    let symbol_id = self.dfa.get_field_symbol_id(&key);
+                    self.dfa.transition(state, symbol_id).map(|next| {
+                        let key = self.dfa.path_key(symbol_id, &key);
+                        (next, PathType::Field(key))
+                    })
+                }
+                ContainerKind::Array => {
+                    let idx = frame.next_index;
+                    frame.next_index += 1;
+                    self.dfa
+                        .get_index_symbol_id(idx)
+                        .and_then(|id| self.dfa.transition(state, id))
+                        .map(|next| (next, PathType::Index(idx)))
+                }
+            };
+
+            self.skip_whitespace();
+            match next_state {
+                Some((next, segment)) => {
+                    self.path.push(segment);
+                    if !self.begin_value(next)? {
+                        self.path.pop();
+                    }
+                }
+                None => self.skip_value()?,
+            }
+        }
+
+        self.skip_whitespace();
+        if !self.stopped && self.pos < self.bytes.len() {
+            return Err(self.error("trailing characters after JSON value"));
+        }
+        Ok(())
+    }
+
+    /// Start reading a value whose DFA state is `state`. Scalars and dead
+    /// containers are consumed entirely; for a live container only the
+    /// opening bracket is consumed and a frame is pushed. Returns whether a
+    /// frame was pushed.
+    fn begin_value(&mut self, state: usize) -> Result<bool, StreamError> {
+        self.skip_whitespace();
+        let match_slot = self.dfa.is_accepting_state(state).then(|| {
+            self.pending.push(Pending {
+                path: self.path.clone(),
+                start: self.pos,
+                end: self.pos,
+            });
+            self.open_matches += 1;
+            self.pending.len() - 1
+        });
+
+        let kind = match self.peek()? {
+            b'{' => Some(ContainerKind::Object),
+            b'[' => Some(ContainerKind::Array),
+            _ => None,
+        };
+        if let Some(kind) = kind
+            && self.dfa.has_transitions(state)
+        {
+            self.pos += 1;
+            self.frames.push(Frame {
+                kind,
+                state,
+                match_slot,
+                next_index: 0,
+                need_separator: false,
+                after_comma: false,
+            });
+            return Ok(true);
+        }
+
+        self.skip_value()?;
+        if let Some(slot) = match_slot {
+            self.finish_match(slot);
+        }
+        Ok(false)
+    }
+
+    /// Pop the innermost frame after its closing bracket was consumed.
+    fn close_container(&mut self) {
+        let frame = self.frames.pop().expect("non-empty stack");
+        if let Some(slot) = frame.match_slot {
+            self.finish_match(slot);
+        }
+        // The root container has no path segment of its own.
+        if !self.frames.is_empty() {
+            self.path.pop();
+        }
+    }
+
+    /// Record the end of the match in `slot`, reporting all buffered matches
+    /// once no match is open anymore, until `on_match` asks to stop.
+    fn finish_match(&mut self, slot: usize) {
+        self.pending[slot].end = self.pos;
+        self.open_matches -= 1;
+        if self.open_matches == 0 {
+            for m in self.pending.drain(..) {
+                let m = StreamMatch {
+                    path: m.path,
+                    raw: &self.input[m.start..m.end],
+                    offset: m.start,
+                };
+                if self.stopped || (self.on_match)(m).is_break() {
+                    self.stopped = true;
+                }
+            }
+        }
+    }
+
+    /// Consume one complete value of any kind without building it.
+    fn skip_value(&mut self) -> Result<(), StreamError> {
+        match self.peek()? {
+            b'{' | b'[' => self.skip_container(),
+            _ => self.skip_scalar(),
+        }
+    }
+
+    /// Consume one string, number, or literal, checking its syntax.
+    fn skip_scalar(&mut self) -> Result<(), StreamError> {
+        match self.peek()? {
+            b'"' => self.skip_string(),
+            b't' => self.expect_literal("true"),
+            b'f' => self.expect_literal("false"),
+            b'n' => self.expect_literal("null"),
+            b'-' | b'0'..=b'9' => self.skip_number(),
+            _ => Err(self.error("expected a JSON value")),
+        }
+    }
+
+    /// Skip a container, checking it against the JSON grammar. Open
+    /// containers are kept on an explicit stack, so arbitrarily deep
+    /// subtrees are skipped without overflowing the call stack.
+    fn skip_container(&mut self) -> Result<(), StreamError> {
+        let close = |kind| match kind {
+            ContainerKind::Object => b'}',
+            ContainerKind::Array => b']',
+        };
+        let mut open: Vec<ContainerKind> = Vec::new();
+        loop {
+            // At the start of a value.
+            self.skip_whitespace();
+            let kind = match self.peek()? {
+                b'{' => Some(ContainerKind::Object),
+                b'[' => Some(ContainerKind::Array),
+                _ => None,
+            };
+            if let Some(kind) = kind {
+                self.pos += 1;
+                self.skip_whitespace();
+                if self.peek()? == close(kind) {
+                    self.pos += 1;
+                } else {
+                    if kind == ContainerKind::Object {
+                        self.skip_member_key()?;
+                    }
+                    open.push(kind);
+                    continue;
+                }
+            } else {
+                self.skip_scalar()?;
+            }
+
+            // A value ended: close containers until a `,` starts the next
+            // value.
+            loop {
+                let Some(&kind) = open.last() else {
+                    return Ok(());
+                };
+                self.skip_whitespace();
+                let byte = self.peek()?;
+                if byte == b',' {
+                    self.pos += 1;
+                    if kind == ContainerKind::Object {
+                        self.skip_whitespace();
+                        self.skip_member_key()?;
+                    }
+                    break;
+                }
+                if byte != close(kind) {
+                    return Err(self.error("expected ',' or closing bracket"));
+                }
+                self.pos += 1;
+                open.pop();
+            }
+        }
+    }
+
+    /// Skip an object key and the `:` after it.
+    fn skip_member_key(&mut self) -> Result<(), StreamError> {
+        if self.peek()? != b'"' {
+            return Err(self.error("expected an object key"));
+        }
+        self.skip_string()?;
+        self.skip_whitespace();
+        if self.peek()? != b':' {
+            return Err(self.error("expected ':'"));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    /// Skip a number: an optional minus sign, an integer part without
+    /// leading zeros, then an optional fraction and exponent.
+    fn skip_number(&mut self) -> Result<(), StreamError> {
+        if self.bytes[self.pos] == b'-' {
+            self.pos += 1;
+        }
+        match self.peek()? {
+            b'0' => self.pos += 1,
+            b'1'..=b'9' => self.skip_digits(),
+            _ => return Err(self.error("expected a digit")),
+        }
+        if self.bytes.get(self.pos) == Some(&b'.') {
+            self.pos += 1;
+            self.expect_digits()?;
+        }
+        if matches!(self.bytes.get(self.pos), Some(b'e' | b'E')) {
+            self.pos += 1;
+            if matches!(self.bytes.get(self.pos), Some(b'+' | b'-')) {
+                self.pos += 1;
+            }
+            self.expect_digits()?;
+        }
+        Ok(())
+    }
+
+    /// Skip one or more digits.
+    fn expect_digits(&mut self) -> Result<(), StreamError> {
+        if !self.peek()?.is_ascii_digit() {
+            return Err(self.error("expected a digit"));
+        }
+        self.skip_digits();
+        Ok(())
+    }
+
+    fn skip_digits(&mut self) {
+        while self.bytes.get(self.pos).is_some_and(u8::is_ascii_digit) {
+            self.pos += 1;
+        }
+    }
+
+    /// Skip a string starting at the opening quote.
+    fn skip_string(&mut self) -> Result<(), StreamError> {
+        self.scan_string().map(|_| ())
+    }
+
+    /// Scan a string starting at the opening quote, leaving `pos` after the
+    /// closing quote. Returns whether the string contains escapes.
+    fn scan_string(&mut self) -> Result<bool, StreamError> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut escaped = false;
+        loop {
+            match self.bytes.get(self.pos) {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(escaped);
+                }
+                Some(b'\\') => {
+                    escaped = true;
+                    self.pos += 1;
+                    self.skip_escape()?;
+                }
+                Some(0..=0x1f) => {
+                    return Err(self.error("control character in string"));
+                }
+                Some(_) => self.pos += 1,
+                None => {
+                    self.pos = start;
+                    return Err(self.error("unterminated string"));
+                }
+            }
+        }
+    }
+
+    /// Skip the escape sequence after a backslash. A `\u` escape of a
+    /// UTF-16 surrogate must be half of a pair.
+    fn skip_escape(&mut self) -> Result<(), StreamError> {
+        match self.peek()? {
+            b'"' | b'\\' | b'/' | b'b' | b'f' | b'n' | b'r' | b't' => {
+                self.pos += 1;
+                Ok(())
+            }
+            b'u' => {
+                self.pos += 1;
+                match self.hex_escape()? {
+                    0xD800..=0xDBFF => {
+                        if !self.bytes[self.pos..].starts_with(b"\\u") {
+                            return Err(self.error("lone surrogate in string"));
+                        }
+                        self.pos += 2;
+                        if !(0xDC00..=0xDFFF).contains(&self.hex_escape()?) {
+                            return Err(self.error("lone surrogate in string"));
+                        }
+                        Ok(())
+                    }
+                    0xDC00..=0xDFFF => {
+                        Err(self.error("lone surrogate in string"))
+                    }
+                    _ => Ok(()),
+                }
+            }
+            _ => Err(self.error("invalid escape in string")),
+        }
+    }
+
+    /// Read the four hex digits of a `\u` escape.
+    fn hex_escape(&mut self) -> Result<u16, StreamError> {
+        let digits = self
+            .bytes
+            .get(self.pos..self.pos + 4)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .filter(|digits| digits.bytes().all(|b| b.is_ascii_hexdigit()))
+            .ok_or_else(|| self.error("invalid unicode escape in string"))?;
+        self.pos += 4;
+        Ok(u16::from_str_radix(digits, 16).expect("four hex digits"))
+    }
+
+    /// Read an object key, borrowing it from the input unless it contains
+    /// escape sequences.
+    fn parse_key(&mut self) -> Result<Cow<'a, str>, StreamError> {
+        if self.peek()? != b'"' {
+            return Err(self.error("expected an object key"));
+        }
+        let start = self.pos;
+        let escaped = self.scan_string()?;
+        let input: &'a str = self.input;
+        if escaped {
+            serde_json::from_str::<String>(&input[start..self.pos])
+                .map(Cow::Owned)
+                .map_err(|_| StreamError {
+                    offset: start,
+                    message: "invalid escape in object key",
+                })
+        } else {
+            Ok(Cow::Borrowed(&input[start + 1..self.pos - 1]))
+        }
+    }
+
+    fn expect_literal(
+        &mut self,
+        literal: &'static str,
+    ) -> Result<(), StreamError> {
+        if self.bytes[self.pos..].starts_with(literal.as_bytes()) {
+            self.pos += literal.len();
+            Ok(())
+        } else {
+            Err(self.error("invalid literal"))
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.bytes.len()
+            && matches!(self.bytes[self.pos], b' ' | b'\t' | b'\n' | b'\r')
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Result<u8, StreamError> {
+        self.bytes
+            .get(self.pos)
+            .copied()
+            .ok_or_else(|| self.error("unexpected end of input"))
+    }
+
+    const fn error(&self, message: &'static str) -> StreamError {
+        StreamError { offset: self.pos, message }
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Unit testing.")]
+mod tests {
+    use super::*;
+
+    /// Assert that the streaming engine returns the same paths and values as
+    /// the tree-walking engine.
+    fn assert_agrees(input: &str, query: &str) {
+        let dfa = QueryDFA::from_query_str(query).unwrap();
+        let json: Value = serde_json::from_str(input).unwrap();
+        let expected = dfa.find(&json);
+        let streamed = dfa.find_stream(input).unwrap();
+
+        assert_eq!(streamed.len(), expected.len(), "query `{query}`");
+        for (s, e) in streamed.iter().zip(&expected) {
+            assert_eq!(s.path, e.path, "query `{query}`");
+            assert_eq!(&s.parse().unwrap(), e.value, "query `{query}`");
+        }
+    }
+
+    const DOC: &str = r#"
+        {
+          "type": "FeatureCollection",
+          "features": [
+            { "id": 1, "geometry": { "coordinates": [[1.5, -2], [3, 4e2]] } },
+            { "id": "two", "props": { "id": null, "tags": ["a", "b]"] } },
+            { "id": true, "nested": { "features": [ { "id": false } ] } }
+          ],
+          "meta": { "count": 3, "esc\"aped": "x\\y" }
+        }
+    "#;
+
+    #[test]
+    fn agrees_with_tree_engine() {
+        for query in [
+            "",
+            "type",
+            "features[*].id",
+            "features[1:].props.tags[*]",
+            "(* | [*])*.id",
+            "(* | [*])*",
+            "features[0].geometry.coordinates[*][1]",
+            "meta.*",
+            "does.not.exist",
+        ] {
+            assert_agrees(DOC, query);
+        }
+    }
+
+    #[test]
+    fn escaped_key_is_decoded() {
+        let dfa = QueryDFA::from_query_str(r#"meta."esc\"aped""#).unwrap();
+        let matches = dfa.find_stream(DOC).unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].raw, r#""x\\y""#);
+    }
+
+    #[test]
+    fn nested_matches_keep_document_order() {
+        let input = r#"{"a": {"a": {"a": 1}}}"#;
+        let dfa = QueryDFA::from_query_str("a*").unwrap();
+        let raws: Vec<&str> =
+            dfa.find_stream(input).unwrap().iter().map(|m| m.raw).collect();
+        assert_eq!(raws, [input, r#"{"a": {"a": 1}}"#, r#"{"a": 1}"#, "1"]);
+    }
+
+    #[test]
+    fn dead_subtrees_are_validated() {
+        let dfa = QueryDFA::from_query_str("keep").unwrap();
+        for skipped in [
+            "[bogus]",
+            "{]",
+            r#"{"x" 1}"#,
+            "[1,]",
+            "-01",
+            "1.",
+            "[1 2]",
+            r#""\q""#,
+            r#""\ud800""#,
+            "\"tab\there\"",
+            "truex",
+        ] {
+            let input = format!(r#"{{"skip": {skipped}, "keep": 1}}"#);
+            assert!(dfa.find_stream(&input).is_err(), "{input}");
+            assert!(serde_json::from_str::<Value>(&input).is_err(), "{input}");
+        }
+        let input = r#"{"skip": [{"x": "]", "y": [-0.5e+3, "\ud83d\ude00"]}, {}, [], null], "keep": 1}"#;
+        let matches = dfa.find_stream(input).unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].raw, "1");
+    }
+
+    #[test]
+    fn search_stops_when_asked() {
+        let dfa = QueryDFA::from_query_str("a.a*").unwrap();
+        let input = r#"{"a": {"a": {"a": 1}}, "b": [1, 2"#;
+        for stop_after in 1..=3 {
+            let mut raws = Vec::new();
+            dfa.try_find_stream_with(input, |m| {
+                raws.push(m.raw);
+                if raws.len() == stop_after {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            })
+            .unwrap();
+            assert_eq!(raws.len(), stop_after);
+        }
+        // Without stopping, the truncated tail is read and reported.
+        assert!(dfa.find_stream(input).is_err());
+    }
+
+    #[test]
+    fn truncated_input_is_an_error() {
+        let dfa = QueryDFA::from_query_str("a.b").unwrap();
+        let err = dfa.find_stream(r#"{"a": {"b": 1"#).unwrap_err();
+        assert_eq!(err.offset, 13);
+        assert!(dfa.find_stream(r#"{"a": [1, 2"#).is_err());
+        assert!(dfa.find_stream(r#"{"a": "unterminated}"#).is_err());
+    }
+
+    #[test]
+    fn trailing_garbage_and_commas_are_errors() {
+        let dfa = QueryDFA::from_query_str("a").unwrap();
+        assert!(dfa.find_stream(r#"{"a": 1} x"#).is_err());
+        assert!(dfa.find_stream(r#"{"a": 1,}"#).is_err());
+        assert!(dfa.find_stream(r#"{"a" 1}"#).is_err());
+    }
+}
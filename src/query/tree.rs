@@ -0,0 +1,444 @@
+/*!
+# Document Trees
+
+The [`JsonTree`] trait abstracts over the in-memory document types the
+query engine can walk. A query only ever needs three things from a node:
+its entries if it is an object, its elements if it is an array, and
+otherwise what kind of leaf it is.
+
+The trait is implemented for [`serde_json_borrow::Value`] (the engine's
+native zero-copy type) and [`serde_json::Value`], plus `serde_yaml::Value`,
+`toml::Value` and `ciborium::Value` behind the `yaml`, `toml` and `cbor`
+features. Documents already held in one of those types can be queried
+directly with [`QueryDFA::find_tree`](crate::query::QueryDFA::find_tree),
+without serializing them to a string and reparsing.
+
+Formats whose map keys are not always strings (YAML and CBOR) have scalar
+keys stringified, the same way the CLI converts them to JSON: `1` becomes
+the field `"1"` and `true` the field `"true"`. Entries with compound keys
+(sequences or maps used as keys) cannot be named by a query and are skipped.
+
+# Examples
+
+```
+use jsongrep::query::QueryDFA;
+use serde_json::json;
+
+let doc = json!({"users": [{"name": "Ada"}, {"name": "Bob"}]});
+let dfa = QueryDFA::from_query_str("users[*].name").unwrap();
+let results = dfa.find_tree(&doc);
+
+assert_eq!(results.len(), 2);
+assert_eq!(results[1].value, &json!("Bob"));
+```
+*/
+use serde_json_borrow::Value;
+use std::borrow::Cow;
+
+use crate::query::{PathType, QueryDFA};
+
+/// The kind of a leaf (non-container) node in a [`JsonTree`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum LeafKind {
+    /// `null`, or YAML `~`.
+    Null,
+    /// `true` or `false`.
+    Bool,
+    /// An integer or floating-point number.
+    Number,
+    /// A string.
+    String,
+    /// A byte string (CBOR).
+    Bytes,
+    /// A date and/or time (TOML).
+    Datetime,
+}
+
+/// A document tree that can be searched by a [`QueryDFA`].
+///
+/// Every node is exactly one of an object, an array, or a leaf: for any
+/// node, exactly one of [`JsonTree::object_entries`],
+/// [`JsonTree::array_elements`] and [`JsonTree::leaf_kind`] returns `Some`.
+pub trait JsonTree {
+    /// The entries of this node if it is an object, in document order.
+    fn object_entries(
+        &self,
+    ) -> Option<impl Iterator<Item = (Cow<'_, str>, &Self)>>;
+
+    /// The elements of this node if it is an array, in order.
+    fn array_elements(&self) -> Option<impl Iterator<Item = &Self>>;
+
+    /// The kind of this node if it is a leaf.
+    fn leaf_kind(&self) -> Option<LeafKind>;
+}
+
+/// The remaining children of a container during an iterative walk.
+///
+/// The walks over [`JsonTree`]s keep one of these per open container on an
+/// explicit heap-allocated stack instead of recursing, so deeply nested
+/// documents cannot overflow the call stack. It is generic over the iterator
+/// types so the ones returned by the trait need not be named.
+pub(crate) enum Children<E, A> {
+    /// The entries of an object.
+    Entries(E),
+    /// The elements of an array, with their indices.
+    Elements(A),
+}
+
+/// The edge a walk took from a container into one of its children.
+pub(crate) enum Edge<'k> {
+    /// A field, with its DFA symbol ID.
+    Field(usize, Cow<'k, str>),
+    /// An array index.
+    Index(usize),
+}
+
+impl Edge<'_> {
+    /// The path segment for this edge, reusing the DFA's interned key when
+    /// possible (see [`QueryDFA::path_key`]).
+    pub(crate) fn to_path(&self, dfa: &QueryDFA) -> PathType {
+        match self {
+            Self::Field(symbol_id, key) => {
+                PathType::Field(dfa.path_key(*symbol_id, key))
+            }
+            Self::Index(idx) => PathType::Index(*idx),
+        }
+    }
+}
+
+/// The children of `value` if it is a container, or `None` for a leaf.
+#[expect(
+    clippy::type_complexity,
+    reason = "The iterator types are opaque and cannot be aliased."
+)]
+pub(crate) fn open_children<T: JsonTree>(
+    value: &T,
+) -> Option<
+    Children<
+        impl Iterator<Item = (Cow<'_, str>, &T)>,
+        impl Iterator<Item = (usize, &T)>,
+    >,
+> {
+    value.object_entries().map_or_else(
+        || {
+            value
+                .array_elements()
+                .map(|elements| Children::Elements(elements.enumerate()))
+        },
+        |entries| Some(Children::Entries(entries)),
+    )
+}
+
+impl<'a, T, E, A> Children<E, A>
+where
+    T: JsonTree + 'a,
+    E: Iterator<Item = (Cow<'a, str>, &'a T)>,
+    A: Iterator<Item = (usize, &'a T)>,
+{
+    /// Advances to the next child. Returns `None` once the container is
+    /// exhausted, `Some(None)` for a child the DFA cannot step into from
+    /// `state`, and otherwise the next state, the edge taken and the child.
+    #[expect(
+        clippy::option_option,
+        reason = "Exhausted and dead edges must be told apart."
+    )]
+    pub(crate) fn next_step(
+        &mut self,
+        dfa: &QueryDFA,
+        state: usize,
+    ) -> Option<Option<(usize, Edge<'a>, &'a T)>> {
+        match self {
+            Self::Entries(entries) => entries.next().map(|(key, child)| {
+                let symbol_id = dfa.get_field_symbol_id(&key);
+                dfa.transition(state, symbol_id)
+                    .map(|next| (next, Edge::Field(symbol_id, key), child))
+            }),
+            Self::Elements(elements) => elements.next().map(|(idx, child)| {
+                dfa.get_index_symbol_id(idx)
+                    .and_then(|id| dfa.transition(state, id))
+                    .map(|next| (next, Edge::Index(idx), child))
+            }),
+        }
+    }
+}
+
+impl JsonTree for Value<'_> {
+    fn object_entries(
+        &self,
+    ) -> Option<impl Iterator<Item = (Cow<'_, str>, &Self)>> {
+        match self {
+            Self::Object(map) => {
+                Some(map.iter().map(|(key, val)| (Cow::Borrowed(key), val)))
+            }
+            _ => None,
+        }
+    }
+
+    fn array_elements(&self) -> Option<impl Iterator<Item = &Self>> {
+        match self {
+            Self::Array(vals) => Some(vals.iter()),
+            _ => None,
+        }
+    }
+
+    fn leaf_kind(&self) -> Option<LeafKind> {
+        match self {
+            Self::Null => Some(LeafKind::Null),
+            Self::Bool(_) => Some(LeafKind::Bool),
+            Self::Number(_) => Some(LeafKind::Number),
+            Self::Str(_) => Some(LeafKind::String),
+            Self::Array(_) | Self::Object(_) => None,
+        }
+    }
+}
+
+impl JsonTree for serde_json::Value {
+    fn object_entries(
+        &self,
+    ) -> Option<impl Iterator<Item = (Cow<'_, str>, &Self)>> {
+        self.as_object().map(|map| {
+            map.iter().map(|(key, val)| (Cow::Borrowed(key.as_str()), val))
+        })
+    }
+
+    fn array_elements(&self) -> Option<impl Iterator<Item = &Self>> {
+        self.as_array().map(|vals| vals.iter())
+    }
+
+    fn leaf_kind(&self) -> Option<LeafKind> {
+        match self {
+            Self::Null => Some(LeafKind::Null),
+            Self::Bool(_) => Some(LeafKind::Bool),
+            Self::Number(_) => Some(LeafKind::Number),
+            Self::String(_) => Some(LeafKind::String),
+            Self::Array(_) | Self::Object(_) => None,
+        }
+    }
+}
+
+/// Strips YAML tags (`!Foo {...}`), which are transparent to queries.
+#[cfg(feature = "yaml")]
+fn untag_yaml(mut value: &serde_yaml::Value) -> &serde_yaml::Value {
+    while let serde_yaml::Value::Tagged(tagged) = value {
+        value = &tagged.value;
+    }
+    value
+}
+
+#[cfg(feature = "yaml")]
+impl JsonTree for serde_yaml::Value {
+    fn object_entries(
+        &self,
+    ) -> Option<impl Iterator<Item = (Cow<'_, str>, &Self)>> {
+        match untag_yaml(self) {
+            Self::Mapping(map) => Some(map.iter().filter_map(|(key, val)| {
+                let key = match untag_yaml(key) {
+                    Self::String(s) => Cow::Borrowed(s.as_str()),
+                    Self::Number(n) => Cow::Owned(n.to_string()),
+                    Self::Bool(b) => Cow::Owned(b.to_string()),
+                    Self::Null => Cow::Borrowed("null"),
+                    _ => return None,
+                };
+                Some((key, val))
+            })),
+            _ => None,
+        }
+    }
+
+    fn array_elements(&self) -> Option<impl Iterator<Item = &Self>> {
+        match untag_yaml(self) {
+            Self::Sequence(vals) => Some(vals.iter()),
+            _ => None,
+        }
+    }
+
+    fn leaf_kind(&self) -> Option<LeafKind> {
+        match untag_yaml(self) {
+            Self::Null => Some(LeafKind::Null),
+            Self::Bool(_) => Some(LeafKind::Bool),
+            Self::Number(_) => Some(LeafKind::Number),
+            Self::String(_) => Some(LeafKind::String),
+            Self::Sequence(_) | Self::Mapping(_) | Self::Tagged(_) => None,
+        }
+    }
+}
+
+#[cfg(feature = "toml")]
+impl JsonTree for toml::Value {
+    fn object_entries(
+        &self,
+    ) -> Option<impl Iterator<Item = (Cow<'_, str>, &Self)>> {
+        self.as_table().map(|table| {
+            table.iter().map(|(key, val)| (Cow::Borrowed(key.as_str()), val))
+        })
+    }
+
+    fn array_elements(&self) -> Option<impl Iterator<Item = &Self>> {
+        self.as_array().map(|vals| vals.iter())
+    }
+
+    fn leaf_kind(&self) -> Option<LeafKind> {
+        match self {
+            Self::Boolean(_) => Some(LeafKind::Bool),
+            Self::Integer(_) | Self::Float(_) => Some(LeafKind::Number),
+            Self::String(_) => Some(LeafKind::String),
+            Self::Datetime(_) => Some(LeafKind::Datetime),
+            Self::Array(_) | Self::Table(_) => None,
+        }
+    }
+}
+
+/// Strips CBOR semantic tags, which are transparent to queries.
+#[cfg(feature = "cbor")]
+fn untag_cbor(mut value: &ciborium::Value) -> &ciborium::Value {
+    while let ciborium::Value::Tag(_, inner) = value {
+        value = inner;
+    }
+    value
+}
+
+#[cfg(feature = "cbor")]
+impl JsonTree for ciborium::Value {
+    fn object_entries(
+        &self,
+    ) -> Option<impl Iterator<Item = (Cow<'_, str>, &Self)>> {
+        match untag_cbor(self) {
+            Self::Map(entries) => {
+                Some(entries.iter().filter_map(|(key, val)| {
+                    let key = match untag_cbor(key) {
+                        Self::Text(s) => Cow::Borrowed(s.as_str()),
+                        Self::Integer(i) => {
+                            Cow::Owned(i128::from(*i).to_string())
+                        }
+                        Self::Float(f) => Cow::Owned(f.to_string()),
+                        Self::Bool(b) => Cow::Owned(b.to_string()),
+                        Self::Null => Cow::Borrowed("null"),
+                        _ => return None,
+                    };
+                    Some((key, val))
+                }))
+            }
+            _ => None,
+        }
+    }
+
+    fn array_elements(&self) -> Option<impl Iterator<Item = &Self>> {
+        match untag_cbor(self) {
+            Self::Array(vals) => Some(vals.iter()),
+            _ => None,
+        }
+    }
+
+    fn leaf_kind(&self) -> Option<LeafKind> {
+        match untag_cbor(self) {
+            Self::Bool(_) => Some(LeafKind::Bool),
+            Self::Integer(_) | Self::Float(_) => Some(LeafKind::Number),
+            Self::Text(_) => Some(LeafKind::String),
+            Self::Bytes(_) => Some(LeafKind::Bytes),
+            Self::Array(_) | Self::Map(_) | Self::Tag(..) => None,
+            // `Null`, plus any variant added later (`Value` is
+            // non-exhaustive).
+            _ => Some(LeafKind::Null),
+        }
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Unit testing.")]
+mod tests {
+    use super::*;
+    use crate::query::{PathType, QueryDFA};
+
+    const DOC: &str = r#"{"a": {"b": [1, {"c": "x"}], "d": null}, "e": true}"#;
+
+    /// Renders every match of `query` as its dotted path, in order.
+    fn paths<T: JsonTree>(query: &str, doc: &T) -> Vec<String> {
+        let dfa = QueryDFA::from_query_str(query).unwrap();
+        dfa.find_tree(doc)
+            .iter()
+            .map(|p| {
+                p.path
+                    .iter()
+                    .map(PathType::to_string)
+                    .collect::<Vec<_>>()
+                    .join(".")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn owned_json_agrees_with_borrowed() {
+        let owned: serde_json::Value = serde_json::from_str(DOC).unwrap();
+        let borrowed: Value = serde_json::from_str(DOC).unwrap();
+        for query in ["a.b[1].c", "(* | [*])*", "a.*", "e", "a.b[5]"] {
+            assert_eq!(
+                paths(query, &owned),
+                paths(query, &borrowed),
+                "{query}"
+            );
+        }
+
+        let dfa = QueryDFA::from_query_str("a.b[*]").unwrap();
+        let results = dfa.find_tree(&owned);
+        assert_eq!(results[1].value, &serde_json::json!({"c": "x"}));
+    }
+
+    #[test]
+    fn leaf_kinds() {
+        let json: serde_json::Value = serde_json::from_str(DOC).unwrap();
+        assert_eq!(json.leaf_kind(), None);
+        assert_eq!(json["a"]["d"].leaf_kind(), Some(LeafKind::Null));
+        assert_eq!(json["e"].leaf_kind(), Some(LeafKind::Bool));
+        assert_eq!(json["a"]["b"][0].leaf_kind(), Some(LeafKind::Number));
+        assert!(json["a"]["b"].array_elements().is_some());
+        assert!(json["a"]["b"].object_entries().is_none());
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn yaml_scalar_keys_are_stringified_and_tags_ignored() {
+        let yaml: serde_yaml::Value = serde_yaml::from_str(
+            "1: one\ntrue: yes\n? [x]\n: compound\nt: !Thing {k: [v]}\n",
+        )
+        .unwrap();
+        assert_eq!(paths("*", &yaml), ["1", "true", "t"]);
+        assert_eq!(paths("t.k[0]", &yaml), ["t.k.[0]"]);
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml_tables_and_datetimes() {
+        let toml: toml::Value = toml::from_str(
+            "[server]\nports = [80, 443]\nstarted = 1979-05-27T07:32:00Z\n",
+        )
+        .unwrap();
+        assert_eq!(
+            paths("server.ports[*]", &toml),
+            ["server.ports.[0]", "server.ports.[1]"]
+        );
+        let dfa = QueryDFA::from_query_str("server.started").unwrap();
+        let results = dfa.find_tree(&toml);
+        assert_eq!(results[0].value.leaf_kind(), Some(LeafKind::Datetime));
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn cbor_integer_keys_and_tags() {
+        use ciborium::Value as Cbor;
+        let cbor = Cbor::Map(vec![
+            (Cbor::Integer(7.into()), Cbor::Text("seven".into())),
+            (
+                Cbor::Text("tagged".into()),
+                Cbor::Tag(
+                    1,
+                    Box::new(Cbor::Array(vec![Cbor::Bytes(vec![0xff])])),
+                ),
+            ),
+        ]);
+        assert_eq!(paths("*", &cbor), ["7", "tagged"]);
+        let dfa = QueryDFA::from_query_str("tagged[0]").unwrap();
+        let results = dfa.find_tree(&cbor);
+        assert_eq!(results[0].value.leaf_kind(), Some(LeafKind::Bytes));
+    }
+}
@@ -0,0 +1,1343 @@
+/*!
+# Search
+
+Running a compiled query over files, readers, and byte slices, exactly the
+way the `jg` binary does: input formats are detected from file extensions,
+large files are memory-mapped (and, for JSON, searched with the streaming
+engine), JSONL is parsed line by line, and a multi-file search keeps going
+past files that fail to parse.
+
+A [`Searcher`] holds the compiled query and search options and reports what
+it finds to a [`Sink`]: [`Sink::begin_file`] before each input,
+[`Sink::matched`] for each match in document order, [`Sink::end_file`] with
+the number of matches, [`Sink::file_error`] when an input could not be
+searched, and [`Sink::summary`] after a multi-input search. Any
+`FnMut(&str, &[PathType], &Value) -> Result<()>` closure is a sink that
+only cares about matches, and the [`printer`](crate::printer) module has
+sinks that print results the way `jg` does.
+
+# Examples
+
+```
+use jsongrep::{Value, query::{PathType, QueryDFA}, search::Searcher};
+
+let dfa = QueryDFA::from_query_str("users[*].name").unwrap();
+let input = br#"{"users": [{"name": "Alice"}, {"name": "Bob"}]}"#;
+
+let mut names = Vec::new();
+let count = Searcher::new(&dfa)
+    .search_slice(
+        "users.json",
+        input,
+        &mut |_: &str, _: &[PathType], value: &Value| {
+            names.push(value.to_string());
+            Ok(())
+        },
+    )
+    .unwrap();
+
+assert_eq!(count, 2);
+assert_eq!(names, [r#""Alice""#, r#""Bob""#]);
+```
+*/
+use anyhow::{Context as _, Result};
+use memmap2::{Mmap, MmapOptions};
+use serde_json_borrow::Value;
+use std::{
+    fs::OpenOptions,
+    io::{self, ErrorKind, Read},
+    ops::ControlFlow,
+    path::Path,
+};
+
+use crate::{
+    format::{FormatParser, FormatRegistry, builtin_format},
+    query::{
+        JSONPointer, Matches, OwnedMatch, PathType, QueryDFA, SearchLimits,
+    },
+    utils::depth,
+};
+
+/// Minimum file size for which memory-mapping is attempted.
+///
+/// For small files, it is likely that a single read call is at least as fast or faster than mmap
+/// (mmap setup and page-fault overhead dominate for small files) and avoids mmap's file-truncation
+/// hazards.
+///
+/// NOTE: in the future when globbing (<https://github.com/micahkepe/jsongrep/issues/33>) and
+/// recursive searching are enabled, can look into other heuristics for performance.
+///
+/// See: <https://burntsushi.net/ripgrep/#mechanics>.
+pub const MMAP_MIN_FILE_SIZE: u64 = 1 << 20; // 1 MiB
+
+/// Minimum size of a memory-mapped JSON file (or byte slice) for which
+/// queries run on the streaming engine ([`QueryDFA::find_stream`]) instead
+/// of parsing the whole document into a tree first.
+///
+/// Below this size, building the tree is cheap and the tree walk is faster;
+/// above it, the parsed tree would cost a multiple of the file size in
+/// memory, while the streaming engine hands each match on as it finds it and
+/// only needs memory for the match at hand.
+pub const STREAM_MIN_FILE_SIZE: u64 = 64 << 20; // 64 MiB
+
+/// The name [`Searcher`] users conventionally give standard input.
+pub const STDIN_NAME: &str = "(standard input)";
+
+/// Supported input formats beyond JSON.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// Detect the format from the file extension, defaulting to JSON.
+    #[default]
+    Auto,
+    /// JSON.
+    Json,
+    /// Newline-delimited JSON, searched as one array of records.
+    Jsonl,
+    /// YAML (needs the `yaml` feature).
+    Yaml,
+    /// TOML (needs the `toml` feature).
+    Toml,
+    /// CBOR (needs the `cbor` feature).
+    Cbor,
+    /// `MessagePack` (needs the `msgpack` feature).
+    Msgpack,
+}
+
+impl std::fmt::Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Auto => write!(f, "Auto"),
+            Self::Json => write!(f, "JSON"),
+            Self::Jsonl => write!(f, "JSONL"),
+            Self::Yaml => write!(f, "YAML"),
+            Self::Toml => write!(f, "TOML"),
+            Self::Cbor => write!(f, "CBOR"),
+            Self::Msgpack => write!(f, "MessagePack"),
+        }
+    }
+}
+
+impl Format {
+    /// The built-in formats, i.e. every format but [`Format::Auto`].
+    pub const BUILTIN: [Self; 6] = [
+        Self::Json,
+        Self::Jsonl,
+        Self::Yaml,
+        Self::Toml,
+        Self::Cbor,
+        Self::Msgpack,
+    ];
+
+    /// The format's name on the command line (`--format`).
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Json => "json",
+            Self::Jsonl => "jsonl",
+            Self::Yaml => "yaml",
+            Self::Toml => "toml",
+            Self::Cbor => "cbor",
+            Self::Msgpack => "msgpack",
+        }
+    }
+
+    /// The format called `name` on the command line, if any.
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        std::iter::once(Self::Auto)
+            .chain(Self::BUILTIN)
+            .find(|format| format.name() == name)
+    }
+
+    /// The file extensions detected as this format.
+    #[must_use]
+    pub const fn extensions(self) -> &'static [&'static str] {
+        match self {
+            Self::Auto => &[],
+            Self::Json => &["json"],
+            Self::Jsonl => &["ndjson", "jsonl"],
+            Self::Yaml => &["yaml", "yml"],
+            Self::Toml => &["toml"],
+            Self::Cbor => &["cbor"],
+            Self::Msgpack => &["msgpack", "mp"],
+        }
+    }
+}
+
+/// The format to parse an input as: `explicit` unless it is
+/// [`Format::Auto`], otherwise detected from the extension of `path`
+/// (JSON when there is no path or the extension is unknown).
+#[must_use]
+pub fn detect_format(path: Option<&Path>, explicit: Format) -> Format {
+    // Use explicit if user overrode the default.
+    if !matches!(explicit, Format::Auto) {
+        return explicit;
+    }
+    // NOTE: we don't support streaming type inference, maybe someday
+    path.and_then(format_from_extension).unwrap_or(Format::Json)
+}
+
+/// The format implied by the extension of `path`, if it is a known one.
+pub(crate) fn format_from_extension(path: &Path) -> Option<Format> {
+    let ext = path.extension()?.to_str()?;
+    Format::BUILTIN
+        .into_iter()
+        .find(|format| format.extensions().contains(&ext))
+}
+
+/// Parse JSONL/NDJSON input line by line into a single top-level array,
+/// borrowing each record directly from the input buffer.
+///
+/// Compared to concatenating all lines into a synthetic `[...]` JSON string
+/// and re-parsing it, this avoids a second full-input-sized allocation and
+/// reports parse errors with the actual line number of the offending record.
+///
+/// # Errors
+///
+/// Returns an error naming the first line that is not valid JSON. Blank
+/// lines are skipped.
+pub fn parse_jsonl(text: &str) -> Result<Value<'_>> {
+    fn parse_line((idx, line): (usize, &str)) -> Result<Value<'_>> {
+        serde_json::from_str(line)
+            .with_context(|| format!("Failed to parse JSONL line {}", idx + 1))
+    }
+    let lines = text
+        .lines()
+        .map(str::trim)
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty());
+
+    // With `rayon`, lines are parsed on the thread pool; collecting the
+    // per-line results first keeps the error for the earliest bad line.
+    #[cfg(feature = "rayon")]
+    let records = {
+        use rayon::prelude::*;
+        let lines: Vec<_> = lines.collect();
+        let parsed: Vec<Result<Value>> =
+            lines.into_par_iter().map(parse_line).collect();
+        parsed.into_iter().collect::<Result<Vec<_>>>()?
+    };
+    #[cfg(not(feature = "rayon"))]
+    let records = lines.map(parse_line).collect::<Result<Vec<_>>>()?;
+
+    Ok(Value::Array(records))
+}
+
+/// Whether any error in the chain is a broken pipe (the downstream consumer
+/// of the output has gone away), which is a signal to stop printing, not an
+/// input-file failure.
+#[must_use]
+pub fn is_broken_pipe(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause
+            .downcast_ref::<io::Error>()
+            .is_some_and(|io_err| io_err.kind() == ErrorKind::BrokenPipe)
+    })
+}
+
+/// The raw bytes of one input.
+///
+/// Input is kept as raw bytes so that binary formats (CBOR, `MessagePack`)
+/// work from any source; UTF-8 is validated only when a text format needs
+/// it.
+#[derive(Debug)]
+pub enum Input {
+    /// Fully buffered input: readers such as stdin, small files, non-regular
+    /// files (FIFOs, process substitution), and the fallback when mmap
+    /// fails.
+    Buffer(Vec<u8>),
+    /// A memory-mapped file from the file system. Assumes an immutable handle.
+    File(Mmap),
+}
+
+impl Input {
+    /// Read the file at `path`, memory-mapping it when it is a regular file
+    /// of at least [`MMAP_MIN_FILE_SIZE`] bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened or read.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let mut fd =
+            OpenOptions::new().read(true).open(path).with_context(|| {
+                format!("Failed to open file {}", path.display())
+            })?;
+
+        // Only mmap large regular files. Non-regular files (FIFOs, process
+        // substitution like `jg q <(curl ...)`, character devices) cannot be
+        // mapped, and small files gain nothing from mapping. If mapping
+        // fails anyway, fall back to a plain read instead of erroring.
+        let metadata = fd.metadata().ok();
+        let is_large_regular_file = metadata
+            .as_ref()
+            .is_some_and(|m| m.is_file() && m.len() >= MMAP_MIN_FILE_SIZE);
+
+        if is_large_regular_file {
+            // SAFETY:
+            // mmap is unsafe if the backing file is modified, either by
+            // ourselves or by other processes. We will never modify the
+            // file, and if other processes do, there is not much we can do
+            // about it.
+            if let Ok(map) = unsafe { MmapOptions::new().map(&fd) } {
+                return Ok(Self::File(map));
+            }
+        }
+
+        // Capacity hint capped at the mmap threshold: only files below it
+        // (or rare mmap fallbacks) reach this path, and a stale/huge stat
+        // length must not trigger a giant allocation.
+        let capacity_hint = metadata
+            .map_or(0, |m| m.len().min(MMAP_MIN_FILE_SIZE))
+            .try_into()
+            .unwrap_or(0);
+        let mut buffer = Vec::with_capacity(capacity_hint);
+        fd.read_to_end(&mut buffer).with_context(|| {
+            format!("Failed to read file {}", path.display())
+        })?;
+        Ok(Self::Buffer(buffer))
+    }
+
+    /// Read `reader` to the end.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails.
+    pub fn from_reader<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut buffer = Vec::new();
+        reader.read_to_end(&mut buffer)?;
+        Ok(Self::Buffer(buffer))
+    }
+
+    /// The input bytes.
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Self::Buffer(buf) => buf.as_slice(),
+            Self::File(mmap) => mmap.as_ref(),
+        }
+    }
+
+    /// Whether this is a memory-mapped file large enough to be searched
+    /// with the streaming engine.
+    fn is_large_mmap(&self) -> bool {
+        match self {
+            Self::Buffer(_) => false,
+            Self::File(mmap) => mmap.len() as u64 >= STREAM_MIN_FILE_SIZE,
+        }
+    }
+
+    /// Parses the input as `format` and invokes `f` with a borrowed
+    /// [`Value`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the input cannot be parsed as `format` (or the
+    /// format's cargo feature is disabled), or whatever `f` returns.
+    pub fn with_json<F, T>(&self, format: Format, f: F) -> Result<T>
+    where
+        F: FnOnce(&Value) -> Result<T>,
+    {
+        with_bytes_json(self.as_bytes(), Parser::Builtin(format), f)
+    }
+
+    /// Parses the input with `parser` and invokes `f` with a borrowed
+    /// [`Value`]. Built-in parsers take the same paths as
+    /// [`Input::with_json`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `parser` fails, or whatever `f` returns.
+    pub fn with_json_as<F, T>(
+        &self,
+        parser: &dyn FormatParser,
+        f: F,
+    ) -> Result<T>
+    where
+        F: FnOnce(&Value) -> Result<T>,
+    {
+        with_bytes_json(self.as_bytes(), Parser::from(parser), f)
+    }
+}
+
+impl From<Vec<u8>> for Input {
+    fn from(buffer: Vec<u8>) -> Self {
+        Self::Buffer(buffer)
+    }
+}
+
+/// How to parse one input: a built-in format, which has zero-copy, streaming
+/// and deserializing fast paths, or a registered [`FormatParser`].
+#[derive(Clone, Copy)]
+enum Parser<'p> {
+    Builtin(Format),
+    Custom(&'p dyn FormatParser),
+}
+
+impl Parser<'_> {
+    /// The name of the format, as given to `--format`.
+    fn name(self) -> &'static str {
+        match self {
+            Self::Builtin(Format::Auto) => Format::Json.name(),
+            Self::Builtin(format) => format.name(),
+            Self::Custom(parser) => parser.name(),
+        }
+    }
+}
+
+impl<'p> From<&'p dyn FormatParser> for Parser<'p> {
+    fn from(parser: &'p dyn FormatParser) -> Self {
+        builtin_format(parser).map_or(Self::Custom(parser), Self::Builtin)
+    }
+}
+
+/// Parses `bytes` with `parser` and invokes `f` with a borrowed [`Value`] to
+/// preserve the zero-copy path for JSON/Auto and JSONL.
+fn with_bytes_json<F, T>(bytes: &[u8], parser: Parser, f: F) -> Result<T>
+where
+    F: FnOnce(&Value) -> Result<T>,
+{
+    let format = match parser {
+        Parser::Builtin(format) => format,
+        Parser::Custom(parser) => {
+            let owned = parser.parse(bytes).with_context(|| {
+                format!("Failed to parse as {}", parser.name())
+            })?;
+            return f(&Value::from(&owned));
+        }
+    };
+
+    // For JSON/Auto we borrow directly from the mmap/stdin buffer,
+    // preserving the zero-copy path that serde_json_borrow provides. JSONL
+    // is parsed per line, likewise borrowing from the input buffer. For
+    // other formats, we deserialize into an owned `serde_json::Value` first
+    // and then borrow from that.
+    match format {
+        Format::Json | Format::Auto => {
+            let json_str =
+                str::from_utf8(bytes).context("Input is not valid UTF-8")?;
+            let json: Value = serde_json::from_str(json_str)
+                .with_context(|| format!("Failed to parse as {format}"))?;
+            f(&json)
+        }
+        Format::Jsonl => {
+            let text = str::from_utf8(bytes).map_err(|_| {
+                anyhow::anyhow!("JSONL input is not valid UTF-8")
+            })?;
+            let json = parse_jsonl(text)?;
+            f(&json)
+        }
+        other => {
+            let owned = to_owned_json(bytes, other)?;
+            let json = Value::from(&owned);
+            f(&json)
+        }
+    }
+}
+
+/// Deserializes a non-JSON format straight into an owned
+/// [`serde_json::Value`], which the borrowed [`Value`] can then point into
+/// without a round trip through JSON text.
+#[cfg_attr(
+    not(any(
+        feature = "yaml",
+        feature = "toml",
+        feature = "cbor",
+        feature = "msgpack"
+    )),
+    expect(unused_variables, reason = "no format enabled")
+)]
+pub(crate) fn to_owned_json(
+    bytes: &[u8],
+    format: Format,
+) -> Result<serde_json::Value> {
+    match format {
+        // YAML
+        #[cfg(feature = "yaml")]
+        Format::Yaml => {
+            let text = str::from_utf8(bytes).map_err(|_| {
+                anyhow::anyhow!("YAML input is not valid UTF-8")
+            })?;
+            serde_yaml::from_str(text).context("parse YAML input")
+        }
+        #[cfg(not(feature = "yaml"))]
+        Format::Yaml => {
+            anyhow::bail!(
+                "YAML support not enabled. Rebuild with --features yaml"
+            )
+        }
+
+        // TOML
+        #[cfg(feature = "toml")]
+        Format::Toml => {
+            let text = str::from_utf8(bytes).map_err(|_| {
+                anyhow::anyhow!("TOML input is not valid UTF-8")
+            })?;
+            toml::from_str(text).context("parse TOML input")
+        }
+        #[cfg(not(feature = "toml"))]
+        Format::Toml => {
+            anyhow::bail!(
+                "TOML support not enabled. Rebuild with --features toml"
+            )
+        }
+
+        // CBOR
+        #[cfg(feature = "cbor")]
+        Format::Cbor => {
+            ciborium::from_reader(bytes).context("parse CBOR input")
+        }
+        #[cfg(not(feature = "cbor"))]
+        Format::Cbor => {
+            anyhow::bail!(
+                "CBOR support not enabled. Rebuild with --features cbor"
+            )
+        }
+
+        // MESSAGEPACK
+        #[cfg(feature = "msgpack")]
+        Format::Msgpack => {
+            rmp_serde::from_slice(bytes).context("parse MessagePack input")
+        }
+        #[cfg(not(feature = "msgpack"))]
+        Format::Msgpack => {
+            anyhow::bail!(
+                "MessagePack support not enabled. Rebuild with --features msgpack"
+            )
+        }
+
+        // Unreachable, someone made an oopsie
+        // (JSONL is parsed per line in `parse_jsonl`, borrowing from the
+        // input buffer, so it never goes through this owned path.)
+        Format::Auto | Format::Json | Format::Jsonl => {
+            unreachable!(
+                "to_owned_json called with Auto, Json, or Jsonl, not needed"
+            )
+        }
+    }
+}
+
+/// Runs `dfa` while deserializing a non-JSON format, so that only the
+/// matched values are built, stopping after `max_matches` (see
+/// [`QueryDFA::find_deserialize_with_limit`]).
+///
+/// Returns `None` for formats whose deserializer cannot be driven directly
+/// (CBOR: `ciborium` only exposes `from_reader`); callers fall back to
+/// [`to_owned_json`] for those.
+#[cfg_attr(
+    not(any(feature = "yaml", feature = "toml", feature = "msgpack")),
+    expect(
+        unused_variables,
+        clippy::missing_const_for_fn,
+        clippy::unnecessary_wraps,
+        reason = "no seedable format enabled"
+    )
+)]
+fn find_deserialize(
+    bytes: &[u8],
+    dfa: &QueryDFA,
+    format: Format,
+    max_matches: usize,
+) -> Result<Option<Vec<OwnedMatch>>> {
+    match format {
+        #[cfg(feature = "yaml")]
+        Format::Yaml => {
+            let text = str::from_utf8(bytes).map_err(|_| {
+                anyhow::anyhow!("YAML input is not valid UTF-8")
+            })?;
+            dfa.find_deserialize_with_limit(
+                serde_yaml::Deserializer::from_str(text),
+                max_matches,
+            )
+            .context("parse YAML input")
+            .map(Some)
+        }
+        #[cfg(feature = "toml")]
+        Format::Toml => {
+            let text = str::from_utf8(bytes).map_err(|_| {
+                anyhow::anyhow!("TOML input is not valid UTF-8")
+            })?;
+            dfa.find_deserialize_with_limit(
+                toml::Deserializer::new(text),
+                max_matches,
+            )
+            .context("parse TOML input")
+            .map(Some)
+        }
+        #[cfg(feature = "msgpack")]
+        Format::Msgpack => {
+            let mut de = rmp_serde::Deserializer::from_read_ref(bytes);
+            dfa.find_deserialize_with_limit(&mut de, max_matches)
+                .context("parse MessagePack input")
+                .map(Some)
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Rejects a document nested deeper than `max_depth` levels, as measured by
+/// [`depth`].
+fn check_depth(json: &Value, max_depth: Option<usize>) -> Result<()> {
+    if let Some(max) = max_depth {
+        let depth = depth(json);
+        if depth > max {
+            anyhow::bail!(
+                "input is nested {depth} levels deep, exceeding --max-depth {max}"
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Receives the results of a [`Searcher`].
+///
+/// For each input, a search calls [`Sink::begin_file`], then (in
+/// [`SearchMode::Matches`]) [`Sink::matched`] for every match in document
+/// order, then [`Sink::end_file`]. An input that cannot be read or parsed
+/// instead ends with [`Sink::file_error`] when searched through
+/// [`Searcher::search_paths`], which finally calls [`Sink::summary`]. An
+/// error returned from any method stops the search and is returned by the
+/// [`Searcher`].
+pub trait Sink {
+    /// Called before `name` is searched, with the name of the format it is
+    /// parsed as (see [`FormatParser::name`]).
+    ///
+    /// # Errors
+    ///
+    /// An error stops the search.
+    fn begin_file(&mut self, name: &str, format: &str) -> Result<()> {
+        let _ = (name, format);
+        Ok(())
+    }
+
+    /// Whether [`Sink::matched`] needs the match paths. Returning `false`
+    /// lets the search skip building them; `matched` then receives empty
+    /// paths.
+    fn wants_paths(&self) -> bool {
+        true
+    }
+
+    /// Called for each match in `name`, in document order.
+    ///
+    /// # Errors
+    ///
+    /// An error stops the search.
+    fn matched(
+        &mut self,
+        name: &str,
+        path: &[PathType],
+        value: &Value,
+    ) -> Result<()>;
+
+    /// Called after `name` was searched, with its number of matches (capped
+    /// at [`Searcher::max_count`]; in [`SearchMode::Any`], 0 or 1).
+    ///
+    /// # Errors
+    ///
+    /// An error stops the search.
+    fn end_file(&mut self, name: &str, count: usize) -> Result<()> {
+        let _ = (name, count);
+        Ok(())
+    }
+
+    /// Called by [`Searcher::search_paths`] when `name` could not be read,
+    /// parsed, or searched. Returning `Ok` skips the file and carries on
+    /// with the next (grep semantics); the default stops the search with
+    /// `err`.
+    ///
+    /// # Errors
+    ///
+    /// An error stops the search.
+    fn file_error(&mut self, name: &str, err: anyhow::Error) -> Result<()> {
+        let _ = name;
+        Err(err)
+    }
+
+    /// Called by [`Searcher::search_paths`] after the last input, with the
+    /// totals over all inputs.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned by the search.
+    fn summary(&mut self, summary: &SearchSummary) -> Result<()> {
+        let _ = summary;
+        Ok(())
+    }
+}
+
+impl<F> Sink for F
+where
+    F: FnMut(&str, &[PathType], &Value) -> Result<()>,
+{
+    fn matched(
+        &mut self,
+        name: &str,
+        path: &[PathType],
+        value: &Value,
+    ) -> Result<()> {
+        self(name, path, value)
+    }
+}
+
+/// What a [`Searcher`] reports for each input.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SearchMode {
+    /// Every match, through [`Sink::matched`].
+    #[default]
+    Matches,
+    /// Only the number of matches, through [`Sink::end_file`]; matches are
+    /// counted without building their paths or values (`jg --count`).
+    Count,
+    /// Only whether there is a match (a count of 0 or 1), stopping at the
+    /// first one (`jg -l` and `jg -q`).
+    Any,
+}
+
+/// Totals over the inputs of [`Searcher::search_paths`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct SearchSummary {
+    /// Inputs searched successfully.
+    pub searched: usize,
+    /// Inputs with at least one match.
+    pub matched: usize,
+    /// Matches across all inputs.
+    pub matches: usize,
+    /// Inputs that could not be read, parsed, or searched.
+    pub failed: usize,
+}
+
+/// Runs one compiled query over files, readers, or byte slices and reports
+/// the results to a [`Sink`].
+///
+/// Built with [`Searcher::new`] and configured with chained setters; the
+/// defaults match plain `jg QUERY FILE...`.
+#[derive(Debug, Clone)]
+pub struct Searcher<'q> {
+    dfa: &'q QueryDFA,
+    format: Format,
+    parser: Option<&'q dyn FormatParser>,
+    registry: Option<&'q FormatRegistry>,
+    mode: SearchMode,
+    max_count: Option<usize>,
+    max_depth: Option<usize>,
+    parallel: bool,
+}
+
+impl<'q> Searcher<'q> {
+    /// A searcher for `dfa` that auto-detects formats and reports every
+    /// match.
+    #[must_use]
+    pub const fn new(dfa: &'q QueryDFA) -> Self {
+        Self {
+            dfa,
+            format: Format::Auto,
+            parser: None,
+            registry: None,
+            mode: SearchMode::Matches,
+            max_count: None,
+            max_depth: None,
+            parallel: false,
+        }
+    }
+
+    /// Parse every input as `format`. With [`Format::Auto`] (the default),
+    /// the format is detected (see [`Searcher::registry`]).
+    #[must_use]
+    pub const fn format(mut self, format: Format) -> Self {
+        self.format = format;
+        self.parser = None;
+        self
+    }
+
+    /// Parse every input with `parser`, e.g. one looked up by name in a
+    /// [`FormatRegistry`].
+    #[must_use]
+    pub const fn parser(mut self, parser: &'q dyn FormatParser) -> Self {
+        self.parser = Some(parser);
+        self
+    }
+
+    /// Detect the format of each input with `registry` (see
+    /// [`FormatRegistry::resolve`]) unless a format or parser is set.
+    /// Without a registry, files are detected by their extension (see
+    /// [`detect_format`]) and readers and slices are parsed as JSON.
+    #[must_use]
+    pub const fn registry(mut self, registry: &'q FormatRegistry) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    /// What to report for each input.
+    #[must_use]
+    pub const fn mode(mut self, mode: SearchMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Report at most `max` matches per input (like `grep -m`). Searches
+    /// stop once `max` matches are found; large JSON and non-JSON inputs are
+    /// then not read any further, so errors after the last match may go
+    /// unreported.
+    #[must_use]
+    pub const fn max_count(mut self, max: Option<usize>) -> Self {
+        self.max_count = max;
+        self
+    }
+
+    /// Fail on inputs nested deeper than `max` levels, as measured by
+    /// [`depth`]. Checking the depth needs the whole document, so this
+    /// disables the streaming and deserializing engines.
+    #[must_use]
+    pub const fn max_depth(mut self, max: Option<usize>) -> Self {
+        self.max_depth = max;
+        self
+    }
+
+    /// Whether to parse large JSON inputs whole and search them on all
+    /// cores instead of streaming them, trading memory for speed. Inputs
+    /// that are parsed anyway are always searched in parallel.
+    #[cfg(feature = "rayon")]
+    #[must_use]
+    pub const fn parallel(mut self, yes: bool) -> Self {
+        self.parallel = yes;
+        self
+    }
+
+    /// Search the file at `path`, named by its display form, returning its
+    /// number of matches.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or parsed, exceeds
+    /// [`Searcher::max_depth`], or the sink fails.
+    pub fn search_path<S: Sink>(
+        &self,
+        path: &Path,
+        sink: &mut S,
+    ) -> Result<usize> {
+        let name = path.display().to_string();
+        let input = Input::from_path(path)?;
+        let parser = self.resolve(Some(path), input.as_bytes());
+        self.search_bytes(
+            &name,
+            input.as_bytes(),
+            input.is_large_mmap(),
+            parser,
+            sink,
+        )
+    }
+
+    /// Search everything `reader` yields, reported as `name`.
+    ///
+    /// # Errors
+    ///
+    /// As [`Searcher::search_path`].
+    pub fn search_reader<R: Read, S: Sink>(
+        &self,
+        name: &str,
+        reader: R,
+        sink: &mut S,
+    ) -> Result<usize> {
+        let input = Input::from_reader(reader)
+            .with_context(|| format!("Failed to read {name}"))?;
+        self.search_slice(name, input.as_bytes(), sink)
+    }
+
+    /// Search `bytes`, reported as `name`.
+    ///
+    /// # Errors
+    ///
+    /// As [`Searcher::search_path`].
+    pub fn search_slice<S: Sink>(
+        &self,
+        name: &str,
+        bytes: &[u8],
+        sink: &mut S,
+    ) -> Result<usize> {
+        let large = bytes.len() as u64 >= STREAM_MIN_FILE_SIZE;
+        let parser = self.resolve(None, bytes);
+        self.search_bytes(name, bytes, large, parser, sink)
+    }
+
+    /// Search each of `paths` in turn. A file that cannot be read, parsed,
+    /// or searched is handed to [`Sink::file_error`] and, if that returns
+    /// `Ok`, skipped (grep semantics).
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error from the sink, including a broken pipe
+    /// while writing (see [`is_broken_pipe`]), which always stops the
+    /// search rather than counting as a failed file.
+    pub fn search_paths<P, S>(
+        &self,
+        paths: impl IntoIterator<Item = P>,
+        sink: &mut S,
+    ) -> Result<SearchSummary>
+    where
+        P: AsRef<Path>,
+        S: Sink,
+    {
+        let mut summary = SearchSummary::default();
+        for path in paths {
+            let path = path.as_ref();
+            match self.search_path(path, sink) {
+                Ok(count) => {
+                    summary.searched += 1;
+                    summary.matches += count;
+                    if count > 0 {
+                        summary.matched += 1;
+                    }
+                }
+                Err(err) if is_broken_pipe(&err) => return Err(err),
+                Err(err) => {
+                    sink.file_error(&path.display().to_string(), err)?;
+                    summary.failed += 1;
+                }
+            }
+        }
+        sink.summary(&summary)?;
+        Ok(summary)
+    }
+
+    /// How to parse the input at `path` (if any) holding `bytes`.
+    fn resolve(&self, path: Option<&Path>, bytes: &[u8]) -> Parser<'q> {
+        if let Some(parser) = self.parser {
+            return Parser::from(parser);
+        }
+        match self.registry {
+            Some(registry) if self.format == Format::Auto => {
+                Parser::from(registry.resolve(path, bytes))
+            }
+            _ => Parser::Builtin(detect_format(path, self.format)),
+        }
+    }
+
+    /// Search one input's bytes, to be parsed with `parser`. `large`
+    /// inputs are searched without building the document when possible.
+    fn search_bytes<S: Sink>(
+        &self,
+        name: &str,
+        bytes: &[u8],
+        large: bool,
+        parser: Parser,
+        sink: &mut S,
+    ) -> Result<usize> {
+        sink.begin_file(name, parser.name())?;
+        let count = match self.mode {
+            SearchMode::Matches => {
+                let with_paths = sink.wants_paths();
+                self.for_each_match(
+                    bytes,
+                    large,
+                    parser,
+                    with_paths,
+                    |path, value| sink.matched(name, path, value),
+                )?
+            }
+            SearchMode::Count => {
+                self.count_matches(bytes, large, parser, false)?
+            }
+            SearchMode::Any => {
+                self.count_matches(bytes, large, parser, true)?
+            }
+        };
+        sink.end_file(name, count)?;
+        Ok(count)
+    }
+
+    /// Runs the query against the input, calls `f` with the path and value
+    /// of each match in document order, and returns the number of matches.
+    /// Paths are only guaranteed when `with_paths` is set; otherwise `f` may
+    /// receive an empty path.
+    ///
+    /// Large JSON inputs are searched with the streaming engine, which hands
+    /// each match to `f` as soon as it is found, so memory does not grow
+    /// with the input or the matches and output starts before the scan ends;
+    /// a syntax error later in the input is reported after the matches
+    /// before it. Other formats are searched with DFA-guided
+    /// deserialization, so the whole document is never materialized;
+    /// everything else is parsed and searched as a tree.
+    ///
+    /// With the `rayon` feature, tree searches run on all cores (see
+    /// [`QueryDFA::par_find_matches`]); large JSON inputs are only parsed
+    /// into a tree for it with [`Searcher::parallel`].
+    fn for_each_match<F>(
+        &self,
+        bytes: &[u8],
+        large: bool,
+        parser: Parser,
+        with_paths: bool,
+        mut f: F,
+    ) -> Result<usize>
+    where
+        F: FnMut(&[PathType], &Value) -> Result<()>,
+    {
+        let dfa = self.dfa;
+        let max = self.max_count.unwrap_or(usize::MAX);
+        // Measuring the depth needs the whole document.
+        let matches_only = self.max_depth.is_none();
+
+        if matches_only
+            && !self.parallel
+            && let Parser::Builtin(format @ (Format::Json | Format::Auto)) =
+                parser
+            && large
+        {
+            let text =
+                str::from_utf8(bytes).context("Input is not valid UTF-8")?;
+            let mut count = 0;
+            let mut failed = None;
+            dfa.try_find_stream_with(text, |m| {
+                if count == max {
+                    return ControlFlow::Break(());
+                }
+                let handled = m
+                    .parse()
+                    .with_context(|| format!("Failed to parse as {format}"))
+                    .and_then(|value| f(&m.path, &value));
+                if let Err(err) = handled {
+                    failed = Some(err);
+                    return ControlFlow::Break(());
+                }
+                count += 1;
+                if count < max {
+                    ControlFlow::Continue(())
+                } else {
+                    ControlFlow::Break(())
+                }
+            })
+            .with_context(|| format!("Failed to parse as {format}"))?;
+            return failed.map_or(Ok(count), Err);
+        }
+
+        if matches_only
+            && let Parser::Builtin(format) = parser
+            && let Some(found) = find_deserialize(bytes, dfa, format, max)?
+        {
+            let found = Found::Owned(found);
+            found.try_for_each(with_paths, &mut f)?;
+            return Ok(found.len());
+        }
+
+        with_bytes_json(bytes, parser, |json| {
+            check_depth(json, self.max_depth)?;
+
+            if let Some(max) = self.max_count {
+                let limits = SearchLimits {
+                    max_matches: Some(max),
+                    ..Default::default()
+                };
+                let found = dfa.find_with_limits(json, &limits).results;
+                let found = Found::Limited(found);
+                found.try_for_each(with_paths, &mut f)?;
+                return Ok(found.len());
+            }
+
+            #[cfg(feature = "rayon")]
+            let matches = dfa.par_find_matches(json);
+            #[cfg(not(feature = "rayon"))]
+            let matches = dfa.find_matches(json);
+            let found = Found::Tree(matches);
+            found.try_for_each(with_paths, &mut f)?;
+            Ok(found.len())
+        })
+    }
+
+    /// Counts the matches in the input without materializing them, up to
+    /// the max count, or up to one when `any` is set. Only the max count
+    /// stops the streaming engine early: without it, the whole input is
+    /// validated whether or not it matches.
+    ///
+    /// Large JSON inputs are counted with the streaming engine; everything
+    /// else is parsed and counted with [`QueryDFA::count_up_to`].
+    fn count_matches(
+        &self,
+        bytes: &[u8],
+        large: bool,
+        parser: Parser,
+        any: bool,
+    ) -> Result<usize> {
+        let dfa = self.dfa;
+        let max = self.max_count.unwrap_or(usize::MAX);
+        let limit = if any { max.min(1) } else { max };
+        if self.max_depth.is_none()
+            && let Parser::Builtin(format @ (Format::Json | Format::Auto)) =
+                parser
+            && large
+        {
+            let text =
+                str::from_utf8(bytes).context("Input is not valid UTF-8")?;
+            let mut count = 0;
+            dfa.try_find_stream_with(text, |_| {
+                count = (count + 1).min(max);
+                if count < max {
+                    ControlFlow::Continue(())
+                } else {
+                    ControlFlow::Break(())
+                }
+            })
+            .with_context(|| format!("Failed to parse as {format}"))?;
+            return Ok(count.min(limit));
+        }
+
+        with_bytes_json(bytes, parser, |json| {
+            check_depth(json, self.max_depth)?;
+            Ok(dfa.count_up_to(json, limit))
+        })
+    }
+}
+
+/// Query matches for one input, from the tree-walking engine or DFA-guided
+/// deserialization.
+enum Found<'a> {
+    /// Matches from [`QueryDFA::find_matches`] over a parsed document.
+    Tree(Matches<'a>),
+    /// Matches from [`QueryDFA::find_deserialize`] over a non-JSON format.
+    Owned(Vec<OwnedMatch>),
+    /// Matches from [`QueryDFA::find_with_limits`], for a max count.
+    Limited(Vec<JSONPointer<'a>>),
+}
+
+impl Found<'_> {
+    const fn len(&self) -> usize {
+        match self {
+            Self::Tree(matches) => matches.len(),
+            Self::Owned(matches) => matches.len(),
+            Self::Limited(matches) => matches.len(),
+        }
+    }
+
+    /// Calls `f` with the path and value of each match in document order.
+    /// Tree paths are only materialized when `with_path` is set; otherwise
+    /// `f` receives an empty path.
+    fn try_for_each<F>(&self, with_path: bool, mut f: F) -> Result<()>
+    where
+        F: FnMut(&[PathType], &Value) -> Result<()>,
+    {
+        match self {
+            Self::Tree(matches) => {
+                for m in matches.iter() {
+                    let path = if with_path { m.to_path() } else { vec![] };
+                    f(&path, m.value)?;
+                }
+            }
+            Self::Owned(matches) => {
+                for m in matches {
+                    f(&m.path, &Value::from(&m.value))?;
+                }
+            }
+            Self::Limited(matches) => {
+                for m in matches {
+                    f(&m.path, m.value)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Unit testing.")]
+mod tests {
+    use super::*;
+    use std::io::Write as _;
+
+    /// Records every event, as `event:name[:detail]` lines.
+    #[derive(Default)]
+    struct Recorder(Vec<String>);
+
+    impl Sink for Recorder {
+        fn begin_file(&mut self, name: &str, format: &str) -> Result<()> {
+            self.0.push(format!("begin:{name}:{format}"));
+            Ok(())
+        }
+
+        fn matched(
+            &mut self,
+            name: &str,
+            path: &[PathType],
+            value: &Value,
+        ) -> Result<()> {
+            let path: Vec<_> = path.iter().map(ToString::to_string).collect();
+            self.0.push(format!("match:{name}:{}={value}", path.join(".")));
+            Ok(())
+        }
+
+        fn end_file(&mut self, name: &str, count: usize) -> Result<()> {
+            self.0.push(format!("end:{name}:{count}"));
+            Ok(())
+        }
+
+        fn file_error(&mut self, name: &str, _: anyhow::Error) -> Result<()> {
+            self.0.push(format!("error:{name}"));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn detect_format_prefers_explicit_then_extension() {
+        let yaml = Path::new("a.yml");
+        assert_eq!(detect_format(Some(yaml), Format::Auto), Format::Yaml);
+        assert_eq!(detect_format(Some(yaml), Format::Toml), Format::Toml);
+        assert_eq!(
+            detect_format(Some(Path::new("a.ndjson")), Format::Auto),
+            Format::Jsonl
+        );
+        assert_eq!(detect_format(None, Format::Auto), Format::Json);
+    }
+
+    #[test]
+    fn parse_jsonl_reports_the_bad_line() {
+        let json = parse_jsonl("{\"a\": 1}\n\n[2]\n").unwrap();
+        assert_eq!(json.to_string(), r#"[{"a":1},[2]]"#);
+
+        let err = parse_jsonl("1\n\n{\n").unwrap_err();
+        assert_eq!(err.to_string(), "Failed to parse JSONL line 3");
+    }
+
+    #[test]
+    fn search_modes_report_matches_or_counts() {
+        let dfa = QueryDFA::from_query_str("a[*]").unwrap();
+        let input = br#"{"a": [1, 2, 3]}"#;
+
+        let mut sink = Recorder::default();
+        let searcher = Searcher::new(&dfa).max_count(Some(2));
+        assert_eq!(searcher.search_slice("in", input, &mut sink).unwrap(), 2);
+        assert_eq!(
+            sink.0,
+            [
+                "begin:in:json",
+                "match:in:a.[0]=1",
+                "match:in:a.[1]=2",
+                "end:in:2"
+            ]
+        );
+
+        for (mode, count) in [(SearchMode::Count, 3), (SearchMode::Any, 1)] {
+            let mut sink = Recorder::default();
+            let searcher = Searcher::new(&dfa).mode(mode);
+            assert_eq!(
+                searcher.search_slice("in", input, &mut sink).unwrap(),
+                count
+            );
+            assert_eq!(
+                sink.0,
+                ["begin:in:json".to_string(), format!("end:in:{count}")]
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_json_fails_in_every_mode_at_every_size() {
+        let dfa = QueryDFA::from_query_str("keep").unwrap();
+        let input = br#"{"skip": [1, bogus, {]], "keep": 1}"#;
+        for mode in [SearchMode::Matches, SearchMode::Count, SearchMode::Any] {
+            for large in [false, true] {
+                let searcher = Searcher::new(&dfa).mode(mode);
+                let parser = searcher.resolve(None, input);
+                let mut sink = Recorder::default();
+                let result = searcher
+                    .search_bytes("in", input, large, parser, &mut sink);
+                assert!(result.is_err(), "{mode:?}, large: {large}");
+            }
+        }
+    }
+
+    #[test]
+    fn max_count_stops_the_search() {
+        let dfa = QueryDFA::from_query_str("[*]").unwrap();
+        let input = b"[1, 2, 3, oops";
+        for mode in [SearchMode::Matches, SearchMode::Count, SearchMode::Any] {
+            let searcher = Searcher::new(&dfa).mode(mode).max_count(Some(2));
+            let parser = searcher.resolve(None, input);
+            let mut sink = Recorder::default();
+            let count = searcher
+                .search_bytes("in", input, true, parser, &mut sink)
+                .unwrap();
+            assert_eq!(count, if mode == SearchMode::Any { 1 } else { 2 });
+            // Small inputs are parsed whole.
+            assert!(searcher.search_slice("in", input, &mut sink).is_err());
+        }
+
+        // Without a max count, the whole input is read.
+        let searcher = Searcher::new(&dfa).mode(SearchMode::Any);
+        let parser = searcher.resolve(None, input);
+        let mut sink = Recorder::default();
+        assert!(
+            searcher
+                .search_bytes("in", input, true, parser, &mut sink)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn streamed_matches_reach_the_sink_as_they_are_found() {
+        let dfa = QueryDFA::from_query_str("[*]").unwrap();
+        let input = b"[1, {\"a\": 2}, oops";
+        let searcher = Searcher::new(&dfa);
+        let parser = searcher.resolve(None, input);
+        let mut sink = Recorder::default();
+        let err = searcher
+            .search_bytes("in", input, true, parser, &mut sink)
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("Failed to parse"), "{err:#}");
+        assert_eq!(
+            sink.0,
+            ["begin:in:json", "match:in:[0]=1", "match:in:[1]={\"a\":2}"]
+        );
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn large_json_is_streamed_unless_parallel() {
+        let dfa = QueryDFA::from_query_str("a").unwrap();
+        let input = br#"{"a": 1, "b": x}"#;
+        let error = |parallel| {
+            let searcher = Searcher::new(&dfa).parallel(parallel);
+            let parser = searcher.resolve(None, input);
+            let mut sink = Recorder::default();
+            let err = searcher
+                .search_bytes("in", input, true, parser, &mut sink)
+                .unwrap_err();
+            format!("{err:#}")
+        };
+        // Only the streaming engine reports byte offsets.
+        assert!(error(false).contains("invalid JSON at byte"));
+        assert!(!error(true).contains("invalid JSON at byte"));
+    }
+
+    #[test]
+    fn search_paths_skips_files_that_fail() {
+        let dir = tempfile::tempdir().unwrap();
+        let good = dir.path().join("good.json");
+        let bad = dir.path().join("bad.json");
+        writeln!(std::fs::File::create(&good).unwrap(), r#"{{"a": [1]}}"#)
+            .unwrap();
+        writeln!(std::fs::File::create(&bad).unwrap(), "{{").unwrap();
+        let missing = dir.path().join("missing.json");
+
+        let dfa = QueryDFA::from_query_str("a").unwrap();
+        let mut sink = Recorder::default();
+        let summary = Searcher::new(&dfa)
+            .search_paths([&bad, &good, &missing], &mut sink)
+            .unwrap();
+
+        assert_eq!(
+            summary,
+            SearchSummary { searched: 1, matched: 1, matches: 1, failed: 2 }
+        );
+        let (bad, good, missing) =
+            (bad.display(), good.display(), missing.display());
+        assert_eq!(
+            sink.0,
+            [
+                format!("begin:{bad}:json"),
+                format!("error:{bad}"),
+                format!("begin:{good}:json"),
+                format!("match:{good}:a=[1]"),
+                format!("end:{good}:1"),
+                format!("error:{missing}"),
+            ]
+        );
+    }
+}
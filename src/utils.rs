@@ -2,25 +2,34 @@
 
 use anyhow::Context as _;
 use colored::Colorize;
-use serde_json_borrow::Value;
+use serde_json_borrow::{ObjectAsVec, Value};
 use std::io::Write;
 use std::io::{self, ErrorKind};
 
-use crate::query::PathType;
+use crate::query::{PathType, ast::quote_field};
 
 /// Returns the depth of the JSON value.
+///
+/// Uses an explicit stack rather than recursion, so arbitrarily deep
+/// documents are measured without overflowing the call stack.
+#[must_use]
 pub fn depth(json: &Value) -> usize {
-    match json {
-        Value::Null | Value::Bool(_) | Value::Number(_) | Value::Str(_) => 1,
-        Value::Array(arr) => {
-            let inner_depth = arr.iter().map(depth).max().unwrap_or(0);
-            1 + inner_depth
-        }
-        Value::Object(map) => {
-            let inner_depth = map.values().map(depth).max().unwrap_or(0);
-            1 + inner_depth
+    let mut max_depth = 0;
+    let mut stack = vec![(json, 1)];
+    while let Some((value, level)) = stack.pop() {
+        max_depth = max_depth.max(level);
+        match value {
+            Value::Array(arr) => {
+                stack.extend(arr.iter().map(|item| (item, level + 1)));
+            }
+            Value::Object(map) => {
+                stack.extend(map.values().map(|val| (val, level + 1)));
+            }
+            Value::Null | Value::Bool(_) | Value::Number(_) | Value::Str(_) => {
+            }
         }
     }
+    max_depth
 }
 
 // ==============================================================================
@@ -28,7 +37,7 @@ pub fn depth(json: &Value) -> usize {
 // ==============================================================================
 
 /// Available options for printing matches.
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Copy)]
 pub struct WriteOptions {
     /// Whether to pretty print the output.
     pub pretty: bool,
@@ -36,6 +45,146 @@ pub struct WriteOptions {
     pub show_path: bool,
     /// Whether to print the raw output or auto-escape characters.
     pub raw: bool,
+    /// The syntax of the path headers.
+    pub path_format: PathFormat,
+}
+
+/// Syntax for printing the path of a match (see [`format_path`]).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum PathFormat {
+    /// jsongrep query syntax, e.g. `a.[0]."b.c"`, which can be fed back in
+    /// as a query matching exactly that path.
+    #[default]
+    Jg,
+    /// JSON Pointer (RFC 6901), e.g. `/a/0/b.c`.
+    Pointer,
+    /// `JSONPath` (RFC 9535), e.g. `$.a[0]['b.c']`.
+    #[value(name = "jsonpath")]
+    JsonPath,
+    /// A jq path expression, e.g. `.a[0]["b.c"]`.
+    Jq,
+    /// A JSON array of field names and indices, e.g. `["a",0,"b.c"]`.
+    Array,
+}
+
+/// Render a match path in the given syntax.
+#[must_use]
+pub fn format_path(path: &[PathType], format: PathFormat) -> String {
+    PathDisplay(path, format).to_string()
+}
+
+/// A match path, displayed in a [`PathFormat`].
+struct PathDisplay<'a>(&'a [PathType], PathFormat);
+
+impl std::fmt::Display for PathDisplay<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self(path, format) = *self;
+        match format {
+            PathFormat::Jg => {
+                for (i, part) in path.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ".")?;
+                    }
+                    match part {
+                        PathType::Index(idx) => write!(f, "[{idx}]")?,
+                        PathType::Field(name) => {
+                            write!(f, "{}", quote_field(name))?;
+                        }
+                    }
+                }
+            }
+            PathFormat::Pointer => {
+                for part in path {
+                    match part {
+                        PathType::Index(idx) => write!(f, "/{idx}")?,
+                        PathType::Field(name) => write!(
+                            f,
+                            "/{}",
+                            name.replace('~', "~0").replace('/', "~1")
+                        )?,
+                    }
+                }
+            }
+            PathFormat::JsonPath => {
+                write!(f, "$")?;
+                for part in path {
+                    match part {
+                        PathType::Index(idx) => write!(f, "[{idx}]")?,
+                        PathType::Field(name) if is_identifier(name) => {
+                            write!(f, ".{name}")?;
+                        }
+                        PathType::Field(name) => {
+                            // Escaped as in RFC 9535 normalized paths.
+                            write!(f, "['")?;
+                            for c in name.chars() {
+                                match c {
+                                    '\'' => write!(f, "\\'")?,
+                                    '\\' => write!(f, "\\\\")?,
+                                    '\u{8}' => write!(f, "\\b")?,
+                                    '\u{c}' => write!(f, "\\f")?,
+                                    '\n' => write!(f, "\\n")?,
+                                    '\r' => write!(f, "\\r")?,
+                                    '\t' => write!(f, "\\t")?,
+                                    c if c < ' ' => {
+                                        write!(f, "\\u{:04x}", u32::from(c))?;
+                                    }
+                                    c => write!(f, "{c}")?,
+                                }
+                            }
+                            write!(f, "']")?;
+                        }
+                    }
+                }
+            }
+            PathFormat::Jq => {
+                for (i, part) in path.iter().enumerate() {
+                    match part {
+                        PathType::Field(name) if is_identifier(name) => {
+                            write!(f, ".{name}")?;
+                        }
+                        // Brackets follow a step directly, but need a leading
+                        // `.` at the start: `.[0]`, `.["a b"]`.
+                        _ if i == 0 => write!(f, ".")?,
+                        _ => {}
+                    }
+                    match part {
+                        PathType::Index(idx) => write!(f, "[{idx}]")?,
+                        PathType::Field(name) if !is_identifier(name) => {
+                            write!(
+                                f,
+                                "[{}]",
+                                serde_json::Value::from(&**name)
+                            )?;
+                        }
+                        PathType::Field(_) => {}
+                    }
+                }
+                if path.is_empty() {
+                    write!(f, ".")?;
+                }
+            }
+            PathFormat::Array => {
+                let parts: serde_json::Value = path
+                    .iter()
+                    .map(|part| match part {
+                        PathType::Index(idx) => serde_json::Value::from(*idx),
+                        PathType::Field(name) => {
+                            serde_json::Value::from(&**name)
+                        }
+                    })
+                    .collect();
+                write!(f, "{parts}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether `name` can be written as `.name` in `JSONPath` and jq.
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
 }
 
 /// Write a single query result (path header + colorized JSON value) to `writer`.
@@ -64,13 +213,7 @@ pub fn write_colored_result<W: Write>(
         if options.show_path && !path.is_empty() {
             // Only pay for building the joined path string when it is
             // actually shown.
-            let mut header = String::new();
-            for (i, part) in path.iter().enumerate() {
-                if i > 0 {
-                    header.push('.');
-                }
-                header.push_str(&part.to_string());
-            }
+            let header = format_path(path, options.path_format);
             writeln!(writer, "{}:", header.bold().magenta())?;
         }
         if options.raw
@@ -94,53 +237,71 @@ pub fn write_colored_result<W: Write>(
     }
 }
 
-/// Recursively write a JSON value with syntax highlighting.
-fn write_colored_json<W: Write>(
+/// An array or object that [`write_colored_json`] has opened but not yet
+/// closed.
+enum OpenContainer<'v> {
+    Array(&'v [Value<'v>]),
+    Object(&'v ObjectAsVec<'v>),
+}
+
+impl OpenContainer<'_> {
+    fn len(&self) -> usize {
+        match self {
+            Self::Array(arr) => arr.len(),
+            Self::Object(obj) => obj.len(),
+        }
+    }
+}
+
+/// Write a JSON value with syntax highlighting.
+///
+/// Open containers are kept on an explicit stack rather than recursing, so
+/// arbitrarily deep values are written without overflowing the call stack.
+pub(crate) fn write_colored_json<W: Write>(
     writer: &mut W,
     value: &Value,
     indent: usize,
     pretty: bool,
 ) -> io::Result<()> {
-    let next_indent = indent + 2;
+    // (container, index of the next child, indent of the container) for
+    // each container being written.
+    let mut stack: Vec<(OpenContainer, usize, usize)> = Vec::new();
+    if let Some(open) = write_colored_scalar_or_open(writer, value)? {
+        stack.push((open, 0, indent));
+    }
 
-    match value {
-        Value::Null => write!(writer, "{}", "null".red().dimmed()),
-        Value::Bool(b) => {
-            write!(writer, "{}", b.to_string().yellow().bold())
-        }
-        Value::Number(n) => write!(writer, "{}", n.to_string().yellow()),
-        Value::Str(s) => {
-            // NOTE: Re-serialize to get proper JSON escaping and quoting.
-            let quoted = serde_json::to_string(s.as_ref())
-                .expect("string serialization cannot fail");
-            write!(writer, "{}", quoted.green())
-        }
-        Value::Array(arr) => {
-            write!(writer, "[")?;
-            for (i, item) in arr.iter().enumerate() {
-                if pretty {
-                    writeln!(writer)?;
-                    write!(writer, "{:width$}", "", width = next_indent)?;
-                }
-                write_colored_json(writer, item, next_indent, pretty)?;
-                if i < arr.len() - 1 {
-                    write!(writer, ",")?;
-                }
-            }
-            if pretty && !arr.is_empty() {
+    while let Some((container, next, indent)) = stack.last_mut() {
+        let indent = *indent;
+        let next_indent = indent + 2;
+        let len = container.len();
+
+        if *next == len {
+            // Every child written: close the container.
+            if pretty && len > 0 {
                 writeln!(writer)?;
                 write!(writer, "{:width$}", "", width = indent)?;
             }
-            write!(writer, "]")
+            match container {
+                OpenContainer::Array(_) => write!(writer, "]")?,
+                OpenContainer::Object(_) => write!(writer, "}}")?,
+            }
+            stack.pop();
+            continue;
         }
-        Value::Object(obj) => {
-            write!(writer, "{{")?;
-            let len = obj.len();
-            for (i, (key, val)) in obj.iter().enumerate() {
-                if pretty {
-                    writeln!(writer)?;
-                    write!(writer, "{:width$}", "", width = next_indent)?;
-                }
+
+        if *next > 0 {
+            write!(writer, ",")?;
+        }
+        if pretty {
+            writeln!(writer)?;
+            write!(writer, "{:width$}", "", width = next_indent)?;
+        }
+        let child = match container {
+            OpenContainer::Array(arr) => &arr[*next],
+            OpenContainer::Object(obj) => {
+                let (key, val) = obj
+                    .get_key_value_at(*next)
+                    .expect("index is below the object's length");
                 // Key with quotes -> colored cyan.
                 let quoted_key = serde_json::to_string(key)
                     .expect("key serialization cannot fail");
@@ -150,18 +311,46 @@ fn write_colored_json<W: Write>(
                 } else {
                     write!(writer, ":")?;
                 }
-                write_colored_json(writer, val, next_indent, pretty)?;
-                if i < len - 1 {
-                    write!(writer, ",")?;
-                }
+                val
             }
-            if pretty && len > 0 {
-                writeln!(writer)?;
-                write!(writer, "{:width$}", "", width = indent)?;
-            }
-            write!(writer, "}}")
+        };
+        *next += 1;
+
+        if let Some(open) = write_colored_scalar_or_open(writer, child)? {
+            stack.push((open, 0, next_indent));
         }
     }
+    Ok(())
+}
+
+/// Writes a scalar `value` in full, or the opening bracket of an array or
+/// object, returning the container so the caller can write its children.
+fn write_colored_scalar_or_open<'v, W: Write>(
+    writer: &mut W,
+    value: &'v Value<'v>,
+) -> io::Result<Option<OpenContainer<'v>>> {
+    match value {
+        Value::Null => write!(writer, "{}", "null".red().dimmed())?,
+        Value::Bool(b) => {
+            write!(writer, "{}", b.to_string().yellow().bold())?;
+        }
+        Value::Number(n) => write!(writer, "{}", n.to_string().yellow())?,
+        Value::Str(s) => {
+            // NOTE: Re-serialize to get proper JSON escaping and quoting.
+            let quoted = serde_json::to_string(s.as_ref())
+                .expect("string serialization cannot fail");
+            write!(writer, "{}", quoted.green())?;
+        }
+        Value::Array(arr) => {
+            write!(writer, "[")?;
+            return Ok(Some(OpenContainer::Array(arr)));
+        }
+        Value::Object(obj) => {
+            write!(writer, "{{")?;
+            return Ok(Some(OpenContainer::Object(obj)));
+        }
+    }
+    Ok(None)
 }
 
 #[cfg(test)]
@@ -223,4 +412,82 @@ mod tests {
         );
         assert!(result.is_err(), "non-pipe IO errors should propagate");
     }
+
+    #[test]
+    fn deeply_nested_values_are_measured_and_written_without_recursion() {
+        const DEPTH: usize = 200_000;
+        let mut value = Value::Null;
+        for _ in 0..DEPTH {
+            value = Value::Array(vec![value]);
+        }
+        assert_eq!(depth(&value), DEPTH + 1);
+
+        let mut out = Vec::new();
+        write_colored_json(&mut out, &value, 0, false).unwrap();
+        assert_eq!(out.len(), 2 * DEPTH + "null".len());
+
+        // Dropping the value would itself recurse once per level.
+        while let Value::Array(mut items) = value {
+            value = items.pop().unwrap();
+        }
+    }
+
+    #[test]
+    fn write_colored_json_pretty_layout() {
+        colored::control::set_override(false);
+        let value: Value =
+            serde_json::from_str(r#"{"a": [1, {}], "b": []}"#).unwrap();
+        let mut out = Vec::new();
+        write_colored_json(&mut out, &value, 0, true).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\n  \"a\": [\n    1,\n    {}\n  ],\n  \"b\": []\n}"
+        );
+    }
+
+    #[test]
+    fn format_path_renders_each_syntax() {
+        let path = [
+            PathType::Field("a".into()),
+            PathType::Index(0),
+            PathType::Field("b.c/d~'".into()),
+        ];
+        let cases = [
+            (PathFormat::Jg, r#"a.[0]."b.c/d~'""#),
+            (PathFormat::Pointer, "/a/0/b.c~1d~0'"),
+            (PathFormat::JsonPath, r"$.a[0]['b.c/d~\'']"),
+            (PathFormat::Jq, r#".a[0]["b.c/d~'"]"#),
+            (PathFormat::Array, r#"["a",0,"b.c/d~'"]"#),
+        ];
+        for (format, expected) in cases {
+            assert_eq!(format_path(&path, format), expected, "{format:?}");
+        }
+        assert_eq!(
+            format_path(
+                &[PathType::Field("l\n\t\r\u{8}\u{c}\u{1f}\\".into())],
+                PathFormat::JsonPath
+            ),
+            r"$['l\n\t\r\b\f\u001f\\']"
+        );
+        assert_eq!(format_path(&[PathType::Index(1)], PathFormat::Jq), ".[1]");
+        assert_eq!(format_path(&[], PathFormat::Jq), ".");
+        assert_eq!(format_path(&[], PathFormat::Pointer), "");
+    }
+
+    #[test]
+    fn jg_paths_round_trip_as_queries() {
+        use crate::query::QueryDFA;
+
+        let json = r#"{"a.b": [{"x y": 1, "": 2, "q\"\\": 3, "[0]": 4, "l\n\t\u0001": 5}]}"#;
+        let value: Value = serde_json::from_str(json).unwrap();
+        let all = QueryDFA::from_query_str("(* | [*])*").unwrap().find(&value);
+        assert_eq!(all.len(), 8);
+
+        for m in all {
+            let query = format_path(&m.path, PathFormat::Jg);
+            let found = QueryDFA::from_query_str(&query).unwrap().find(&value);
+            assert_eq!(found.len(), 1, "{query}");
+            assert_eq!(found[0].path, m.path, "{query}");
+        }
+    }
 }
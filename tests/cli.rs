@@ -12,10 +12,12 @@ const SIMPLE_JSONL_FILEPATH: &str = "tests/data/simple/simple.jsonl";
 const SIMPLE_YAML_FILEPATH: &str = "tests/data/simple/simple.yaml";
 
 /// Path to the `simple.toml` file.
+#[cfg(feature = "toml")]
 const SIMPLE_TOML_FILEPATH: &str = "tests/data/simple/simple.toml";
 
 /// The canonical simple.json content, embedded at compile time for generating
 /// binary test fixtures (`CBOR`, `MessagePack`) without checking in opaque blobs.
+#[cfg(any(feature = "cbor", feature = "msgpack"))]
 const SIMPLE_JSON_STR: &str = include_str!("data/simple/simple.json");
 
 /// Helper function to run the `main` binary with the given arguments and return a
@@ -416,6 +418,75 @@ mod tests {
             .code(2);
     }
 
+    #[test]
+    fn max_count_stops_after_num_matches() {
+        let output = query_stdin_output(
+            &["-m", "2", "[*]", "--compact", "--no-path"],
+            "[1, 2, 3]",
+        );
+        assert_eq!(output, "1\n2\n");
+
+        let output = query_stdin_output(
+            &["--max-count", "2", "[*]", "--count", "--porcelain"],
+            "[1, 2, 3]",
+        );
+        assert_eq!(output, "2\n");
+
+        // Like `grep -m 0`: nothing is searched, so nothing matches.
+        Command::cargo_bin("jg")
+            .expect("Failed to find main binary")
+            .args(["-m", "0", "[*]"])
+            .write_stdin("[1, 2, 3]")
+            .assert()
+            .failure()
+            .code(1);
+    }
+
+    #[test]
+    fn max_count_conflicts_with_whole_document_edits() {
+        for edit in [
+            &["--set", "[*]", "0"][..],
+            &["--delete", "[*]"],
+            &["--redact", "[*]"],
+            &["--prune", "[*]"],
+        ] {
+            Command::cargo_bin("jg")
+                .expect("Failed to find main binary")
+                .args(["-m", "1"])
+                .args(edit)
+                .write_stdin("[1, 2, 3]")
+                .assert()
+                .failure()
+                .code(2);
+        }
+    }
+
+    #[test]
+    fn max_depth_rejects_deeper_inputs() {
+        // Depth 3, as reported by `--depth`.
+        let input = r#"{"a": {"b": 1}}"#;
+        let output = query_stdin_output(
+            &["--max-depth", "3", "a.b", "--no-path"],
+            input,
+        );
+        assert_eq!(output.trim(), "1");
+
+        for args in
+            [&["--max-depth", "2", "a.b"][..], &["--max-depth", "2", "-q", "a"]]
+        {
+            let assert = Command::cargo_bin("jg")
+                .expect("Failed to find main binary")
+                .args(args)
+                .write_stdin(input)
+                .assert()
+                .failure()
+                .code(2);
+            let stderr = String::from_utf8_lossy(&assert.get_output().stderr)
+                .into_owned();
+            assert!(stderr.contains("--max-depth 2"), "got: {stderr:?}");
+        }
+    }
+
     // ==============================================================================
     // Multiple file arguments
     // ==============================================================================
@@ -461,6 +532,7 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "yaml")]
     #[test]
     fn multi_file_mixed_formats() {
         let a = temp_file_with(".json", br#"{"host": "json-host"}"#);
@@ -684,6 +756,12 @@ mod tests {
 
     /// Reference outputs from the canonical JSON file, used to verify that
     /// each format produces identical results after conversion.
+    #[cfg(any(
+        feature = "yaml",
+        feature = "toml",
+        feature = "cbor",
+        feature = "msgpack"
+    ))]
     fn json_reference(query: &str) -> String {
         query_output(&[query, SIMPLE_JSON_FILEPATH, "--no-path", "--compact"])
     }
@@ -789,6 +867,34 @@ mod tests {
         assert_eq!(output.trim(), "42");
     }
 
+    #[test]
+    fn very_large_file_uses_streaming_search() {
+        // Files at or above the streaming threshold (64 MiB) are searched
+        // without building the document tree; matches, paths, and errors
+        // must be the same as for the tree engine.
+        let mut big = String::from(r#"{"pad": ""#);
+        big.push_str(&"x".repeat(65 * 1024 * 1024));
+        big.push_str(r#"", "items": [{"id": 1}, {"id": {"n": 2}}]}"#);
+        let tmp = temp_file_with(".json", big.as_bytes());
+        let path = tmp.path().to_str().expect("temp path");
+
+        let output =
+            query_output(&["items[*].id", path, "--with-path", "--compact"]);
+        assert_eq!(output, "items.[0].id:\n1\nitems.[1].id:\n{\"n\":2}\n");
+
+        // Counting goes through the same engine without keeping matches.
+        let output =
+            query_output(&["items[*].id", path, "--count", "--porcelain"]);
+        assert_eq!(output, "2\n");
+
+        // A truncated large file is still reported as a parse error.
+        let truncated =
+            temp_file_with(".json", &big.as_bytes()[..big.len() - 2]);
+        run_main(&["items", truncated.path().to_str().expect("temp path")])
+            .failure()
+            .code(2);
+    }
+
     #[test]
     fn jsonl_parse_error_reports_line_number() {
         // Line 2 is malformed; the error must point at the user's actual
@@ -874,6 +980,7 @@ mod tests {
 
     // ---------- YAML ----------
 
+    #[cfg(feature = "yaml")]
     #[test]
     fn yaml_scalar_field() {
         let output = query_output(&[
@@ -885,6 +992,7 @@ mod tests {
         assert_eq!(output.trim(), json_reference("age").trim());
     }
 
+    #[cfg(feature = "yaml")]
     #[test]
     fn yaml_nested_field() {
         let output = query_output(&[
@@ -900,6 +1008,7 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "yaml")]
     #[test]
     fn yaml_array_field() {
         let output = query_output(&[
@@ -915,6 +1024,24 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn yaml_skips_unmatched_subtrees_while_deserializing() {
+        // The complex mapping key under `other` has no JSON equivalent, but
+        // the query never enters it, so it is skipped rather than converted.
+        let tmp = temp_file_with(
+            ".yaml",
+            b"wanted: {id: 1}\nother:\n  ? [1, 2]\n  : pair\n",
+        );
+        let output = query_output(&[
+            "wanted.id",
+            tmp.path().to_str().expect("temp path"),
+            "--no-path",
+        ]);
+        assert_eq!(output.trim(), "1");
+    }
+
+    #[cfg(feature = "yaml")]
     #[test]
     fn yaml_explicit_format_flag() {
         let output = query_output(&[
@@ -930,6 +1057,7 @@ mod tests {
 
     // ---------- TOML ----------
 
+    #[cfg(feature = "toml")]
     #[test]
     fn toml_scalar_field() {
         let output = query_output(&[
@@ -941,6 +1069,7 @@ mod tests {
         assert_eq!(output.trim(), json_reference("age").trim());
     }
 
+    #[cfg(feature = "toml")]
     #[test]
     fn toml_nested_field() {
         let output = query_output(&[
@@ -956,6 +1085,7 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "toml")]
     #[test]
     fn toml_array_field() {
         let output = query_output(&[
@@ -971,6 +1101,7 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "toml")]
     #[test]
     fn toml_explicit_format_flag() {
         let output = query_output(&[
@@ -987,6 +1118,7 @@ mod tests {
     // ---------- CBOR ----------
 
     /// Generate a CBOR temp file from the canonical simple.json.
+    #[cfg(feature = "cbor")]
     fn cbor_temp(suffix: &str) -> tempfile::NamedTempFile {
         let value: serde_json::Value =
             serde_json::from_str(SIMPLE_JSON_STR).expect("parse simple.json");
@@ -996,6 +1128,7 @@ mod tests {
         temp_file_with(suffix, &cbor_buf)
     }
 
+    #[cfg(feature = "cbor")]
     #[test]
     fn cbor_via_stdin() {
         // Binary formats must work over stdin: input is read as raw bytes,
@@ -1012,6 +1145,7 @@ mod tests {
         assert_eq!(output.trim(), json_reference("age").trim());
     }
 
+    #[cfg(feature = "msgpack")]
     #[test]
     fn msgpack_via_stdin() {
         let value: serde_json::Value =
@@ -1025,6 +1159,7 @@ mod tests {
         assert_eq!(output.trim(), json_reference("name.first").trim());
     }
 
+    #[cfg(feature = "cbor")]
     #[test]
     fn cbor_scalar_field() {
         let tmp = cbor_temp(".cbor");
@@ -1037,6 +1172,7 @@ mod tests {
         assert_eq!(output.trim(), json_reference("age").trim());
     }
 
+    #[cfg(feature = "cbor")]
     #[test]
     fn cbor_nested_field() {
         let tmp = cbor_temp(".cbor");
@@ -1053,6 +1189,7 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "cbor")]
     #[test]
     fn cbor_explicit_format_flag() {
         let tmp = cbor_temp(".bin");
@@ -1070,6 +1207,7 @@ mod tests {
     // ---------- MessagePack ----------
 
     /// Generate a `MessagePack` temp file from the canonical simple.json.
+    #[cfg(feature = "msgpack")]
     fn msgpack_temp(suffix: &str) -> tempfile::NamedTempFile {
         let value: serde_json::Value =
             serde_json::from_str(SIMPLE_JSON_STR).expect("parse simple.json");
@@ -1078,6 +1216,7 @@ mod tests {
         temp_file_with(suffix, &msgpack_buf)
     }
 
+    #[cfg(feature = "msgpack")]
     #[test]
     fn msgpack_scalar_field() {
         let tmp = msgpack_temp(".msgpack");
@@ -1090,6 +1229,7 @@ mod tests {
         assert_eq!(output.trim(), json_reference("age").trim());
     }
 
+    #[cfg(feature = "msgpack")]
     #[test]
     fn msgpack_nested_field() {
         let tmp = msgpack_temp(".msgpack");
@@ -1106,6 +1246,7 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "msgpack")]
     #[test]
     fn msgpack_explicit_format_flag() {
         let tmp = msgpack_temp(".bin");
@@ -1225,4 +1366,562 @@ mod tests {
         run_main(&["age", SIMPLE_JSON_FILEPATH, "--count", "--depth"])
             .failure();
     }
+
+    /// Parses `--json` output into its events.
+    fn json_events(stdout: &[u8]) -> Vec<Value> {
+        String::from_utf8(stdout.to_vec())
+            .expect("Invalid UTF-8 output")
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("JSON event"))
+            .collect()
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn json_emits_events_per_file() {
+        let a = temp_file_with(".json", br#"{"x": {"y.z": [1]}}"#);
+        let b = temp_file_with(".yaml", b"x: 2\n");
+        let a_path = a.path().to_str().expect("path").to_string();
+        let b_path = b.path().to_str().expect("path").to_string();
+
+        let assert = run_main(&["x", &a_path, &b_path, "--json"]).success();
+        let events = json_events(&assert.get_output().stdout);
+
+        let types: Vec<_> = events
+            .iter()
+            .map(|event| event["type"].as_str().expect("type"))
+            .collect();
+        assert_eq!(
+            types,
+            ["begin", "match", "end", "begin", "match", "end", "summary"]
+        );
+        assert_eq!(events[0]["data"]["file"], a_path.as_str());
+        assert_eq!(events[3]["data"]["format"], "yaml");
+        assert_eq!(events[1]["data"]["path"], serde_json::json!(["x"]));
+        assert_eq!(events[1]["data"]["value"], serde_json::json!({"y.z": [1]}));
+        assert_eq!(events[4]["data"]["value"], 2);
+        assert_eq!(events[6]["data"]["matches"], 2);
+    }
+
+    #[test]
+    fn json_paths_are_segment_arrays() {
+        let assert = Command::cargo_bin("jg")
+            .expect("Failed to find main binary")
+            .args(["(* | [*])*.n", "--json"])
+            .write_stdin(r#"{"a.b": [{"n": null}]}"#)
+            .assert()
+            .success();
+        let events = json_events(&assert.get_output().stdout);
+        assert_eq!(events[0]["data"]["file"], "(standard input)");
+        assert_eq!(
+            events[1]["data"]["path"],
+            serde_json::json!(["a.b", 0, "n"])
+        );
+        assert_eq!(events.last().expect("summary")["type"], "summary");
+    }
+
+    #[test]
+    fn json_conflicts_with_count() {
+        run_main(&["age", SIMPLE_JSON_FILEPATH, "--json", "--count"]).failure();
+    }
+
+    #[test]
+    fn path_format_selects_header_syntax() {
+        for (format, header) in [
+            ("jg", r#"a.[0]."b.c":"#),
+            ("pointer", "/a/0/b.c:"),
+            ("jsonpath", "$.a[0]['b.c']:"),
+            ("jq", r#".a[0]["b.c"]:"#),
+            ("array", r#"["a",0,"b.c"]:"#),
+        ] {
+            let assert = Command::cargo_bin("jg")
+                .expect("Failed to find main binary")
+                .args(["a[0].\"b.c\"", "--with-path", "--path-format", format])
+                .write_stdin(r#"{"a": [{"b.c": 1}]}"#)
+                .assert()
+                .success();
+            let output = String::from_utf8(assert.get_output().stdout.clone())
+                .expect("Invalid UTF-8 output");
+            assert_eq!(output, format!("{header}\n1\n"), "{format}");
+        }
+    }
+
+    #[test]
+    fn set_replaces_matches_in_the_whole_document() {
+        let file = temp_file_with(
+            ".json",
+            br#"{"services": {"web": {"image": "nginx:1.25"}, "db": {"port": 1}}}"#,
+        );
+        let path = file.path().to_str().expect("path");
+
+        let assert = run_main(&[
+            "--compact",
+            "--set",
+            "services.*.image",
+            r#""{{value}}-patched""#,
+            "--set",
+            "services.db.port",
+            "5432",
+            path,
+        ])
+        .success();
+        let output = String::from_utf8(assert.get_output().stdout.clone())
+            .expect("Invalid UTF-8 output");
+        assert_eq!(
+            output,
+            r#"{"services":{"web":{"image":"nginx:1.25-patched"},"db":{"port":5432}}}"#
+                .to_string()
+                + "\n"
+        );
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn set_reads_other_formats_and_reports_no_match() {
+        Command::cargo_bin("jg")
+            .expect("Failed to find main binary")
+            .args(["--compact", "--set", "a", "[1]", "-f", "yaml"])
+            .write_stdin("a: 1\nb: 2\n")
+            .assert()
+            .success()
+            .stdout("{\"a\":[1],\"b\":2}\n");
+
+        run_main(&["--compact", "--set", "nope", "1", SIMPLE_JSON_FILEPATH])
+            .code(1);
+    }
+
+    #[test]
+    fn edits_keep_jsonl_one_record_per_line() {
+        let file =
+            temp_file_with(".jsonl", b"{\"a\": 1}\n{\"a\": 2, \"b\": 3}\n");
+        let path = file.path().to_str().unwrap();
+        run_main(&["--set", "[*].a", "0", "--delete", "[*].b", path])
+            .success()
+            .stdout("{\"a\":0}\n{\"a\":0}\n");
+
+        Command::cargo_bin("jg")
+            .expect("Failed to find main binary")
+            .args(["-f", "jsonl", "--delete", "[0]"])
+            .write_stdin("1\n2\n")
+            .assert()
+            .success()
+            .stdout("2\n");
+    }
+
+    #[test]
+    fn set_rejects_invalid_values_and_several_inputs() {
+        run_main(&["--set", "name", "bare", SIMPLE_JSON_FILEPATH]).code(2);
+        run_main(&[
+            "--set",
+            "name",
+            "1",
+            SIMPLE_JSON_FILEPATH,
+            SIMPLE_JSON_FILEPATH,
+        ])
+        .code(2);
+    }
+
+    #[test]
+    fn delete_removes_matches_and_prints_the_rest() {
+        Command::cargo_bin("jg")
+            .expect("Failed to find main binary")
+            .args([
+                "--compact",
+                "--delete",
+                "(* | [*])*.(password | token)",
+                "--delete",
+                "items.([0] | [2])",
+            ])
+            .write_stdin(
+                r#"{"user": {"password": "x", "id": 1}, "items": [0, 1, 2, 3], "token": "t"}"#,
+            )
+            .assert()
+            .success()
+            .stdout("{\"user\":{\"id\":1},\"items\":[1,3]}\n");
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn prune_keeps_the_shape_of_matched_paths() {
+        let file = temp_file_with(
+            ".yaml",
+            b"kind: Deployment\nspec:\n  replicas: 3\n  template:\n    spec:\n      containers:\n        - name: a\n          image: x\n        - name: b\n",
+        );
+        let path = file.path().to_str().expect("path");
+
+        let assert = run_main(&[
+            "--prune",
+            "spec.(replicas | template.spec.containers[*].image)",
+            path,
+            "--compact",
+        ])
+        .success();
+        let output = String::from_utf8(assert.get_output().stdout.clone())
+            .expect("Invalid UTF-8 output");
+        assert_eq!(
+            output.trim(),
+            r#"{"spec":{"replicas":3,"template":{"spec":{"containers":[{"image":"x"}]}}}}"#
+        );
+
+        run_main(&["--keep", "nope", path]).code(1).stdout("");
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn patch_prints_edits_that_patch_apply_replays() {
+        let doc = temp_file_with(
+            ".yaml",
+            b"services:\n  web: {image: nginx}\n  db: {image: pg, password: x}\n",
+        );
+        let doc_path = doc.path().to_str().expect("path");
+
+        let assert = run_main(&[
+            "--set",
+            "services.*.image",
+            r#""{{value}}:latest""#,
+            "--delete",
+            "services.*.password",
+            "--patch",
+            "--compact",
+            doc_path,
+        ])
+        .success();
+        let patch = String::from_utf8(assert.get_output().stdout.clone())
+            .expect("utf-8");
+        assert_eq!(
+            patch,
+            concat!(
+                r#"[{"op":"replace","path":"/services/web/image","value":"nginx:latest"},"#,
+                r#"{"op":"replace","path":"/services/db/image","value":"pg:latest"},"#,
+                r#"{"op":"remove","path":"/services/db/password"}]"#,
+                "\n"
+            )
+        );
+
+        let patch_file = temp_file_with(".json", patch.as_bytes());
+        let patch_path = patch_file.path().to_str().expect("path");
+        run_main(&["patch", "apply", patch_path, doc_path, "--compact"])
+            .success()
+            .stdout(
+                "{\"services\":{\"web\":{\"image\":\"nginx:latest\"},\"db\":{\"image\":\"pg:latest\"}}}\n",
+            );
+    }
+
+    #[test]
+    fn patch_apply_reads_stdin_and_reports_failed_operations() {
+        let patch_file = temp_file_with(
+            ".json",
+            br#"[{"op": "add", "path": "/a/-", "value": 2}, {"op": "test", "path": "/b", "value": 1}]"#,
+        );
+        let patch_path = patch_file.path().to_str().expect("path");
+
+        let assert = Command::cargo_bin("jg")
+            .expect("Failed to find main binary")
+            .args(["patch", "apply", patch_path])
+            .write_stdin(r#"{"a": [1], "b": 2}"#)
+            .assert()
+            .code(2)
+            .stdout("");
+        let stderr = String::from_utf8(assert.get_output().stderr.clone())
+            .expect("utf-8");
+        assert!(stderr.contains("Patch operation 1 failed"), "{stderr:?}");
+
+        run_main(&["--patch", "a", SIMPLE_JSON_FILEPATH]).code(2);
+    }
+
+    #[cfg(all(feature = "yaml", feature = "toml"))]
+    #[test]
+    fn preserve_format_edits_only_the_matched_values() {
+        let file = temp_file_with(
+            ".toml",
+            b"# Package metadata.\n[package]\nname = \"demo\" # the crate\nversion = \"0.1.0\"\n\n[dependencies]\nserde = \"1\"\nanyhow = \"1\"\n",
+        );
+        let path = file.path().to_str().expect("path");
+
+        run_main(&[
+            "--preserve-format",
+            "--set",
+            "package.version",
+            r#""0.2.0""#,
+            "--delete",
+            "dependencies.anyhow",
+            path,
+        ])
+        .success()
+        .stdout(
+            "# Package metadata.\n[package]\nname = \"demo\" # the crate\nversion = \"0.2.0\"\n\n[dependencies]\nserde = \"1\"\n",
+        );
+
+        Command::cargo_bin("jg")
+            .expect("Failed to find main binary")
+            .args(["--preserve-format", "-f", "yaml", "--set", "*.tag", "2"])
+            .write_stdin(
+                "image:   # the app\n  tag: 1 # pinned\n  pull: Always\n",
+            )
+            .assert()
+            .success()
+            .stdout("image:   # the app\n  tag: 2 # pinned\n  pull: Always\n");
+    }
+
+    #[test]
+    fn preserve_format_rejects_other_formats() {
+        let assert = run_main(&[
+            "--preserve-format",
+            "--set",
+            "name",
+            "1",
+            SIMPLE_JSON_FILEPATH,
+        ])
+        .code(2)
+        .stdout("");
+        let stderr = String::from_utf8(assert.get_output().stderr.clone())
+            .expect("utf-8");
+        assert!(stderr.contains("only TOML and YAML"), "{stderr:?}");
+
+        run_main(&["--preserve-format", "name", SIMPLE_JSON_FILEPATH]).code(2);
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn redact_keeps_structure_and_types() {
+        Command::cargo_bin("jg")
+            .expect("Failed to find main binary")
+            .args(["--compact", "--redact", "user", "--redact", "[*]"])
+            .write_stdin(
+                r#"{"user": {"email": "a@b.c", "age": 42, "admin": true, "tags": ["x", null]}, "id": 7}"#,
+            )
+            .assert()
+            .success()
+            .stdout(
+                "{\"user\":{\"email\":\"[REDACTED]\",\"age\":0,\"admin\":true,\"tags\":[\"[REDACTED]\",null]},\"id\":7}\n",
+            );
+
+        let file = temp_file_with(
+            ".yaml",
+            b"db:\n  host: prod\n  password: hunter2\n",
+        );
+        let path = file.path().to_str().expect("path");
+        run_main(&[
+            "--compact",
+            "--redact",
+            "db.password",
+            "--redact-placeholder",
+            "***",
+            path,
+        ])
+        .success()
+        .stdout("{\"db\":{\"host\":\"prod\",\"password\":\"***\"}}\n");
+
+        run_main(&["--redact", "missing", SIMPLE_JSON_FILEPATH]).code(1);
+        run_main(&["--redact-placeholder", "***", SIMPLE_JSON_FILEPATH])
+            .code(2);
+    }
+
+    #[test]
+    fn redact_key_hashes_equal_values_to_equal_tokens() {
+        let file = temp_file_with(
+            ".jsonl",
+            b"{\"email\": \"a@b.c\"}\n{\"email\": \"d@e.f\"}\n{\"email\": \"a@b.c\"}\n",
+        );
+        let path = file.path().to_str().expect("path");
+        let redacted = |key: &str| {
+            let assert =
+                run_main(&["--redact", "[*].email", "--redact-key", key, path])
+                    .success();
+            String::from_utf8(assert.get_output().stdout.clone())
+                .expect("utf-8")
+        };
+
+        let out = redacted("secret");
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 3, "{out:?}");
+        assert!(lines[0].starts_with(r#"{"email":"sha256:"#), "{out:?}");
+        assert_eq!(lines[0], lines[2]);
+        assert_ne!(lines[0], lines[1]);
+        assert_eq!(redacted("secret"), out);
+        assert_ne!(redacted("other"), out);
+    }
+
+    #[test]
+    fn redact_streams_jsonl_records() {
+        Command::cargo_bin("jg")
+            .expect("Failed to find main binary")
+            .args(["-f", "jsonl", "--redact", "[1].token"])
+            .write_stdin(
+                "{\"token\": \"t0\"}\n\n{\"token\": \"t1\", \"n\": 1}\n{\"token\": \"t2\"}\n",
+            )
+            .assert()
+            .success()
+            .stdout(
+                "{\"token\":\"t0\"}\n{\"token\":\"[REDACTED]\",\"n\":1}\n{\"token\":\"t2\"}\n",
+            );
+
+        let assert = Command::cargo_bin("jg")
+            .expect("Failed to find main binary")
+            .args(["-f", "jsonl", "--redact", "[*].a"])
+            .write_stdin("{\"a\": 1}\nnot json\n")
+            .assert()
+            .code(2)
+            .stdout("{\"a\":0}\n");
+        let stderr = String::from_utf8(assert.get_output().stderr.clone())
+            .expect("utf-8");
+        assert!(stderr.contains("Failed to parse JSONL line 2"), "{stderr:?}");
+    }
+
+    #[cfg(all(feature = "yaml", feature = "toml"))]
+    #[test]
+    fn output_format_writes_each_match_as_a_document() {
+        run_main(&["-o", "yaml", "hobbies[*]", SIMPLE_JSON_FILEPATH])
+            .success()
+            .stdout("fishing\n---\nyoga\n");
+        run_main(&["-o", "jsonl", "name | age", SIMPLE_JSON_FILEPATH])
+            .success()
+            .stdout("{\"first\":\"John\",\"last\":\"Doe\"}\n32\n");
+        run_main(&["-o", "toml", "name", SIMPLE_JSON_FILEPATH])
+            .success()
+            .stdout("first = \"John\"\nlast = \"Doe\"\n");
+
+        let assert = run_main(&["-o", "toml", "age", SIMPLE_JSON_FILEPATH])
+            .code(2)
+            .stdout("");
+        let stderr = String::from_utf8(assert.get_output().stderr.clone())
+            .expect("utf-8");
+        assert!(
+            stderr.contains("a TOML document must be a table"),
+            "{stderr:?}"
+        );
+
+        run_main(&["-o", "yaml", "--set", "age", "33", SIMPLE_JSON_FILEPATH])
+            .success()
+            .stdout(
+                "name:\n  first: John\n  last: Doe\nage: 33\nhobbies:\n- fishing\n- yoga\n",
+            );
+        run_main(&["-o", "yaml", "-r", "age", SIMPLE_JSON_FILEPATH]).code(2);
+    }
+
+    #[cfg(all(feature = "yaml", feature = "toml", feature = "cbor"))]
+    #[test]
+    fn convert_round_trips_between_formats() {
+        let cbor = Command::cargo_bin("jg")
+            .expect("Failed to find main binary")
+            .args(["convert", "-o", "cbor", SIMPLE_JSON_FILEPATH])
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+        let toml = Command::cargo_bin("jg")
+            .expect("Failed to find main binary")
+            .args(["convert", "-f", "cbor", "-o", "toml", "--compact"])
+            .write_stdin(cbor)
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+        assert_eq!(
+            String::from_utf8(toml.clone()).expect("utf-8"),
+            "age = 32\nhobbies = [\"fishing\", \"yoga\"]\n\n[name]\nfirst = \"John\"\nlast = \"Doe\"\n"
+        );
+        Command::cargo_bin("jg")
+            .expect("Failed to find main binary")
+            .args(["convert", "-f", "toml", "--compact"])
+            .write_stdin(toml)
+            .assert()
+            .success()
+            .stdout(
+                "{\"age\":32,\"hobbies\":[\"fishing\",\"yoga\"],\"name\":{\"first\":\"John\",\"last\":\"Doe\"}}\n",
+            );
+
+        let file = temp_file_with(".jsonl", b"{\"a\": 1}\n\n[2]\n");
+        let path = file.path().to_str().expect("path");
+        run_main(&["convert", "-o", "jsonl", path])
+            .success()
+            .stdout("{\"a\":1}\n[2]\n");
+        run_main(&["convert", "-o", "yaml", path])
+            .success()
+            .stdout("- a: 1\n- - 2\n");
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn flatten_prints_a_line_per_leaf_that_unflatten_reads_back() {
+        let flat = "name.first = \"John\"\nname.last = \"Doe\"\nage = 32\nhobbies.[0] = \"fishing\"\nhobbies.[1] = \"yoga\"\n";
+        run_main(&["--flatten", SIMPLE_JSON_FILEPATH]).success().stdout(flat);
+
+        Command::cargo_bin("jg")
+            .expect("Failed to find main binary")
+            .args(["--unflatten", "--compact"])
+            .write_stdin(flat)
+            .assert()
+            .success()
+            .stdout(
+                "{\"name\":{\"first\":\"John\",\"last\":\"Doe\"},\"age\":32,\"hobbies\":[\"fishing\",\"yoga\"]}\n",
+            );
+
+        let file = temp_file_with(".yaml", b"a: {}\n\"b c\": [null]\n");
+        let path = file.path().to_str().expect("path");
+        run_main(&["--flatten", path, SIMPLE_JSON_FILEPATH]).success().stdout(
+            format!(
+                "{path}:a = {{}}\n{path}:\"b c\".[0] = null\n\
+                 {SIMPLE_JSON_FILEPATH}:name.first = \"John\"\n\
+                 {SIMPLE_JSON_FILEPATH}:name.last = \"Doe\"\n\
+                 {SIMPLE_JSON_FILEPATH}:age = 32\n\
+                 {SIMPLE_JSON_FILEPATH}:hobbies.[0] = \"fishing\"\n\
+                 {SIMPLE_JSON_FILEPATH}:hobbies.[1] = \"yoga\"\n"
+            ),
+        );
+    }
+
+    #[test]
+    fn unflatten_reports_the_bad_line() {
+        let assert = Command::cargo_bin("jg")
+            .expect("Failed to find main binary")
+            .args(["--unflatten"])
+            .write_stdin("a = 1\na.b = 2\n")
+            .assert()
+            .code(2)
+            .stdout("");
+        let stderr = String::from_utf8(assert.get_output().stderr.clone())
+            .expect("utf-8");
+        assert!(stderr.contains("Failed to unflatten line 2"), "{stderr:?}");
+
+        run_main(&["--flatten", "--count", SIMPLE_JSON_FILEPATH]).code(2);
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn diff_reports_changes_by_path_across_formats() {
+        let new = temp_file_with(
+            ".yaml",
+            b"age: 33
+name:
+  last: Doe
+  first: John
+hobbies: [fishing]
+",
+        );
+        let new = new.path().to_str().expect("path");
+        run_main(&["diff", SIMPLE_JSON_FILEPATH, new])
+            .code(1)
+            .stdout("~ age = 32 -> 33\n- hobbies.[1] = \"yoga\"\n");
+        run_main(&["diff", SIMPLE_JSON_FILEPATH, new, "name"])
+            .success()
+            .stdout("");
+        run_main(&["diff", SIMPLE_JSON_FILEPATH, new, "--json", "--compact"])
+            .code(1)
+            .stdout(
+                "[{\"op\":\"changed\",\"path\":[\"age\"],\"old\":32,\"new\":33},{\"op\":\"removed\",\"path\":[\"hobbies\",1],\"value\":\"yoga\"}]\n",
+            );
+        run_main(&[
+            "diff",
+            SIMPLE_JSON_FILEPATH,
+            new,
+            "age",
+            "--patch",
+            "--compact",
+        ])
+        .code(1)
+        .stdout("[{\"op\":\"replace\",\"path\":\"/age\",\"value\":33}]\n");
+    }
 }